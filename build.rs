@@ -17,12 +17,16 @@ fn main() {
     // Indica a Cargo que vuelva a ejecutar este script si el archivo `halt.S` cambia.
     println!("cargo:rerun-if-changed=src/arch/x86_64/halt.S");
     println!("cargo:rerun-if-changed=src/arch/x86_64/interrupts.S");
+    println!("cargo:rerun-if-changed=src/arch/x86_64/switch.S");
+    println!("cargo:rerun-if-changed=src/arch/x86_64/syscall.S");
 
     // Usa la crate `cc` para invocar al compilador C (que también maneja ensamblador)
     // y compilar `halt.S` en un objeto que se enlazará con el kernel.
     cc::Build::new()
         .file("src/arch/x86_64/halt.S")
         .file("src/arch/x86_64/interrupts.S")
+        .file("src/arch/x86_64/switch.S")
+        .file("src/arch/x86_64/syscall.S")
         .compile("asm"); // El resultado se llamará `libasm.a`
 
     // --- Tarea 2: Procesamiento de la imagen del logo ---
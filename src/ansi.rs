@@ -0,0 +1,319 @@
+//! Intérprete de secuencias de escape ANSI/VT100.
+//!
+//! Convierte un flujo de caracteres en una serie de `Action`: caracteres que
+//! hay que imprimir, caracteres de control y órdenes de las secuencias de
+//! escape reconocidas. Quien escribe en pantalla (la consola o el
+//! `FramebufferWriter`) decide cómo aplicar cada acción.
+//!
+//! Secuencias soportadas:
+//! - SGR (`ESC [ ... m`): reinicio, negrita, colores de 16, 256 y 24 bits
+//!   para primer plano y fondo, y vuelta a los colores por defecto.
+//! - Movimiento del cursor: `CUU`/`CUD`/`CUF`/`CUB` (`A`/`B`/`C`/`D`),
+//!   `CNL`/`CPL` (`E`/`F`), `CHA` (`G`) y `CUP`/`HVP` (`H`/`f`).
+//! - Borrado: `EL` (`K`) y `ED` (`J`), en sus modos 0, 1 y 2.
+//! - Guardar y restaurar el cursor: `ESC 7`/`ESC 8` y `CSI s`/`CSI u`.
+//!
+//! Las secuencias desconocidas se descartan sin mostrar nada.
+
+use core::fmt;
+
+use crate::colors::Color;
+
+/// Número máximo de parámetros numéricos de una secuencia CSI.
+const MAX_PARAMS: usize = 16;
+
+/// Paleta de los 16 colores básicos (0-7 normales, 8-15 brillantes).
+const PALETTE: [Color; 16] = [
+    0x00000000, 0x00CD3131, 0x000DBC79, 0x00E5E510, 0x002472C8, 0x00BC3FBC, 0x0011A8CD, 0x00E5E5E5,
+    0x00666666, 0x00F14C4C, 0x0023D18B, 0x00F5F543, 0x003B8EEA, 0x00D670D6, 0x0029B8DB, 0x00FFFFFF,
+];
+
+/// Qué parte de la línea o de la pantalla borra `EL`/`ED`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Erase {
+    /// Desde el cursor hasta el final (modo 0).
+    ToEnd,
+    /// Desde el principio hasta el cursor, incluido (modo 1).
+    ToStart,
+    /// Todo (modo 2).
+    All,
+}
+
+/// Una acción producida por el intérprete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Imprime un carácter.
+    Print(char),
+    /// Ejecuta un carácter de control (`\n`, `\r`, `\t`, retroceso...).
+    Control(char),
+    /// Cambia el color de primer plano (`None`: color por defecto).
+    Foreground(Option<Color>),
+    /// Cambia el color de fondo (`None`: color por defecto).
+    Background(Option<Color>),
+    /// Mueve el cursor relativamente: `(columnas, filas)`, positivas hacia la derecha y abajo.
+    MoveCursor(isize, isize),
+    /// Mueve el cursor a la columna dada de la fila actual (desde 0).
+    CursorColumn(usize),
+    /// Mueve el cursor a la posición `(columna, fila)` (desde 0).
+    CursorPosition(usize, usize),
+    /// Borra parte de la línea del cursor.
+    EraseInLine(Erase),
+    /// Borra parte de la pantalla.
+    EraseInDisplay(Erase),
+    /// Guarda la posición del cursor y los colores actuales.
+    SaveCursor,
+    /// Restaura lo guardado con `SaveCursor`.
+    RestoreCursor,
+}
+
+/// Estado del intérprete.
+///
+/// Es un entero y no un `enum`: ver [`Parser`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct State(u8);
+
+impl State {
+    /// Texto normal.
+    const GROUND: State = State(0);
+    /// Se ha leído `ESC`.
+    const ESCAPE: State = State(1);
+    /// Dentro de una secuencia `ESC [`.
+    const CSI: State = State(2);
+    /// Secuencia que se descarta hasta su carácter final.
+    const IGNORE: State = State(3);
+}
+
+/// Bit de `Parser::flags`: la secuencia CSI lleva un prefijo privado (`?`, `>`...).
+const PRIVATE: u8 = 1 << 0;
+/// Bit de `Parser::flags`: la negrita está activa y los colores básicos se
+/// muestran brillantes.
+const BOLD: u8 = 1 << 1;
+
+/// Intérprete incremental de secuencias de escape.
+///
+/// Conserva su estado entre llamadas, de modo que una secuencia puede llegar
+/// partida entre varias escrituras. Todos sus campos empiezan a cero y
+/// ninguno tiene valores prohibidos (no hay `bool` ni `enum`): si no, el
+/// compilador codificaría en ellos el `None` del `Option<FramebufferWriter>`
+/// de la consola, que dejaría de ser todo ceros y de vivir en `.bss`.
+pub struct Parser {
+    state: State,
+    params: [u16; MAX_PARAMS],
+    /// Número de parámetros empezados (el actual incluido).
+    param_count: usize,
+    /// Bits [`PRIVATE`] y [`BOLD`].
+    flags: u8,
+}
+
+impl Parser {
+    /// Crea un intérprete en estado inicial.
+    pub const fn new() -> Self {
+        Self {
+            state: State::GROUND,
+            params: [0; MAX_PARAMS],
+            param_count: 0,
+            flags: 0,
+        }
+    }
+
+    /// Activa o desactiva el bit `flag` de `flags`.
+    fn set_flag(&mut self, flag: u8, on: bool) {
+        if on { self.flags |= flag } else { self.flags &= !flag }
+    }
+
+    /// Procesa un carácter y entrega a `perform` las acciones que produzca.
+    pub fn advance(&mut self, c: char, mut perform: impl FnMut(Action)) {
+        match self.state {
+            State::GROUND => match c {
+                '\x1b' => self.state = State::ESCAPE,
+                c if c.is_control() => perform(Action::Control(c)),
+                c => perform(Action::Print(c)),
+            },
+            State::ESCAPE => {
+                self.state = State::GROUND;
+                match c {
+                    '[' => {
+                        self.params = [0; MAX_PARAMS];
+                        self.param_count = 0;
+                        self.set_flag(PRIVATE, false);
+                        self.state = State::CSI;
+                    }
+                    '7' => perform(Action::SaveCursor),
+                    '8' => perform(Action::RestoreCursor),
+                    'c' => {
+                        self.set_flag(BOLD, false);
+                        perform(Action::Foreground(None));
+                        perform(Action::Background(None));
+                        perform(Action::EraseInDisplay(Erase::All));
+                        perform(Action::CursorPosition(0, 0));
+                    }
+                    _ => {}
+                }
+            }
+            State::CSI => match c {
+                '0'..='9' => {
+                    if self.param_count == 0 {
+                        self.param_count = 1;
+                    }
+                    if let Some(param) = self.params.get_mut(self.param_count - 1) {
+                        *param = param.saturating_mul(10).saturating_add(c as u16 - b'0' as u16);
+                    }
+                }
+                ';' | ':' => self.param_count = (self.param_count.max(1) + 1).min(MAX_PARAMS + 1),
+                '<'..='?' => self.set_flag(PRIVATE, true),
+                '\x20'..='\x2F' => self.state = State::IGNORE,
+                '\x40'..='\x7E' => {
+                    self.state = State::GROUND;
+                    if self.flags & PRIVATE == 0 {
+                        self.dispatch_csi(c, &mut perform);
+                    }
+                }
+                // Un carácter inesperado cancela la secuencia.
+                _ => self.state = State::GROUND,
+            },
+            State::IGNORE => {
+                if ('\x40'..='\x7E').contains(&c) {
+                    self.state = State::GROUND;
+                }
+            }
+            // Ningún otro valor se guarda nunca.
+            _ => self.state = State::GROUND,
+        }
+    }
+
+    /// Parámetros de la secuencia actual.
+    fn params(&self) -> &[u16] {
+        &self.params[..self.param_count.min(MAX_PARAMS)]
+    }
+
+    /// Parámetro `index`, o `default` si falta o es 0.
+    fn param_or(&self, index: usize, default: u16) -> u16 {
+        match self.params().get(index) {
+            Some(&value) if value != 0 => value,
+            _ => default,
+        }
+    }
+
+    /// Ejecuta una secuencia CSI completa con carácter final `command`.
+    fn dispatch_csi(&mut self, command: char, perform: &mut impl FnMut(Action)) {
+        let count = self.param_or(0, 1) as isize;
+        match command {
+            'A' => perform(Action::MoveCursor(0, -count)),
+            'B' => perform(Action::MoveCursor(0, count)),
+            'C' => perform(Action::MoveCursor(count, 0)),
+            'D' => perform(Action::MoveCursor(-count, 0)),
+            'E' => {
+                perform(Action::MoveCursor(0, count));
+                perform(Action::CursorColumn(0));
+            }
+            'F' => {
+                perform(Action::MoveCursor(0, -count));
+                perform(Action::CursorColumn(0));
+            }
+            'G' => perform(Action::CursorColumn(self.param_or(0, 1) as usize - 1)),
+            'H' | 'f' => {
+                let row = self.param_or(0, 1) as usize - 1;
+                let column = self.param_or(1, 1) as usize - 1;
+                perform(Action::CursorPosition(column, row));
+            }
+            'J' | 'K' => {
+                let erase = match self.params().first().copied().unwrap_or(0) {
+                    0 => Erase::ToEnd,
+                    1 => Erase::ToStart,
+                    // El modo 3 (borrar también el historial) se trata como el 2.
+                    _ => Erase::All,
+                };
+                perform(if command == 'J' { Action::EraseInDisplay(erase) } else { Action::EraseInLine(erase) });
+            }
+            's' => perform(Action::SaveCursor),
+            'u' => perform(Action::RestoreCursor),
+            'm' => self.dispatch_sgr(perform),
+            _ => {}
+        }
+    }
+
+    /// Ejecuta una secuencia SGR (Select Graphic Rendition).
+    fn dispatch_sgr(&mut self, perform: &mut impl FnMut(Action)) {
+        let mut params = [0u16; MAX_PARAMS];
+        let count = self.params().len();
+        params[..count].copy_from_slice(self.params());
+        // `ESC [ m` equivale a `ESC [ 0 m`.
+        let params = if count == 0 { &params[..1] } else { &params[..count] };
+
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => {
+                    self.set_flag(BOLD, false);
+                    perform(Action::Foreground(None));
+                    perform(Action::Background(None));
+                }
+                1 => self.set_flag(BOLD, true),
+                22 => self.set_flag(BOLD, false),
+                code @ 30..=37 => {
+                    let index = (code - 30) as usize + if self.flags & BOLD != 0 { 8 } else { 0 };
+                    perform(Action::Foreground(Some(PALETTE[index])));
+                }
+                39 => perform(Action::Foreground(None)),
+                code @ 40..=47 => perform(Action::Background(Some(PALETTE[(code - 40) as usize]))),
+                49 => perform(Action::Background(None)),
+                code @ 90..=97 => perform(Action::Foreground(Some(PALETTE[(code - 90) as usize + 8]))),
+                code @ 100..=107 => perform(Action::Background(Some(PALETTE[(code - 100) as usize + 8]))),
+                code @ (38 | 48) => {
+                    let (color, used) = extended_color(&params[i + 1..]);
+                    i += used;
+                    if let Some(color) = color {
+                        perform(if code == 38 { Action::Foreground(Some(color)) } else { Action::Background(Some(color)) });
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+}
+
+/// Interpreta los parámetros que siguen a un `38`/`48` de SGR: `5;n` (paleta
+/// de 256 colores) o `2;r;g;b` (color de 24 bits). Devuelve el color y el
+/// número de parámetros consumidos.
+fn extended_color(params: &[u16]) -> (Option<Color>, usize) {
+    match params {
+        [5, index, ..] => (Some(color_256(*index as u8)), 2),
+        [2, r, g, b, ..] => {
+            let channel = |value: u16| value.min(255) as Color;
+            (Some(channel(*r) << 16 | channel(*g) << 8 | channel(*b)), 4)
+        }
+        _ => (None, params.len()),
+    }
+}
+
+/// Devuelve el color de la paleta xterm de 256 colores con el índice dado.
+pub fn color_256(index: u8) -> Color {
+    const LEVELS: [Color; 6] = [0x00, 0x5F, 0x87, 0xAF, 0xD7, 0xFF];
+    match index {
+        0..=15 => PALETTE[index as usize],
+        16..=231 => {
+            let index = index - 16;
+            let r = LEVELS[(index / 36) as usize];
+            let g = LEVELS[(index / 6 % 6) as usize];
+            let b = LEVELS[(index % 6) as usize];
+            r << 16 | g << 8 | b
+        }
+        232..=255 => {
+            let level = 8 + 10 * (index - 232) as Color;
+            level << 16 | level << 8 | level
+        }
+    }
+}
+
+/// Secuencia SGR que cambia el color de primer plano a un color de 24 bits.
+///
+/// Permite colorear texto dentro de un mismo `write!`, por ejemplo
+/// `write!(writer, "{}etiqueta {}valor", Fg(colors::TEXT_SECONDARY), Fg(colors::TEXT_PRIMARY))`.
+pub struct Fg(pub Color);
+
+impl fmt::Display for Fg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\x1b[38;2;{};{};{}m", (self.0 >> 16) & 0xFF, (self.0 >> 8) & 0xFF, self.0 & 0xFF)
+    }
+}
@@ -0,0 +1,64 @@
+//! La lógica de la aplicación `cpuinfo`.
+
+use core::fmt::Write;
+use crate::arch::target::cpuid::{self, Feature};
+use crate::colors;
+use crate::console::Console;
+
+/// Número de características que se muestran por línea.
+const FEATURES_PER_LINE: usize = 8;
+
+/// Ejecuta la aplicación `cpuinfo`.
+///
+/// Escribe en la posición actual del cursor toda la información del
+/// procesador obtenida mediante `cpuid`.
+pub fn run(console: &mut Console) {
+    let info = cpuid::info();
+
+    draw_label(console, "Fabricante:");
+    writeln!(console, "{}", info.vendor()).unwrap();
+    draw_label(console, "Modelo:");
+    writeln!(console, "{}", info.brand()).unwrap();
+    draw_label(console, "Firma:");
+    writeln!(console, "familia {}, modelo {}, stepping {}", info.family, info.model, info.stepping).unwrap();
+    draw_label(console, "Núcleos:");
+    writeln!(console, "{} lógicos", info.logical_cores).unwrap();
+
+    draw_label(console, "Cachés:");
+    if info.caches.is_empty() {
+        writeln!(console, "no disponibles").unwrap();
+    } else {
+        writeln!(console).unwrap();
+        for cache in &info.caches {
+            write!(console, "  L{} {:<14} {:>6} KiB", cache.level, cache.kind.name(), cache.size_kb).unwrap();
+            if cache.ways != 0 {
+                write!(console, ", {} vías", cache.ways).unwrap();
+            }
+            writeln!(console, ", línea de {} B", cache.line_size).unwrap();
+        }
+    }
+
+    draw_label(console, "Características:");
+    writeln!(console).unwrap();
+    let mut shown = 0;
+    for feature in Feature::ALL.iter().filter(|f| info.has(**f)) {
+        if shown % FEATURES_PER_LINE == 0 {
+            write!(console, " ").unwrap();
+        }
+        write!(console, " {}", feature.name()).unwrap();
+        shown += 1;
+        if shown % FEATURES_PER_LINE == 0 {
+            writeln!(console).unwrap();
+        }
+    }
+    if shown % FEATURES_PER_LINE != 0 {
+        writeln!(console).unwrap();
+    }
+}
+
+/// Escribe una etiqueta con el color secundario y deja el color primario para el valor.
+fn draw_label(console: &mut Console, label: &str) {
+    console.set_color(colors::TEXT_SECONDARY);
+    write!(console, "{:<17}", label).unwrap();
+    console.set_color(colors::TEXT_PRIMARY);
+}
@@ -0,0 +1,122 @@
+//! La lógica de la aplicación `fbbench`.
+//!
+//! Mide el rendimiento de las primitivas de dibujo del `FramebufferWriter`
+//! para que una regresión se note en cuanto aparece: rellenos de pantalla
+//! completa, copia del búfer trasero a la pantalla y dibujo de texto.
+
+use core::fmt::Write;
+use core::time::Duration;
+use profont::PROFONT_14_POINT;
+
+use crate::colors;
+use crate::console::Console;
+use crate::time;
+use crate::vga::FramebufferWriter;
+
+/// Tiempo durante el que se repite cada prueba.
+const TEST_DURATION: Duration = Duration::from_millis(500);
+/// Colores que alternan las pruebas, para que cada iteración cambie la pantalla.
+const COLORS: [colors::Color; 2] = [colors::COSMIC_BLUE, colors::DEEP_BLACK];
+
+/// Resultado de una prueba: iteraciones completadas y tiempo empleado.
+struct Measure {
+    iterations: u64,
+    elapsed: Duration,
+}
+
+impl Measure {
+    /// Ritmo por segundo de algo de lo que cada iteración procesa `amount` unidades.
+    fn rate(&self, amount: u64) -> u64 {
+        let millis = self.elapsed.as_millis().max(1) as u64;
+        self.iterations * amount * 1000 / millis
+    }
+}
+
+/// Resultados de todas las pruebas.
+struct Report {
+    width: usize,
+    height: usize,
+    fill: Measure,
+    frame: Measure,
+    text: Measure,
+    /// Celdas de texto dibujadas en cada iteración de la prueba de texto.
+    cells: u64,
+    /// Aciertos y fallos de la caché de glifos al terminar.
+    glyphs: Option<(u64, u64)>,
+}
+
+/// Repite `step` hasta agotar `TEST_DURATION`. `step` recibe el número de
+/// iteración.
+fn measure(writer: &mut FramebufferWriter, mut step: impl FnMut(&mut FramebufferWriter, u64)) -> Measure {
+    let start = time::uptime();
+    let mut iterations = 0;
+    while time::uptime() - start < TEST_DURATION {
+        step(writer, iterations);
+        iterations += 1;
+    }
+    Measure { iterations, elapsed: time::uptime() - start }
+}
+
+/// Ejecuta la aplicación `fbbench` y escribe los resultados en la consola.
+pub fn run(console: &mut Console) {
+    let report = console.fullscreen(|writer| {
+        let (width, height) = (writer.width(), writer.height());
+
+        // Relleno de la pantalla completa, sin copiarla a la memoria de vídeo.
+        let fill = measure(writer, |writer, i| writer.clear(COLORS[i as usize % 2]));
+        writer.present();
+
+        // Relleno y copia a la pantalla: un fotograma completo.
+        let frame = measure(writer, |writer, i| {
+            writer.clear(COLORS[i as usize % 2]);
+            writer.present();
+        });
+
+        // Una pantalla llena de celdas de texto por iteración.
+        let font = &PROFONT_14_POINT;
+        let cell_width = (font.character_size.width + font.character_spacing) as usize;
+        let cell_height = font.character_size.height as usize;
+        let (columns, rows) = (width / cell_width, height / cell_height);
+        let text = measure(writer, |writer, i| {
+            for row in 0..rows {
+                for column in 0..columns {
+                    let c = (b'!' + ((row * columns + column + i as usize) % 94) as u8) as char;
+                    let fg = [colors::TEXT_PRIMARY, colors::NEON_GREEN][(row + i as usize) % 2];
+                    writer.draw_cell(column * cell_width, row * cell_height, c, font, fg, colors::BACKGROUND_COLOR);
+                }
+            }
+            writer.present();
+        });
+
+        let cells = (columns * rows) as u64;
+        Report { width, height, fill, frame, text, cells, glyphs: writer.glyph_cache_stats() }
+    });
+
+    let pixels = (report.width * report.height) as u64;
+    writeln!(console, "Resolución {}x{}, pruebas de {} ms:", report.width, report.height, TEST_DURATION.as_millis()).unwrap();
+    writeln!(
+        console,
+        "  relleno:   {:>5} pantallas/s, {:>5} Mpx/s",
+        report.fill.rate(1),
+        report.fill.rate(pixels) / 1_000_000
+    )
+    .unwrap();
+    writeln!(
+        console,
+        "  fotograma: {:>5} pantallas/s, {:>5} Mpx/s (relleno y copia a la pantalla)",
+        report.frame.rate(1),
+        report.frame.rate(pixels) / 1_000_000
+    )
+    .unwrap();
+    writeln!(
+        console,
+        "  texto:     {:>5} pantallas/s, {:>5} kglifos/s",
+        report.text.rate(1),
+        report.text.rate(report.cells) / 1000
+    )
+    .unwrap();
+    match report.glyphs {
+        Some((hits, misses)) => writeln!(console, "  caché de glifos: {} aciertos, {} fallos", hits, misses).unwrap(),
+        None => writeln!(console, "  caché de glifos: no disponible").unwrap(),
+    }
+}
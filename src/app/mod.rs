@@ -1,3 +1,5 @@
 //! Contiene aplicaciones de alto nivel que pueden ser llamadas por la shell o el kernel.
 
+pub mod cpuinfo_app;
+pub mod fbbench_app;
 pub mod vesperfetch_app;
\ No newline at end of file
@@ -1,32 +1,33 @@
 //! La lógica de la aplicación VesperFetch.
 
+use core::fmt::Write;
+
+use crate::arch::target::cpuid;
+use crate::arch::target::keyboard::KeyStream;
 use crate::colors;
-use crate::MEMMAP_REQUEST;
+use crate::time;
+use crate::memory;
 use crate::vesperfetch;
 use crate::vga::FramebufferWriter;
 
 /// Ejecuta la aplicación VesperFetch.
 ///
 /// Esta función borra la pantalla, recopila información del sistema y la
-/// muestra utilizando el módulo VesperFetch.
-pub fn run(writer: &mut FramebufferWriter) {
+/// muestra utilizando el módulo VesperFetch. Termina cuando el usuario pulsa
+/// una tecla.
+pub async fn run(writer: &mut FramebufferWriter, keys: &mut KeyStream) {
     writer.clear(colors::BACKGROUND_COLOR);
 
-    let mut total_memory: u64 = 0;
-    if let Some(memmap_response) = MEMMAP_REQUEST.get_response() {
-        for entry in memmap_response.entries() {
-            if entry.entry_type == limine::memory_map::EntryType::USABLE {
-                total_memory += entry.length;
-            }
-        }
-    }
+    let (used_memory, total_memory) = memory::frame_stats()
+        .map_or((0, 0), |stats| (stats.used_bytes(), stats.total_bytes()));
 
     let sys_info = vesperfetch::SystemInfo {
         os_name: "Vesper OS",
         os_version: "0.1.0 (Nocturna)",
         kernel_version: "Vesper-Core",
-        cpu_info: "N/A", // Placeholder
-        uptime: "N/A",   // Placeholder
+        cpu_info: cpuid::info().brand(),
+        uptime: time::uptime(),
+        memory_used_mb: used_memory / 1024 / 1024,
         memory_total_mb: total_memory / 1024 / 1024,
         resolution_width: writer.width() as u64,
         resolution_height: writer.height() as u64,
@@ -38,4 +39,13 @@ pub fn run(writer: &mut FramebufferWriter) {
 
     let fetch = vesperfetch::VesperFetch::new(sys_info);
     fetch.display(writer, fetch_x, fetch_y);
+
+    // Pausa hasta que el usuario presione una tecla.
+    writer.set_cursor_position(20, writer.height() - 40);
+    writer.set_color(colors::TEXT_SECONDARY);
+    let _ = write!(writer, "[Presiona cualquier tecla para continuar]");
+    writer.present();
+
+    keys.discard_pending();
+    keys.next().await;
 }
\ No newline at end of file
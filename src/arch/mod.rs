@@ -18,7 +18,32 @@ pub fn init() {
     target::init();
 }
 
+/// Sustituye las tablas de páginas de Limine por las del kernel. Requiere el
+/// asignador de marcos físicos.
+pub fn init_paging() {
+    target::init_paging();
+}
+
 /// Pone la CPU en un estado de bajo consumo hasta la próxima interrupción.
 pub fn wait_for_interrupt() {
     target::wait_for_interrupt();
+}
+
+/// Habilita las interrupciones y espera a la próxima. Sirve para dormir tras
+/// comprobar, con las interrupciones deshabilitadas, que no hay trabajo.
+pub fn enable_interrupts_and_wait() {
+    target::enable_interrupts_and_wait();
+}
+
+/// Ejecuta `f` con las interrupciones deshabilitadas y restaura el estado previo.
+pub fn without_interrupts<F: FnOnce() -> R, R>(f: F) -> R {
+    target::without_interrupts(f)
+}
+
+/// Frecuencia del temporizador del sistema, en ticks por segundo.
+pub const TIMER_HZ: u64 = target::TIMER_HZ;
+
+/// Devuelve el número de ticks del temporizador del sistema desde el arranque.
+pub fn timer_ticks() -> u64 {
+    target::timer_ticks()
 }
\ No newline at end of file
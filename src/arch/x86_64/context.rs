@@ -0,0 +1,79 @@
+//! Contexto de ejecución de un hilo del kernel.
+//!
+//! El cambio de contexto está escrito en ensamblador en `switch.S`: guarda
+//! los registros que el ABI obliga a preservar en la pila del hilo que se
+//! abandona y los recupera de la pila del hilo al que se cambia. Por eso el
+//! contexto de un hilo parado es, sobre todo, su puntero de pila.
+//!
+//! Al cambiar de hilo también se activan su espacio de direcciones y su pila
+//! del kernel, la que usa la CPU al entrar desde modo usuario.
+
+use super::{cpu, paging, syscall};
+
+unsafe extern "C" {
+    fn switch_context(from_rsp: *mut u64, to_rsp: u64);
+    fn thread_trampoline();
+}
+
+/// Estado guardado de un hilo que no se está ejecutando.
+#[derive(Debug)]
+pub struct Context {
+    /// Puntero de pila en el momento en que el hilo dejó la CPU.
+    rsp: u64,
+    /// Cima de la pila propia del hilo, o 0 si no tiene (el hilo de arranque).
+    kernel_stack: u64,
+    /// PML4 del espacio de direcciones del hilo, o 0 para el del kernel.
+    root: u64,
+}
+
+impl Context {
+    /// Contexto del hilo que ya se está ejecutando; se rellena la primera vez
+    /// que deja la CPU.
+    pub const fn current() -> Self {
+        Self { rsp: 0, kernel_stack: 0, root: 0 }
+    }
+
+    /// Prepara la pila de un hilo nuevo, cuya cima es `stack_top`, para que
+    /// el primer cambio a él ejecute `entry(argument)` con las interrupciones
+    /// deshabilitadas.
+    ///
+    /// # Safety
+    ///
+    /// `stack_top` tiene que ser la cima, alineada a 16 bytes, de una pila
+    /// mapeada y sin usar.
+    pub unsafe fn new(stack_top: u64, entry: extern "C" fn(usize) -> !, argument: usize) -> Self {
+        // Marco que desapila `switch_context`: r15, r14, r13, r12, rbx, rbp
+        // y la dirección de retorno.
+        let frame = [0, 0, entry as *const () as u64, argument as u64, 0, 0, thread_trampoline as *const () as u64];
+        let rsp = stack_top - size_of_val(&frame) as u64;
+        unsafe { (rsp as *mut [u64; 7]).write(frame) };
+        Self { rsp, kernel_stack: stack_top, root: 0 }
+    }
+
+    /// Hace que el hilo se ejecute en el espacio de direcciones cuya PML4
+    /// está en `root`, que tiene que compartir la mitad del kernel.
+    pub fn set_address_space(&mut self, root: u64) {
+        self.root = root;
+    }
+}
+
+/// Guarda el estado del hilo actual en `from` y continúa la ejecución en el
+/// hilo guardado en `to`. Vuelve cuando otro cambio de contexto reanuda `from`.
+///
+/// # Safety
+///
+/// Hay que llamarla con las interrupciones deshabilitadas, y ambos contextos
+/// tienen que seguir existiendo hasta que el cambio termine.
+pub unsafe fn switch(from: *mut Context, to: *const Context) {
+    let (kernel_stack, root) = unsafe { ((*to).kernel_stack, (*to).root) };
+    if kernel_stack != 0 {
+        syscall::set_kernel_stack(kernel_stack);
+    }
+    let root = if root != 0 { root } else { paging::kernel_root() };
+    if cpu::read_cr3() & paging::ROOT_MASK != root {
+        // Ambos espacios comparten la mitad del kernel, donde está todo lo
+        // que se usa a continuación.
+        unsafe { cpu::write_cr3(root) };
+    }
+    unsafe { switch_context(&raw mut (*from).rsp, (*to).rsp) };
+}
@@ -25,6 +25,19 @@ pub fn hlt() {
     }
 }
 
+/// Habilita las interrupciones y detiene la CPU hasta la próxima (`sti; hlt`).
+///
+/// `sti` no tiene efecto hasta después de la instrucción siguiente, así que
+/// ninguna interrupción puede colarse entre las dos: si el llamador comprobó
+/// con las interrupciones deshabilitadas que no había nada que hacer, la
+/// interrupción que cambie eso despertará a la CPU.
+#[inline]
+pub fn enable_interrupts_and_hlt() {
+    unsafe {
+        asm!("sti", "hlt", options(nomem, nostack));
+    }
+}
+
 /// Lee un byte del puerto de E/S especificado.
 ///
 /// # Safety
@@ -38,4 +51,154 @@ pub unsafe fn inb(port: u16) -> u8 {
         asm!("in al, dx", in("dx") port, out("al") value, options(nomem, nostack, preserves_flags));
     }
     value
-}
\ No newline at end of file
+}
+/// Lee el registro de control CR0 (modo protegido, paginación, caché).
+#[inline]
+pub fn read_cr0() -> u64 {
+    let value: u64;
+    unsafe {
+        asm!("mov {}, cr0", out(reg) value, options(nomem, nostack, preserves_flags));
+    }
+    value
+}
+
+/// Lee el registro de control CR2.
+///
+/// Tras un fallo de página (#PF), contiene la dirección virtual que lo provocó.
+#[inline]
+pub fn read_cr2() -> u64 {
+    let value: u64;
+    unsafe {
+        asm!("mov {}, cr2", out(reg) value, options(nomem, nostack, preserves_flags));
+    }
+    value
+}
+
+/// Lee el registro de control CR3 (dirección física de la tabla PML4 activa).
+#[inline]
+pub fn read_cr3() -> u64 {
+    let value: u64;
+    unsafe {
+        asm!("mov {}, cr3", out(reg) value, options(nomem, nostack, preserves_flags));
+    }
+    value
+}
+
+/// Lee el registro de control CR4 (extensiones de la CPU como PAE, SMEP o SMAP).
+#[inline]
+pub fn read_cr4() -> u64 {
+    let value: u64;
+    unsafe {
+        asm!("mov {}, cr4", out(reg) value, options(nomem, nostack, preserves_flags));
+    }
+    value
+}
+
+/// Escribe el registro de control CR0.
+///
+/// # Safety
+///
+/// CR0 controla la paginación y la protección de memoria; un valor incorrecto
+/// deja la CPU en un estado inconsistente.
+#[inline]
+pub unsafe fn write_cr0(value: u64) {
+    unsafe {
+        asm!("mov cr0, {}", in(reg) value, options(nostack, preserves_flags));
+    }
+}
+
+/// Carga una nueva tabla PML4 en CR3, lo que también vacía la TLB.
+///
+/// # Safety
+///
+/// `value` debe ser la dirección física de una PML4 válida que mapee, como
+/// mínimo, el código, la pila y los datos que se usan a continuación.
+#[inline]
+pub unsafe fn write_cr3(value: u64) {
+    unsafe {
+        asm!("mov cr3, {}", in(reg) value, options(nostack, preserves_flags));
+    }
+}
+
+/// Invalida la entrada de la TLB de la página que contiene `address`.
+#[inline]
+pub fn invlpg(address: u64) {
+    unsafe {
+        asm!("invlpg [{}]", in(reg) address, options(nostack, preserves_flags));
+    }
+}
+
+/// Lee un registro específico del modelo (MSR).
+///
+/// # Safety
+///
+/// Leer un MSR que no existe provoca una excepción de protección general.
+#[inline]
+pub unsafe fn rdmsr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    unsafe {
+        asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+    }
+    (high as u64) << 32 | low as u64
+}
+
+/// Escribe un registro específico del modelo (MSR).
+///
+/// # Safety
+///
+/// Los MSR controlan el comportamiento de la CPU; el registro debe existir y
+/// el valor debe ser válido para él.
+#[inline]
+pub unsafe fn wrmsr(msr: u32, value: u64) {
+    unsafe {
+        asm!("wrmsr", in("ecx") msr, in("eax") value as u32, in("edx") (value >> 32) as u32, options(nostack, preserves_flags));
+    }
+}
+
+/// Escribe un byte en el puerto de E/S especificado.
+///
+/// # Safety
+///
+/// Escribir en un puerto de E/S es una operación privilegiada que actúa
+/// directamente sobre el hardware. El puerto y el valor deben ser válidos.
+#[inline]
+pub unsafe fn outb(port: u16, value: u8) {
+    unsafe {
+        asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack, preserves_flags));
+    }
+}
+
+/// Deshabilita las interrupciones de hardware (`cli`).
+#[inline]
+pub fn disable_interrupts() {
+    unsafe {
+        asm!("cli", options(nomem, nostack));
+    }
+}
+
+/// Indica si las interrupciones de hardware están habilitadas (bit IF de RFLAGS).
+#[inline]
+pub fn interrupts_enabled() -> bool {
+    let rflags: u64;
+    unsafe {
+        asm!("pushfq", "pop {}", out(reg) rflags, options(nomem, preserves_flags));
+    }
+    rflags & (1 << 9) != 0
+}
+
+/// Ejecuta `f` con las interrupciones deshabilitadas y restaura el estado previo.
+///
+/// Sirve para tomar un lock que también usa un manejador de interrupciones
+/// sin que este pueda dispararse mientras lo tenemos (lo que bloquearía la CPU).
+#[inline]
+pub fn without_interrupts<F: FnOnce() -> R, R>(f: F) -> R {
+    let were_enabled = interrupts_enabled();
+    if were_enabled {
+        disable_interrupts();
+    }
+    let result = f();
+    if were_enabled {
+        unsafe { enable_interrupts() };
+    }
+    result
+}
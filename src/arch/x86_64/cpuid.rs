@@ -0,0 +1,374 @@
+//! Identificación del procesador mediante la instrucción `cpuid`.
+//!
+//! Decodifica el fabricante, la cadena de marca, la familia/modelo/stepping,
+//! el número de procesadores lógicos, la jerarquía de cachés y las
+//! características soportadas. La información se obtiene una sola vez y se
+//! guarda en una instancia global accesible con `info()`.
+
+use core::arch::x86_64::{CpuidResult, __cpuid_count};
+use lazy_static::lazy_static;
+
+/// Número máximo de niveles de caché que se registran.
+const MAX_CACHES: usize = 8;
+
+/// Tipo de una caché de la CPU.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheKind {
+    /// Caché de datos.
+    Data,
+    /// Caché de instrucciones.
+    Instruction,
+    /// Caché unificada (datos e instrucciones).
+    Unified,
+}
+
+impl CacheKind {
+    /// Devuelve el nombre corto del tipo de caché.
+    pub fn name(self) -> &'static str {
+        match self {
+            CacheKind::Data => "datos",
+            CacheKind::Instruction => "instrucciones",
+            CacheKind::Unified => "unificada",
+        }
+    }
+}
+
+/// Descripción de una caché de la CPU.
+#[derive(Debug, Clone, Copy)]
+pub struct CacheInfo {
+    /// Nivel de la caché (L1, L2, L3...).
+    pub level: u8,
+    /// Tipo de la caché.
+    pub kind: CacheKind,
+    /// Tamaño total en KiB.
+    pub size_kb: u32,
+    /// Asociatividad (vías). 0 indica totalmente asociativa o desconocida.
+    pub ways: u32,
+    /// Tamaño de la línea de caché en bytes.
+    pub line_size: u32,
+}
+
+/// Características de la CPU que el kernel sabe detectar.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Feature {
+    Fpu,
+    Tsc,
+    Apic,
+    Sse,
+    Sse2,
+    Sse3,
+    Ssse3,
+    Sse41,
+    Sse42,
+    Popcnt,
+    Aes,
+    Xsave,
+    Avx,
+    Avx2,
+    Avx512f,
+    Rdrand,
+    Rdseed,
+    X2apic,
+    Nx,
+    Pages1G,
+    Rdtscp,
+    Smep,
+    Smap,
+    Umip,
+    Hypervisor,
+}
+
+impl Feature {
+    /// Todas las características detectables, en el orden en que se listan.
+    pub const ALL: [Feature; 25] = [
+        Feature::Fpu, Feature::Tsc, Feature::Apic,
+        Feature::Sse, Feature::Sse2, Feature::Sse3, Feature::Ssse3, Feature::Sse41, Feature::Sse42,
+        Feature::Popcnt, Feature::Aes, Feature::Xsave,
+        Feature::Avx, Feature::Avx2, Feature::Avx512f,
+        Feature::Rdrand, Feature::Rdseed, Feature::X2apic,
+        Feature::Nx, Feature::Pages1G, Feature::Rdtscp,
+        Feature::Smep, Feature::Smap, Feature::Umip,
+        Feature::Hypervisor,
+    ];
+
+    /// Devuelve el nombre de la característica tal y como lo muestra `cpuinfo`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Fpu => "fpu",
+            Feature::Tsc => "tsc",
+            Feature::Apic => "apic",
+            Feature::Sse => "sse",
+            Feature::Sse2 => "sse2",
+            Feature::Sse3 => "sse3",
+            Feature::Ssse3 => "ssse3",
+            Feature::Sse41 => "sse4.1",
+            Feature::Sse42 => "sse4.2",
+            Feature::Popcnt => "popcnt",
+            Feature::Aes => "aes",
+            Feature::Xsave => "xsave",
+            Feature::Avx => "avx",
+            Feature::Avx2 => "avx2",
+            Feature::Avx512f => "avx512f",
+            Feature::Rdrand => "rdrand",
+            Feature::Rdseed => "rdseed",
+            Feature::X2apic => "x2apic",
+            Feature::Nx => "nx",
+            Feature::Pages1G => "pdpe1gb",
+            Feature::Rdtscp => "rdtscp",
+            Feature::Smep => "smep",
+            Feature::Smap => "smap",
+            Feature::Umip => "umip",
+            Feature::Hypervisor => "hypervisor",
+        }
+    }
+
+    /// Bit que ocupa la característica en el conjunto `CpuInfo::features`.
+    fn bit(self) -> u64 {
+        1 << (self as u64)
+    }
+}
+
+/// Información del procesador obtenida con `cpuid`.
+pub struct CpuInfo {
+    vendor: [u8; 12],
+    brand: [u8; 48],
+    /// Familia efectiva (familia base + familia extendida).
+    pub family: u32,
+    /// Modelo efectivo (modelo base + modelo extendido).
+    pub model: u32,
+    /// Revisión (stepping) del procesador.
+    pub stepping: u32,
+    /// Número de procesadores lógicos por paquete.
+    pub logical_cores: u32,
+    /// Cachés detectadas, de menor a mayor nivel.
+    pub caches: heapless::Vec<CacheInfo, MAX_CACHES>,
+    /// Conjunto de bits de las `Feature` soportadas.
+    features: u64,
+}
+
+/// Ejecuta `cpuid` con la hoja y subhoja indicadas.
+fn cpuid(leaf: u32, subleaf: u32) -> CpuidResult {
+    __cpuid_count(leaf, subleaf)
+}
+
+/// Devuelve el valor del bit `bit` de `value`.
+fn bit(value: u32, bit: u32) -> bool {
+    value & (1 << bit) != 0
+}
+
+impl CpuInfo {
+    /// Consulta a la CPU y decodifica toda la información disponible.
+    fn detect() -> Self {
+        let mut info = CpuInfo {
+            vendor: [0; 12],
+            brand: [0; 48],
+            family: 0,
+            model: 0,
+            stepping: 0,
+            logical_cores: 1,
+            caches: heapless::Vec::new(),
+            features: 0,
+        };
+
+        // Hoja 0: hoja básica máxima y fabricante (en el orden EBX, EDX, ECX).
+        let leaf0 = cpuid(0, 0);
+        let max_leaf = leaf0.eax;
+        info.vendor[0..4].copy_from_slice(&leaf0.ebx.to_le_bytes());
+        info.vendor[4..8].copy_from_slice(&leaf0.edx.to_le_bytes());
+        info.vendor[8..12].copy_from_slice(&leaf0.ecx.to_le_bytes());
+
+        let max_extended_leaf = cpuid(0x8000_0000, 0).eax;
+
+        if max_leaf >= 1 {
+            let leaf1 = cpuid(1, 0);
+            info.decode_signature(leaf1.eax);
+            // EBX[23:16] solo es válido si la CPU indica Hyper-Threading (EDX bit 28).
+            if bit(leaf1.edx, 28) {
+                info.logical_cores = ((leaf1.ebx >> 16) & 0xFF).max(1);
+            }
+            info.set(Feature::Fpu, bit(leaf1.edx, 0));
+            info.set(Feature::Tsc, bit(leaf1.edx, 4));
+            info.set(Feature::Apic, bit(leaf1.edx, 9));
+            info.set(Feature::Sse, bit(leaf1.edx, 25));
+            info.set(Feature::Sse2, bit(leaf1.edx, 26));
+            info.set(Feature::Sse3, bit(leaf1.ecx, 0));
+            info.set(Feature::Ssse3, bit(leaf1.ecx, 9));
+            info.set(Feature::Sse41, bit(leaf1.ecx, 19));
+            info.set(Feature::Sse42, bit(leaf1.ecx, 20));
+            info.set(Feature::X2apic, bit(leaf1.ecx, 21));
+            info.set(Feature::Popcnt, bit(leaf1.ecx, 23));
+            info.set(Feature::Aes, bit(leaf1.ecx, 25));
+            info.set(Feature::Xsave, bit(leaf1.ecx, 26));
+            info.set(Feature::Avx, bit(leaf1.ecx, 28));
+            info.set(Feature::Rdrand, bit(leaf1.ecx, 30));
+            info.set(Feature::Hypervisor, bit(leaf1.ecx, 31));
+        }
+
+        if max_leaf >= 7 {
+            let leaf7 = cpuid(7, 0);
+            info.set(Feature::Avx2, bit(leaf7.ebx, 5));
+            info.set(Feature::Smep, bit(leaf7.ebx, 7));
+            info.set(Feature::Avx512f, bit(leaf7.ebx, 16));
+            info.set(Feature::Rdseed, bit(leaf7.ebx, 18));
+            info.set(Feature::Smap, bit(leaf7.ebx, 20));
+            info.set(Feature::Umip, bit(leaf7.ecx, 2));
+        }
+
+        let mut has_topology_extensions = false;
+        if max_extended_leaf >= 0x8000_0001 {
+            let ext1 = cpuid(0x8000_0001, 0);
+            info.set(Feature::Nx, bit(ext1.edx, 20));
+            info.set(Feature::Pages1G, bit(ext1.edx, 26));
+            info.set(Feature::Rdtscp, bit(ext1.edx, 27));
+            has_topology_extensions = bit(ext1.ecx, 22);
+        }
+
+        // Hojas 0x80000002-0x80000004: cadena de marca de 48 bytes.
+        if max_extended_leaf >= 0x8000_0004 {
+            for (i, leaf) in (0x8000_0002..=0x8000_0004).enumerate() {
+                let regs = cpuid(leaf, 0);
+                for (j, reg) in [regs.eax, regs.ebx, regs.ecx, regs.edx].iter().enumerate() {
+                    let offset = i * 16 + j * 4;
+                    info.brand[offset..offset + 4].copy_from_slice(&reg.to_le_bytes());
+                }
+            }
+        }
+
+        // Cachés: Intel usa la hoja 4; AMD la 0x8000001D (mismo formato) si
+        // tiene extensiones de topología, o las hojas heredadas 0x80000005/6.
+        if info.vendor() == "GenuineIntel" && max_leaf >= 4 {
+            info.detect_deterministic_caches(4);
+        } else if has_topology_extensions && max_extended_leaf >= 0x8000_001D {
+            info.detect_deterministic_caches(0x8000_001D);
+        } else if max_extended_leaf >= 0x8000_0006 {
+            info.detect_legacy_amd_caches();
+        }
+
+        info
+    }
+
+    /// Calcula familia, modelo y stepping a partir de EAX de la hoja 1.
+    fn decode_signature(&mut self, eax: u32) {
+        let base_family = (eax >> 8) & 0xF;
+        let base_model = (eax >> 4) & 0xF;
+        self.stepping = eax & 0xF;
+        self.family = if base_family == 0xF { base_family + ((eax >> 20) & 0xFF) } else { base_family };
+        self.model = if base_family == 0x6 || base_family == 0xF {
+            base_model | (((eax >> 16) & 0xF) << 4)
+        } else {
+            base_model
+        };
+    }
+
+    /// Recorre las subhojas de una hoja de parámetros de caché deterministas.
+    fn detect_deterministic_caches(&mut self, leaf: u32) {
+        for subleaf in 0..MAX_CACHES as u32 {
+            let regs = cpuid(leaf, subleaf);
+            let kind = match regs.eax & 0x1F {
+                1 => CacheKind::Data,
+                2 => CacheKind::Instruction,
+                3 => CacheKind::Unified,
+                _ => break, // 0 = no hay más cachés.
+            };
+            let line_size = (regs.ebx & 0xFFF) + 1;
+            let partitions = ((regs.ebx >> 12) & 0x3FF) + 1;
+            let ways = ((regs.ebx >> 22) & 0x3FF) + 1;
+            let sets = regs.ecx + 1;
+            let fully_associative = bit(regs.eax, 9);
+            let _ = self.caches.push(CacheInfo {
+                level: ((regs.eax >> 5) & 0x7) as u8,
+                kind,
+                size_kb: ways * partitions * line_size * sets / 1024,
+                ways: if fully_associative { 0 } else { ways },
+                line_size,
+            });
+        }
+    }
+
+    /// Lee las cachés de las hojas heredadas de AMD (0x80000005 y 0x80000006).
+    fn detect_legacy_amd_caches(&mut self) {
+        /// Traduce la asociatividad codificada de la hoja 0x80000006.
+        fn l2_l3_ways(encoded: u32) -> u32 {
+            match encoded {
+                0x1 => 1, 0x2 => 2, 0x4 => 4, 0x6 => 8, 0x8 => 16, 0xA => 32,
+                0xB => 48, 0xC => 64, 0xD => 96, 0xE => 128,
+                _ => 0,
+            }
+        }
+
+        let l1 = cpuid(0x8000_0005, 0);
+        for (reg, kind) in [(l1.ecx, CacheKind::Data), (l1.edx, CacheKind::Instruction)] {
+            if reg >> 24 != 0 {
+                let ways = (reg >> 16) & 0xFF;
+                let _ = self.caches.push(CacheInfo {
+                    level: 1,
+                    kind,
+                    size_kb: reg >> 24,
+                    ways: if ways == 0xFF { 0 } else { ways },
+                    line_size: reg & 0xFF,
+                });
+            }
+        }
+
+        let l2_l3 = cpuid(0x8000_0006, 0);
+        if l2_l3.ecx >> 16 != 0 {
+            let _ = self.caches.push(CacheInfo {
+                level: 2,
+                kind: CacheKind::Unified,
+                size_kb: l2_l3.ecx >> 16,
+                ways: l2_l3_ways((l2_l3.ecx >> 12) & 0xF),
+                line_size: l2_l3.ecx & 0xFF,
+            });
+        }
+        if l2_l3.edx >> 18 != 0 {
+            let _ = self.caches.push(CacheInfo {
+                level: 3,
+                kind: CacheKind::Unified,
+                // EDX[31:18] expresa el tamaño en unidades de 512 KiB.
+                size_kb: (l2_l3.edx >> 18) * 512,
+                ways: l2_l3_ways((l2_l3.edx >> 12) & 0xF),
+                line_size: l2_l3.edx & 0xFF,
+            });
+        }
+    }
+
+    /// Marca o desmarca una característica.
+    fn set(&mut self, feature: Feature, present: bool) {
+        if present {
+            self.features |= feature.bit();
+        } else {
+            self.features &= !feature.bit();
+        }
+    }
+
+    /// Indica si la CPU soporta la característica dada.
+    pub fn has(&self, feature: Feature) -> bool {
+        self.features & feature.bit() != 0
+    }
+
+    /// Devuelve el identificador del fabricante (ej. "GenuineIntel", "AuthenticAMD").
+    pub fn vendor(&self) -> &str {
+        core::str::from_utf8(&self.vendor).unwrap_or("Desconocido")
+    }
+
+    /// Devuelve la cadena de marca del procesador, sin espacios ni nulos de relleno.
+    ///
+    /// Si la CPU no la proporciona, devuelve el identificador del fabricante.
+    pub fn brand(&self) -> &str {
+        let brand = core::str::from_utf8(&self.brand)
+            .unwrap_or("")
+            .trim_end_matches('\0')
+            .trim();
+        if brand.is_empty() { self.vendor() } else { brand }
+    }
+}
+
+lazy_static! {
+    /// Información del procesador, detectada la primera vez que se consulta.
+    static ref CPU_INFO: CpuInfo = CpuInfo::detect();
+}
+
+/// Devuelve la información del procesador en el que se ejecuta el kernel.
+pub fn info() -> &'static CpuInfo {
+    &CPU_INFO
+}
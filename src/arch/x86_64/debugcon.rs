@@ -0,0 +1,24 @@
+//! Salida de depuración por el puerto 0xE9 ("debugcon" de QEMU y Bochs).
+//!
+//! Cada byte escrito en el puerto aparece en la consola de depuración del
+//! emulador (`-debugcon file:...` o `-debugcon stdio` en QEMU). No necesita
+//! inicialización, por lo que sirve incluso cuando el kernel está en pánico.
+//! En hardware real el puerto no está conectado y las escrituras se pierden.
+
+use core::fmt;
+use super::cpu;
+
+/// Puerto de E/S de la consola de depuración.
+const DEBUGCON_PORT: u16 = 0xE9;
+
+/// Escritor de texto sobre la consola de depuración.
+pub struct DebugCon;
+
+impl fmt::Write for DebugCon {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            unsafe { cpu::outb(DEBUGCON_PORT, byte) };
+        }
+        Ok(())
+    }
+}
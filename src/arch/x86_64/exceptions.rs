@@ -0,0 +1,209 @@
+//! Manejo de las excepciones de la CPU (vectores 0-31).
+//!
+//! Los stubs de `interrupts.S` guardan el estado completo de la CPU en un
+//! `ExceptionFrame` y llaman a `rust_exception_handler`. Una excepción
+//! provocada por un programa en modo usuario solo termina ese proceso; las
+//! del kernel son fatales: se pinta una pantalla de diagnóstico con los
+//! registros y se detiene el sistema.
+
+use super::cpu;
+use crate::colors;
+use crate::fault::FaultScreen;
+use crate::{log, process};
+
+/// Vector de la interrupción no enmascarable (NMI).
+pub const NMI_VECTOR: usize = 2;
+/// Vector de la excepción de doble fallo (#DF).
+pub const DOUBLE_FAULT_VECTOR: usize = 8;
+/// Vector de la excepción de fallo de página (#PF).
+pub const PAGE_FAULT_VECTOR: usize = 14;
+/// Vector de la excepción Machine Check (#MC).
+pub const MACHINE_CHECK_VECTOR: usize = 18;
+
+/// Estado de la CPU en el momento de una excepción.
+///
+/// El orden de los campos coincide con el de la pila construida por
+/// `exception_common` en `interrupts.S`: primero los registros guardados por el
+/// stub (de la dirección más baja a la más alta) y luego el marco que apila la CPU.
+#[derive(Debug)]
+#[repr(C)]
+pub struct ExceptionFrame {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rbp: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64,
+    /// Número de vector de la excepción (0-31).
+    pub vector: u64,
+    /// Código de error apilado por la CPU, o 0 si la excepción no tiene.
+    pub error_code: u64,
+    // --- Apilado por la CPU ---
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+/// Mnemónico y descripción de cada excepción, indexados por vector.
+const EXCEPTIONS: [(&str, &str); 32] = [
+    ("#DE", "Error de división"),
+    ("#DB", "Depuración"),
+    ("NMI", "Interrupción no enmascarable"),
+    ("#BP", "Breakpoint"),
+    ("#OF", "Overflow"),
+    ("#BR", "Rango excedido"),
+    ("#UD", "Opcode inválido"),
+    ("#NM", "Dispositivo no disponible"),
+    ("#DF", "Doble fallo"),
+    ("---", "Coprocessor Segment Overrun"),
+    ("#TS", "TSS inválido"),
+    ("#NP", "Segmento no presente"),
+    ("#SS", "Fallo del segmento de pila"),
+    ("#GP", "Protección general"),
+    ("#PF", "Fallo de página"),
+    ("---", "Reservado"),
+    ("#MF", "Error de coma flotante x87"),
+    ("#AC", "Comprobación de alineación"),
+    ("#MC", "Machine Check"),
+    ("#XM", "Excepción de coma flotante SIMD"),
+    ("#VE", "Excepción de virtualización"),
+    ("#CP", "Protección de control de flujo"),
+    ("---", "Reservado"),
+    ("---", "Reservado"),
+    ("---", "Reservado"),
+    ("---", "Reservado"),
+    ("---", "Reservado"),
+    ("---", "Reservado"),
+    ("#HV", "Inyección del hipervisor"),
+    ("#VC", "Comunicación con el VMM"),
+    ("#SX", "Seguridad"),
+    ("---", "Reservado"),
+];
+
+/// Indica si la CPU apila un código de error para el vector dado.
+fn has_error_code(vector: u64) -> bool {
+    matches!(vector, 8 | 10..=14 | 17 | 21 | 29 | 30)
+}
+
+/// Indica si el código de error del vector dado es un selector de segmento:
+/// solo en #TS, #NP, #SS y #GP. Los demás se muestran sin decodificar.
+fn has_selector_error(vector: u64) -> bool {
+    matches!(vector, 10..=13)
+}
+
+/// Manejador común en Rust para todas las excepciones de la CPU.
+/// Esta función es llamada desde `exception_common` en `interrupts.S`.
+#[unsafe(no_mangle)] // Requerido por la edición 2024 para atributos `extern`.
+pub extern "C" fn rust_exception_handler(frame: &ExceptionFrame) {
+    // CR2 se lee antes que nada: cualquier otro fallo de página lo sobrescribiría.
+    let cr2 = cpu::read_cr2();
+    let fatal = matches!(frame.vector as usize, NMI_VECTOR | DOUBLE_FAULT_VECTOR | MACHINE_CHECK_VECTOR);
+    if frame.cs & 3 == 3 && !fatal {
+        kill_process(frame, cr2);
+    }
+    show_fault_screen(frame, cr2);
+    crate::hcf();
+}
+
+/// Termina el proceso de usuario que provocó la excepción.
+fn kill_process(frame: &ExceptionFrame, cr2: u64) -> ! {
+    let (mnemonic, name) = EXCEPTIONS[(frame.vector & 31) as usize];
+    if frame.vector == PAGE_FAULT_VECTOR as u64 {
+        log::error!("{} {} en {:#x} accediendo a {:#x}", mnemonic, name, frame.rip, cr2);
+    } else {
+        log::error!("{} {} en {:#x}", mnemonic, name, frame.rip);
+    }
+    // Estamos en la pila del kernel del proceso, que no se libera hasta que
+    // el planificador lo elimine tras cambiar a otro hilo. Como al salir por
+    // una llamada al sistema, las interrupciones quedan habilitadas: liberar
+    // el proceso puede esperar a locks que tengan otros hilos.
+    unsafe { cpu::enable_interrupts() };
+    process::exit(-1)
+}
+
+/// Pinta el informe de la excepción con todos los registros capturados.
+fn show_fault_screen(frame: &ExceptionFrame, cr2: u64) {
+    let mut screen = FaultScreen::open("Excepción de la CPU");
+
+    let (mnemonic, name) = EXCEPTIONS[(frame.vector & 31) as usize];
+    screen.line(colors::ERROR_COLOR, format_args!("{} {} (vector {})", mnemonic, name, frame.vector));
+    screen.gap();
+
+    screen.field("RIP", format_args!("{:#018x}", frame.rip));
+    screen.field("RSP", format_args!("{:#018x}", frame.rsp));
+    screen.field("RFLAGS", format_args!("{:#018x}", frame.rflags));
+    screen.field("CS:SS", format_args!("{:#06x}:{:#06x}", frame.cs, frame.ss));
+    if has_error_code(frame.vector) {
+        screen.field("Código de error", format_args!("{:#x}", frame.error_code));
+    }
+    if frame.vector == DOUBLE_FAULT_VECTOR as u64 {
+        screen.field("Pista", format_args!("posible desbordamiento de la pila del kernel"));
+    }
+    if frame.vector == PAGE_FAULT_VECTOR as u64 {
+        describe_page_fault(&mut screen, frame.error_code, cr2);
+    } else if has_selector_error(frame.vector) && frame.error_code != 0 {
+        describe_selector_error(&mut screen, frame.error_code);
+    }
+    screen.gap();
+
+    let registers = [
+        ("RAX", frame.rax), ("RBX", frame.rbx), ("RCX", frame.rcx),
+        ("RDX", frame.rdx), ("RSI", frame.rsi), ("RDI", frame.rdi),
+        ("RBP", frame.rbp), ("R8", frame.r8), ("R9", frame.r9),
+        ("R10", frame.r10), ("R11", frame.r11), ("R12", frame.r12),
+        ("R13", frame.r13), ("R14", frame.r14), ("R15", frame.r15),
+    ];
+    for (name, value) in registers {
+        screen.register(name, value);
+    }
+    screen.gap();
+
+    screen.register("CR0", cpu::read_cr0());
+    screen.register("CR2", cr2);
+    screen.register("CR3", cpu::read_cr3());
+    screen.register("CR4", cpu::read_cr4());
+    screen.gap();
+
+    screen.line(colors::TEXT_ACCENT, format_args!("El sistema se ha detenido."));
+}
+
+/// Decodifica el código de error de un fallo de página (#PF).
+fn describe_page_fault(screen: &mut FaultScreen, error_code: u64, cr2: u64) {
+    let cause = if error_code & 0x1 != 0 { "violación de protección" } else { "página no presente" };
+    let access = if error_code & 0x10 != 0 {
+        "ejecución"
+    } else if error_code & 0x2 != 0 {
+        "escritura"
+    } else {
+        "lectura"
+    };
+    let mode = if error_code & 0x4 != 0 { "usuario" } else { "kernel" };
+
+    screen.field("Dirección (CR2)", format_args!("{:#018x}", cr2));
+    screen.field("Causa", format_args!("{} durante {} en modo {}", cause, access, mode));
+    if error_code & 0x8 != 0 {
+        screen.field("Aviso", format_args!("bit reservado activo en una tabla de páginas"));
+    }
+}
+
+/// Decodifica el código de error de selector de #TS, #NP, #SS y #GP.
+fn describe_selector_error(screen: &mut FaultScreen, error_code: u64) {
+    let table = match (error_code >> 1) & 0b11 {
+        0 => "GDT",
+        2 => "LDT",
+        _ => "IDT",
+    };
+    let external = if error_code & 0x1 != 0 { " (evento externo)" } else { "" };
+    screen.field("Selector", format_args!("{} índice {}{}", table, (error_code >> 3) & 0x1FFF, external));
+}
@@ -0,0 +1,213 @@
+//! Tabla Global de Descriptores (GDT) y Task State Segment (TSS) del kernel.
+//!
+//! Limine deja instalada una GDT propia, pero el kernel no debe depender de su
+//! disposición. Este módulo instala una GDT conocida con los segmentos de
+//! código y datos de kernel y de usuario, más un TSS cuya Interrupt Stack Table
+//! (IST) proporciona pilas dedicadas para las excepciones que no pueden
+//! ejecutarse sobre la pila actual, como el doble fallo provocado por un
+//! desbordamiento de la pila del kernel.
+
+use core::mem::size_of;
+use lazy_static::lazy_static;
+use crate::log;
+
+/// Selector del segmento de código del kernel (ring 0, 64 bits).
+pub const KERNEL_CODE_SELECTOR: u16 = 0x08;
+/// Selector del segmento de datos del kernel (ring 0).
+pub const KERNEL_DATA_SELECTOR: u16 = 0x10;
+/// Selector del segmento de datos de usuario (ring 3).
+///
+/// Los segmentos de usuario van en el orden datos-código que exige `sysret`.
+pub const USER_DATA_SELECTOR: u16 = 0x18 | 3;
+/// Selector del segmento de código de usuario (ring 3, 64 bits).
+pub const USER_CODE_SELECTOR: u16 = 0x20 | 3;
+/// Selector del descriptor del TSS (ocupa dos entradas de la GDT).
+pub const TSS_SELECTOR: u16 = 0x28;
+
+/// Entrada de la IST usada por el doble fallo (#DF).
+pub const DOUBLE_FAULT_IST_INDEX: u8 = 1;
+/// Entrada de la IST usada por la interrupción no enmascarable (NMI).
+pub const NMI_IST_INDEX: u8 = 2;
+/// Entrada de la IST usada por el Machine Check (#MC).
+pub const MACHINE_CHECK_IST_INDEX: u8 = 3;
+
+/// Tamaño de cada una de las pilas de la IST.
+const IST_STACK_SIZE: usize = 4096 * 5;
+
+/// Descriptores de segmento planos (base 0, límite máximo) en su forma codificada.
+mod descriptor {
+    /// Código de 64 bits, ring 0: Presente, DPL=0, ejecutable/legible, L=1.
+    pub const KERNEL_CODE: u64 = 0x00AF_9A00_0000_FFFF;
+    /// Datos, ring 0: Presente, DPL=0, escribible.
+    pub const KERNEL_DATA: u64 = 0x00CF_9200_0000_FFFF;
+    /// Datos, ring 3: Presente, DPL=3, escribible.
+    pub const USER_DATA: u64 = 0x00CF_F200_0000_FFFF;
+    /// Código de 64 bits, ring 3: Presente, DPL=3, ejecutable/legible, L=1.
+    pub const USER_CODE: u64 = 0x00AF_FA00_0000_FFFF;
+}
+
+/// El Task State Segment de 64 bits.
+///
+/// En modo largo ya no se usa para la conmutación de tareas: solo guarda las
+/// pilas a las que salta la CPU al cambiar de privilegio (`privilege_stack_table`)
+/// y las pilas de la IST.
+#[repr(C, packed(4))]
+pub struct TaskStateSegment {
+    reserved_1: u32,
+    /// Pilas que se cargan al pasar de un ring menos privilegiado a los rings 0-2.
+    pub privilege_stack_table: [u64; 3],
+    reserved_2: u64,
+    /// Las siete pilas de la Interrupt Stack Table (IST1 a IST7).
+    pub interrupt_stack_table: [u64; 7],
+    reserved_3: u64,
+    reserved_4: u16,
+    /// Desplazamiento del mapa de permisos de E/S respecto al inicio del TSS.
+    pub iomap_base: u16,
+}
+
+impl TaskStateSegment {
+    /// Crea un TSS vacío sin mapa de permisos de E/S.
+    const fn new() -> Self {
+        TaskStateSegment {
+            reserved_1: 0,
+            privilege_stack_table: [0; 3],
+            reserved_2: 0,
+            interrupt_stack_table: [0; 7],
+            reserved_3: 0,
+            reserved_4: 0,
+            iomap_base: size_of::<TaskStateSegment>() as u16,
+        }
+    }
+}
+
+/// Una pila de kernel reservada estáticamente, alineada a 16 bytes.
+#[repr(C, align(16))]
+struct Stack([u8; IST_STACK_SIZE]);
+
+impl Stack {
+    /// Devuelve la dirección del final de la pila (las pilas crecen hacia abajo).
+    fn top(stack: *const Stack) -> u64 {
+        stack as u64 + IST_STACK_SIZE as u64
+    }
+}
+
+static mut DOUBLE_FAULT_STACK: Stack = Stack([0; IST_STACK_SIZE]);
+static mut NMI_STACK: Stack = Stack([0; IST_STACK_SIZE]);
+static mut MACHINE_CHECK_STACK: Stack = Stack([0; IST_STACK_SIZE]);
+
+/// El TSS del kernel. La CPU lo lee directamente a través del descriptor de la GDT.
+static mut TSS: TaskStateSegment = TaskStateSegment::new();
+
+/// Número de entradas de 8 bytes de la GDT (el TSS ocupa dos).
+const GDT_ENTRIES: usize = 7;
+
+/// Representa la GDT del kernel.
+#[repr(C, align(8))]
+pub struct GlobalDescriptorTable {
+    entries: [u64; GDT_ENTRIES],
+}
+
+impl GlobalDescriptorTable {
+    /// Construye la GDT con los segmentos planos y el descriptor del TSS indicado.
+    fn new(tss: *const TaskStateSegment) -> Self {
+        let base = tss as u64;
+        let limit = (size_of::<TaskStateSegment>() - 1) as u64;
+
+        // Descriptor de sistema de 16 bytes: Presente, tipo 0x9 (TSS de 64 bits disponible).
+        let tss_low = (limit & 0xFFFF)
+            | ((base & 0xFF_FFFF) << 16)
+            | (0x89 << 40)
+            | (((limit >> 16) & 0xF) << 48)
+            | (((base >> 24) & 0xFF) << 56);
+        let tss_high = base >> 32;
+
+        GlobalDescriptorTable {
+            entries: [
+                0, // Descriptor nulo obligatorio.
+                descriptor::KERNEL_CODE,
+                descriptor::KERNEL_DATA,
+                descriptor::USER_DATA,
+                descriptor::USER_CODE,
+                tss_low,
+                tss_high,
+            ],
+        }
+    }
+
+    /// Carga la GDT con `lgdt`, recarga todos los registros de segmento y el TSS.
+    ///
+    /// # Safety
+    ///
+    /// Esta función es insegura porque cambia los segmentos con los que se está
+    /// ejecutando el kernel. Debe llamarse solo una vez durante la inicialización.
+    fn load(&'static self) {
+        #[repr(C, packed(2))]
+        struct DescriptorTablePointer {
+            size: u16,
+            address: u64,
+        }
+
+        let pointer = DescriptorTablePointer {
+            size: (size_of::<Self>() - 1) as u16,
+            address: self as *const _ as u64,
+        };
+
+        unsafe {
+            core::arch::asm!(
+                "lgdt [{ptr}]",
+                // CS solo puede recargarse con un salto lejano: simulamos el
+                // retorno de una llamada lejana hacia la etiqueta siguiente.
+                "push {code}",
+                "lea {tmp}, [rip + 2f]",
+                "push {tmp}",
+                "retfq",
+                "2:",
+                "mov ds, {data:x}",
+                "mov es, {data:x}",
+                "mov fs, {data:x}",
+                "mov gs, {data:x}",
+                "mov ss, {data:x}",
+                "ltr {tss:x}",
+                ptr = in(reg) &pointer,
+                code = in(reg) KERNEL_CODE_SELECTOR as u64,
+                data = in(reg) KERNEL_DATA_SELECTOR as u64,
+                tss = in(reg) TSS_SELECTOR as u64,
+                tmp = lateout(reg) _,
+                options(preserves_flags),
+            );
+        }
+    }
+}
+
+lazy_static! {
+    /// La GDT del kernel, con el descriptor apuntando al TSS estático.
+    static ref GDT: GlobalDescriptorTable = GlobalDescriptorTable::new(&raw const TSS);
+}
+
+/// Prepara las pilas de la IST en el TSS e instala la GDT del kernel.
+///
+/// Debe llamarse antes de cargar la IDT, ya que sus entradas hacen referencia
+/// al selector de código y a las pilas definidas aquí.
+pub fn init() {
+    // Es seguro escribir en el TSS aquí: todavía no se ha cargado en la CPU
+    // y las interrupciones siguen deshabilitadas.
+    let mut stacks = [0u64; 7];
+    stacks[(DOUBLE_FAULT_IST_INDEX - 1) as usize] = Stack::top(&raw const DOUBLE_FAULT_STACK);
+    stacks[(NMI_IST_INDEX - 1) as usize] = Stack::top(&raw const NMI_STACK);
+    stacks[(MACHINE_CHECK_IST_INDEX - 1) as usize] = Stack::top(&raw const MACHINE_CHECK_STACK);
+    unsafe { TSS.interrupt_stack_table = stacks };
+
+    GDT.load();
+    log::debug!("GDT y TSS instalados con pilas IST para #DF, NMI y #MC");
+}
+
+/// Cambia la pila a la que salta la CPU cuando una interrupción o excepción
+/// llega mientras se ejecuta código de usuario.
+///
+/// Cada hilo que entra en modo usuario tiene su propia pila del kernel, así
+/// que se actualiza en cada cambio de contexto.
+pub fn set_kernel_stack(top: u64) {
+    // El TSS solo se lee cuando la CPU cambia de privilegio, y quien llama
+    // tiene las interrupciones deshabilitadas.
+    unsafe { TSS.privilege_stack_table[0] = top };
+}
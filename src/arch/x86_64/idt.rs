@@ -2,6 +2,7 @@
 
 use core::mem::size_of;
 use core::ops::{Index, IndexMut};
+use super::gdt;
 
 /// Representa una entrada en la IDT.
 #[derive(Debug, Clone, Copy)]
@@ -33,12 +34,19 @@ impl IdtEntry {
         self.pointer_low = handler_addr as u16;
         self.pointer_middle = (handler_addr >> 16) as u16;
         self.pointer_high = (handler_addr >> 32) as u32;
-        // El selector de segmento 0x8 es el segmento de código en nuestra GDT.
-        self.gdt_selector = 0x8;
+        // El manejador se ejecuta en el segmento de código del kernel de nuestra GDT.
+        self.gdt_selector = gdt::KERNEL_CODE_SELECTOR;
         // Opciones: Presente=1, Nivel de Privilegio=0 (kernel), Tipo=Puerta de Interrupción de 32 bits.
         self.options = 0x8E00;
         self
     }
+
+    /// Hace que la CPU cambie a la pila `index` de la IST del TSS (1-7) antes
+    /// de ejecutar el manejador. Un índice 0 mantiene la pila actual.
+    pub fn set_stack_index(&mut self, index: u8) -> &mut Self {
+        self.options = (self.options & !0b111) | (index as u16 & 0b111);
+        self
+    }
 }
 
 /// Representa la IDT, un array de 256 entradas.
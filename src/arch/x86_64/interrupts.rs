@@ -1,9 +1,12 @@
 //! Módulo para manejar las interrupciones de la CPU en x86_64.
 
 use lazy_static::lazy_static;
-use super::{cpu, idt::InterruptDescriptorTable, keyboard};
+use super::{cpu, exceptions, gdt, idt::InterruptDescriptorTable, keyboard, pit, serial};
 use pic8259::ChainedPics;
-use spin;
+use crate::log;
+use crate::sched;
+use crate::sync::{lock_order, IrqSpinlock};
+use crate::task;
 
 /// El offset para el controlador de interrupciones programable (PIC).
 /// Las interrupciones del PIC empezarán en el vector 32 para no solaparse
@@ -11,9 +14,16 @@ use spin;
 pub const PIC_1_OFFSET: u8 = 32;
 pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 
+/// Máscara del PIC maestro: solo se habilitan IRQ0 (timer), IRQ1 (teclado)
+/// e IRQ2 (cascada hacia el PIC esclavo). Un bit a 1 enmascara la línea.
+/// Las IRQ3 e IRQ4 se desenmascaran después si hay puertos serie presentes.
+const PIC_1_MASK: u8 = 0b1111_1000;
+/// Máscara del PIC esclavo: todas sus líneas siguen enmascaradas.
+const PIC_2_MASK: u8 = 0b1111_1111;
+
 /// Instancia estática y segura del controlador de interrupciones.
-pub static PICS: spin::Mutex<ChainedPics> =
-    spin::Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
+pub static PICS: IrqSpinlock<ChainedPics> =
+    IrqSpinlock::new(&lock_order::PICS, unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
 
 /*
  * Declaraciones `extern` para que Rust conozca los símbolos de nuestras
@@ -25,6 +35,10 @@ pub static PICS: spin::Mutex<ChainedPics> =
 unsafe extern "C" {
     fn timer_interrupt_stub();
     fn keyboard_interrupt_stub();
+    fn serial_irq3_interrupt_stub();
+    fn serial_irq4_interrupt_stub();
+    /// Direcciones de los stubs de las 32 excepciones de la CPU, por vector.
+    static exception_stub_table: [u64; 32];
 }
 
 lazy_static! {
@@ -35,21 +49,42 @@ lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
 
+        // Las 32 primeras entradas corresponden a las excepciones de la CPU.
+        for (vector, &stub) in unsafe { exception_stub_table.iter() }.enumerate() {
+            idt[vector].set_handler(stub);
+        }
+        // Estas excepciones usan pilas dedicadas de la IST: un doble fallo por
+        // desbordamiento de pila no puede ejecutarse sobre la pila desbordada.
+        idt[exceptions::DOUBLE_FAULT_VECTOR].set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
+        idt[exceptions::NMI_VECTOR].set_stack_index(gdt::NMI_IST_INDEX);
+        idt[exceptions::MACHINE_CHECK_VECTOR].set_stack_index(gdt::MACHINE_CHECK_IST_INDEX);
+
         // Asigna la dirección de la rutina de ensamblador a cada interrupción.
         // Esto es 100% compatible con Rust `stable`.
-        idt[InterruptIndex::Timer.as_usize()].set_handler(timer_interrupt_stub as u64);
-        idt[InterruptIndex::Keyboard.as_usize()].set_handler(keyboard_interrupt_stub as u64);
+        idt[InterruptIndex::Timer.as_usize()].set_handler(timer_interrupt_stub as *const () as u64);
+        idt[InterruptIndex::Keyboard.as_usize()].set_handler(keyboard_interrupt_stub as *const () as u64);
+        idt[InterruptIndex::SerialIrq3.as_usize()].set_handler(serial_irq3_interrupt_stub as *const () as u64);
+        idt[InterruptIndex::SerialIrq4.as_usize()].set_handler(serial_irq4_interrupt_stub as *const () as u64);
 
         idt
     };
 }
 
-/// Inicializa la IDT y el controlador de interrupciones.
+/// Inicializa la IDT, el controlador de interrupciones, el temporizador (PIT)
+/// y los puertos serie.
 pub fn init() {
     IDT.load();
-    unsafe { PICS.lock().initialize() };
+    let serial_irqs = serial::init();
+    unsafe {
+        let mut pics = PICS.lock();
+        pics.initialize();
+        // No dependemos de las máscaras que dejó el gestor de arranque.
+        pics.write_masks(PIC_1_MASK & !serial_irqs, PIC_2_MASK);
+    }
+    pit::init();
     // Habilita las interrupciones globalmente.
     unsafe { cpu::enable_interrupts() };
+    log::info!("IDT cargada e interrupciones habilitadas");
 }
 
 /// Enumera los índices de las interrupciones de hardware que manejamos.
@@ -58,6 +93,8 @@ pub fn init() {
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     Keyboard,
+    SerialIrq3 = PIC_1_OFFSET + 3,
+    SerialIrq4,
 }
 
 impl InterruptIndex {
@@ -69,10 +106,15 @@ impl InterruptIndex {
 /// Esta función es llamada desde el stub de ensamblador `timer_interrupt_stub`.
 #[unsafe(no_mangle)] // Requerido por la edición 2024 para atributos `extern`.
 pub extern "C" fn rust_timer_interrupt_handler() {
-    // Aquí se podría incrementar un contador de 'ticks' del sistema.
+    pit::tick();
     unsafe {
         PICS.lock().notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
     }
+    // El fin de interrupción ya se ha notificado: si el planificador cambia
+    // de hilo, el temporizador sigue funcionando mientras se ejecuta el otro,
+    // y este manejador termina cuando el hilo interrumpido vuelva a la CPU.
+    task::timer::wake_expired(pit::ticks());
+    sched::timer_tick();
 }
 
 /// Manejador en Rust para la interrupción del teclado.
@@ -87,4 +129,24 @@ pub extern "C" fn rust_keyboard_interrupt_handler() {
     unsafe {
         PICS.lock().notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
     }
-}
\ No newline at end of file
+}
+
+/// Manejador en Rust para la IRQ3 (puertos serie COM2 y COM4).
+/// Esta función es llamada desde el stub de ensamblador `serial_irq3_interrupt_stub`.
+#[unsafe(no_mangle)] // Requerido por la edición 2024 para atributos `extern`.
+pub extern "C" fn rust_serial_irq3_handler() {
+    serial::handle_interrupt(3);
+    unsafe {
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::SerialIrq3.as_u8());
+    }
+}
+
+/// Manejador en Rust para la IRQ4 (puertos serie COM1 y COM3).
+/// Esta función es llamada desde el stub de ensamblador `serial_irq4_interrupt_stub`.
+#[unsafe(no_mangle)] // Requerido por la edición 2024 para atributos `extern`.
+pub extern "C" fn rust_serial_irq4_handler() {
+    serial::handle_interrupt(4);
+    unsafe {
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::SerialIrq4.as_u8());
+    }
+}
@@ -1,40 +1,117 @@
 //! Módulo para el driver del teclado estándar PS/2.
 
+use core::future;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll};
+
 use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
-use spin::Mutex;
 use lazy_static::lazy_static;
 
+use crate::log;
+use crate::sync::{lock_order, IrqSpinlock, SpscRing};
+use crate::task::WakerSlot;
+
+/// Capacidad de la cola de scancodes.
+const SCANCODE_CAPACITY: usize = 64;
+
 lazy_static! {
     /// Instancia global del driver de teclado.
-    /// Utiliza un Mutex para un acceso seguro desde el manejador de interrupciones
-    /// y el bucle principal del kernel.
-    static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
+    ///
+    /// Quien tiene este lock es el único consumidor de [`SCANCODES`].
+    static ref KEYBOARD: IrqSpinlock<Keyboard<layouts::Us104Key, ScancodeSet1>> =
         // El orden correcto de los argumentos es: ScancodeSet, Layout, HandleControl.
-        Mutex::new(Keyboard::new(ScancodeSet1::new(), layouts::Us104Key, HandleControl::Ignore));
+        IrqSpinlock::new(&lock_order::KEYBOARD, Keyboard::new(ScancodeSet1::new(), layouts::Us104Key, HandleControl::Ignore));
 }
 
-lazy_static! {
-    /// Una cola simple para los scancodes.
-    /// El manejador de interrupciones escribe aquí, y el bucle principal lee.
-    static ref SCANCODE_QUEUE: Mutex<heapless::Deque<u8, 8>> =
-        Mutex::new(heapless::Deque::new());
-}
+/// Scancodes recibidos y aún sin decodificar. El manejador de interrupción
+/// es el único productor.
+static SCANCODES: SpscRing<u8, SCANCODE_CAPACITY> = SpscRing::new();
+/// Desbordamientos de [`SCANCODES`] ya avisados en el registro.
+static REPORTED_OVERFLOWS: AtomicU64 = AtomicU64::new(0);
+
+/// Tarea que espera teclas en un [`KeyStream`].
+static KEY_WAKER: WakerSlot = WakerSlot::new();
 
 /// Llamado por el manejador de interrupciones del teclado.
 pub fn add_scancode(scancode: u8) {
-    if SCANCODE_QUEUE.lock().push_back(scancode).is_err() {
-        // La cola está llena: se pierde el carácter. Aceptable para un sistema simple.
-    }
+    // El manejador del teclado no es reentrante: es el único productor.
+    // Si la cola está llena, el scancode se cuenta como perdido.
+    unsafe { SCANCODES.push(scancode) };
+    KEY_WAKER.wake();
 }
 
 /// Sondea la cola en busca de un nuevo evento de teclado decodificado.
+///
+/// Procesa scancodes hasta obtener una tecla o vaciar la cola: los que no
+/// producen tecla (como soltar una) no deben dejar otros esperando.
 pub fn poll_key() -> Option<DecodedKey> {
-    let mut scancode_queue = SCANCODE_QUEUE.lock();
-    if let Some(scancode) = scancode_queue.pop_front() {
-        let mut keyboard = KEYBOARD.lock();
-        if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
-            return keyboard.process_keyevent(key_event);
+    report_overflows();
+    let mut keyboard = KEYBOARD.lock();
+    // El lock del decodificador hace de este código el único consumidor.
+    while let Some(scancode) = unsafe { SCANCODES.pop() } {
+        if let Ok(Some(key_event)) = keyboard.add_byte(scancode)
+            && let Some(key) = keyboard.process_keyevent(key_event)
+        {
+            return Some(key);
         }
     }
     None
 }
+
+/// Avisa en el registro de los scancodes perdidos desde el último aviso.
+fn report_overflows() {
+    let overflows = SCANCODES.overflows();
+    let reported = REPORTED_OVERFLOWS.swap(overflows, Ordering::Relaxed);
+    if overflows > reported {
+        log::warn!("teclado: cola llena, {} scancodes perdidos ({} en total)", overflows - reported, overflows);
+    }
+}
+
+/// Scancodes perdidos desde el arranque por tener la cola llena.
+#[allow(dead_code)]
+pub fn dropped_scancodes() -> u64 {
+    SCANCODES.overflows()
+}
+
+/// Indica si alguna de las teclas Shift está pulsada.
+pub fn shift_pressed() -> bool {
+    KEYBOARD.lock().get_modifiers().is_shifted()
+}
+
+/// Flujo asíncrono de las teclas pulsadas, alimentado por la interrupción
+/// del teclado.
+///
+/// Solo una tarea puede esperar teclas a la vez: si dos usan un `KeyStream`,
+/// las teclas se reparten entre ambas y solo despierta la última que esperó.
+pub struct KeyStream {
+    _private: (),
+}
+
+impl KeyStream {
+    pub fn new() -> Self {
+        Self { _private: () }
+    }
+
+    /// Devuelve la siguiente tecla, o registra la tarea para cuando llegue.
+    pub fn poll_next(&mut self, context: &mut Context) -> Poll<DecodedKey> {
+        if let Some(key) = poll_key() {
+            return Poll::Ready(key);
+        }
+        KEY_WAKER.register(context.waker());
+        // Una tecla puede haber llegado antes de registrar la tarea.
+        match poll_key() {
+            Some(key) => Poll::Ready(key),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Espera a la siguiente tecla.
+    pub async fn next(&mut self) -> DecodedKey {
+        future::poll_fn(|context| self.poll_next(context)).await
+    }
+
+    /// Descarta las teclas pulsadas que aún no se han leído.
+    pub fn discard_pending(&mut self) {
+        while poll_key().is_some() {}
+    }
+}
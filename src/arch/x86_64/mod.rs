@@ -1,16 +1,52 @@
 //! Implementación de la arquitectura x86_64.
 
+pub mod context;
+pub mod cpuid;
+pub mod debugcon;
 pub mod interrupts;
 pub mod keyboard; // El driver del teclado es específico de la arquitectura de PC.
-mod cpu;
+pub mod paging;
+pub mod serial;
+pub mod syscall;
+pub mod cpu;
+mod exceptions;
+mod gdt;
 mod idt;
+mod pit;
 
-/// Inicializa la IDT y el controlador de interrupciones (PIC).
+/// Instala la GDT y el TSS del kernel, y después la IDT y el controlador de
+/// interrupciones (PIC). También habilita la entrada por `syscall`.
 pub fn init() {
+    gdt::init();
     interrupts::init();
+    syscall::init();
+}
+
+/// Construye las tablas de páginas del kernel y las activa.
+pub fn init_paging() {
+    paging::init();
 }
 
 /// Pone la CPU en estado de bajo consumo (HLT) hasta la próxima interrupción.
 pub fn wait_for_interrupt() {
     cpu::hlt();
+}
+
+/// Habilita las interrupciones y detiene la CPU hasta la próxima, sin que
+/// ninguna pueda llegar entre ambas cosas.
+pub fn enable_interrupts_and_wait() {
+    cpu::enable_interrupts_and_hlt();
+}
+
+/// Frecuencia a la que el PIT genera la interrupción del temporizador.
+pub const TIMER_HZ: u64 = pit::TICK_HZ;
+
+/// Devuelve el número de ticks del PIT desde que se inicializaron las interrupciones.
+pub fn timer_ticks() -> u64 {
+    pit::ticks()
+}
+
+/// Ejecuta `f` con las interrupciones deshabilitadas y restaura el estado previo.
+pub fn without_interrupts<F: FnOnce() -> R, R>(f: F) -> R {
+    cpu::without_interrupts(f)
 }
\ No newline at end of file
@@ -0,0 +1,437 @@
+//! Paginación de x86_64: tablas de páginas propias del kernel.
+//!
+//! Al arrancar, el kernel funciona sobre las tablas que preparó Limine, que
+//! no puede modificar de forma segura. Este módulo construye una PML4 propia
+//! que reproduce lo necesario de aquella:
+//!
+//! - El mapeo directo de la memoria física (HHDM): los primeros 4 GiB y todas
+//!   las regiones del mapa de memoria, con páginas de 2 MiB siempre que se
+//!   puede. Es lectura/escritura y no ejecutable.
+//! - La imagen del kernel, con los permisos que declaran los PHDRS de
+//!   `linker.ld`: código de lectura y ejecución, datos de solo lectura y
+//!   datos de lectura/escritura no ejecutables.
+//!
+//! Las tablas se leen y escriben a través del HHDM. Las 256 entradas de la
+//! mitad superior de la PML4 se crean al inicializar, de modo que cualquier
+//! espacio de direcciones que copie esa mitad comparte para siempre los
+//! mapeos del kernel. Así son los espacios de usuario: su mitad inferior es
+//! propia y la superior es la del kernel.
+
+use core::fmt;
+use core::ops::BitOr;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use super::cpu;
+use super::cpuid::{self, Feature};
+use crate::log;
+use crate::memory;
+use crate::sync::{lock_order, IrqSpinlock};
+use crate::{EXECUTABLE_ADDRESS_REQUEST, MEMMAP_REQUEST};
+
+/// Tamaño de una página normal.
+pub const PAGE_SIZE: u64 = 4096;
+/// Tamaño de una página grande (una entrada de directorio de páginas).
+const HUGE_PAGE_SIZE: u64 = 2 * 1024 * 1024;
+/// Memoria física que se mapea siempre en el HHDM, esté o no en el mapa de memoria.
+const LOW_MEMORY: u64 = 4 * 1024 * 1024 * 1024;
+/// Bits de una entrada que contienen la dirección física.
+const ADDRESS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+/// Bits de CR3 que contienen la dirección física de la PML4.
+pub const ROOT_MASK: u64 = ADDRESS_MASK;
+/// Primera entrada de la PML4 que pertenece a la mitad del kernel.
+const KERNEL_HALF: usize = 256;
+
+/// MSR EFER y su bit NXE, que habilita el bit de no ejecución.
+const EFER: u32 = 0xC000_0080;
+const EFER_NXE: u64 = 1 << 11;
+/// Bit WP de CR0: el kernel también respeta las páginas de solo lectura.
+const CR0_WP: u64 = 1 << 16;
+
+/// Una tabla de páginas de cualquier nivel.
+type Table = [u64; 512];
+
+/// Atributos que admite la CPU. [`init`] quita [`Flags::NO_EXECUTE`] si no
+/// hay NXE, y los mapeos lo quitan de lo que piden sus llamadores.
+static SUPPORTED_FLAGS: AtomicU64 = AtomicU64::new(u64::MAX);
+
+unsafe extern "C" {
+    /// Principios de los segmentos del kernel y final de la imagen, definidos en `linker.ld`.
+    static __text_start: u8;
+    static __rodata_start: u8;
+    static __data_start: u8;
+    static __kernel_end: u8;
+}
+
+/// Permisos y atributos de una página.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flags(u64);
+
+impl Flags {
+    /// Página de solo lectura y ejecutable, accesible solo por el kernel.
+    #[allow(dead_code)]
+    pub const EMPTY: Flags = Flags(0);
+    const PRESENT: Flags = Flags(1 << 0);
+    /// Se puede escribir.
+    pub const WRITABLE: Flags = Flags(1 << 1);
+    /// Accesible desde el modo usuario (ring 3).
+    pub const USER: Flags = Flags(1 << 2);
+    /// Las escrituras llegan a memoria sin esperar a la caché.
+    pub const WRITE_THROUGH: Flags = Flags(1 << 3);
+    /// Sin caché, para registros de dispositivos.
+    #[allow(dead_code)]
+    pub const NO_CACHE: Flags = Flags(1 << 4);
+    const HUGE: Flags = Flags(1 << 7);
+    /// No se invalida de la TLB al cambiar de espacio de direcciones.
+    pub const GLOBAL: Flags = Flags(1 << 8);
+    /// No se puede ejecutar código de la página.
+    pub const NO_EXECUTE: Flags = Flags(1 << 63);
+
+    /// Indica si contiene todos los bits de `other`.
+    pub const fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Atributos de una entrada de tabla.
+    const fn of(entry: u64) -> Flags {
+        Flags(entry & !ADDRESS_MASK)
+    }
+
+    /// Bits que se escriben de verdad en una entrada: sin los que la CPU no
+    /// admite, que serían bits reservados.
+    fn supported(self) -> u64 {
+        self.0 & SUPPORTED_FLAGS.load(Ordering::Relaxed)
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Flags;
+
+    fn bitor(self, other: Flags) -> Flags {
+        Flags(self.0 | other.0)
+    }
+}
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bit = |flag: Flags, c: char| if self.contains(flag) { c } else { '-' };
+        write!(
+            f,
+            "r{}{}{}",
+            bit(Flags::WRITABLE, 'w'),
+            if self.contains(Flags::NO_EXECUTE) { '-' } else { 'x' },
+            bit(Flags::USER, 'u')
+        )
+    }
+}
+
+/// Errores de las operaciones de mapeo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapError {
+    /// La dirección virtual o física no está alineada a página.
+    Unaligned,
+    /// La página ya estaba mapeada.
+    AlreadyMapped,
+    /// La página no está mapeada.
+    NotMapped,
+    /// La dirección pertenece a una página grande, que no se puede modificar por partes.
+    HugePage,
+    /// No quedan marcos físicos para las tablas de páginas.
+    OutOfMemory,
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MapError::Unaligned => "dirección no alineada a página",
+            MapError::AlreadyMapped => "la página ya está mapeada",
+            MapError::NotMapped => "la página no está mapeada",
+            MapError::HugePage => "la dirección está dentro de una página grande",
+            MapError::OutOfMemory => "no hay memoria para las tablas de páginas",
+        })
+    }
+}
+
+/// Índice de `virt` en la tabla del nivel dado (4 = PML4, 1 = tabla de páginas).
+fn index(virt: u64, level: u32) -> usize {
+    ((virt >> (12 + 9 * (level - 1))) & 0x1FF) as usize
+}
+
+/// Accede a la tabla de páginas que está en la dirección física `physical`.
+fn table(physical: u64) -> &'static mut Table {
+    // Las tablas son marcos propiedad de un `AddressSpace`, accesibles por el HHDM.
+    unsafe { &mut *(memory::phys_to_virt(physical) as *mut Table) }
+}
+
+/// Reserva un marco y lo deja a cero, listo para ser una tabla de páginas.
+fn allocate_table() -> Result<u64, MapError> {
+    let frame = memory::allocate_frame().ok_or(MapError::OutOfMemory)?;
+    table(frame).fill(0);
+    Ok(frame)
+}
+
+/// Un espacio de direcciones: una jerarquía de tablas de páginas con su PML4.
+pub struct AddressSpace {
+    /// Dirección física de la PML4.
+    root: u64,
+}
+
+impl AddressSpace {
+    /// Crea un espacio de direcciones de usuario con la mitad inferior vacía
+    /// y la superior compartida con el kernel.
+    ///
+    /// Los marcos que se mapeen en su mitad inferior pasan a ser suyos: al
+    /// soltar el espacio se liberan junto con sus tablas.
+    pub fn new_user() -> Result<Self, MapError> {
+        let space = AddressSpace { root: allocate_table()? };
+        let kernel = table(kernel_root());
+        table(space.root)[KERNEL_HALF..].copy_from_slice(&kernel[KERNEL_HALF..]);
+        Ok(space)
+    }
+
+    /// Dirección física de la PML4, el valor que se carga en CR3.
+    pub fn root(&self) -> u64 {
+        self.root
+    }
+
+    /// Devuelve la entrada de `virt` en la tabla del nivel `level`.
+    ///
+    /// Con `create`, las tablas intermedias que falten se crean, y se marcan
+    /// como accesibles desde el modo usuario si lo es `create`.
+    fn entry(&mut self, virt: u64, level: u32, create: Option<Flags>) -> Result<&'static mut u64, MapError> {
+        let mut current = table(self.root);
+        for depth in (level + 1..=4).rev() {
+            let entry = &mut current[index(virt, depth)];
+            if *entry & Flags::PRESENT.0 == 0 {
+                let Some(flags) = create else { return Err(MapError::NotMapped) };
+                *entry = allocate_table()? | (Flags::PRESENT | Flags::WRITABLE).0 | (flags.0 & Flags::USER.0);
+            } else if Flags::of(*entry).contains(Flags::HUGE) {
+                return Err(MapError::HugePage);
+            } else if let Some(flags) = create
+                && flags.contains(Flags::USER)
+            {
+                *entry |= Flags::USER.0;
+            }
+            current = table(*entry & ADDRESS_MASK);
+        }
+        Ok(&mut current[index(virt, level)])
+    }
+
+    /// Mapea la página de 4 KiB `virt` en el marco `physical`.
+    pub fn map(&mut self, virt: u64, physical: u64, flags: Flags) -> Result<(), MapError> {
+        if !virt.is_multiple_of(PAGE_SIZE) || !physical.is_multiple_of(PAGE_SIZE) {
+            return Err(MapError::Unaligned);
+        }
+        let entry = self.entry(virt, 1, Some(flags))?;
+        if *entry & Flags::PRESENT.0 != 0 {
+            return Err(MapError::AlreadyMapped);
+        }
+        *entry = physical | (flags | Flags::PRESENT).supported();
+        cpu::invlpg(virt);
+        Ok(())
+    }
+
+    /// Mapea la página grande de 2 MiB `virt` en la memoria física `physical`.
+    fn map_huge(&mut self, virt: u64, physical: u64, flags: Flags) -> Result<(), MapError> {
+        let entry = self.entry(virt, 2, Some(flags))?;
+        if *entry & Flags::PRESENT.0 != 0 {
+            return Err(MapError::AlreadyMapped);
+        }
+        *entry = physical | (flags | Flags::PRESENT | Flags::HUGE).supported();
+        cpu::invlpg(virt);
+        Ok(())
+    }
+
+    /// Mapea `size` bytes a partir de `virt` sobre la memoria física que
+    /// empieza en `physical`, con páginas grandes donde la alineación lo
+    /// permite. Las páginas que ya estaban mapeadas se dejan como están.
+    fn map_region(&mut self, virt: u64, physical: u64, size: u64, flags: Flags) -> Result<(), MapError> {
+        let mut offset = 0;
+        while offset < size {
+            let (virt, physical) = (virt + offset, physical + offset);
+            let huge = virt.is_multiple_of(HUGE_PAGE_SIZE)
+                && physical.is_multiple_of(HUGE_PAGE_SIZE)
+                && size - offset >= HUGE_PAGE_SIZE;
+            let result = if huge { self.map_huge(virt, physical, flags) } else { self.map(virt, physical, flags) };
+            match result {
+                Ok(()) | Err(MapError::AlreadyMapped | MapError::HugePage) => {}
+                Err(error) => return Err(error),
+            }
+            offset += if huge { HUGE_PAGE_SIZE } else { PAGE_SIZE };
+        }
+        Ok(())
+    }
+
+    /// Elimina el mapeo de la página `virt` y devuelve el marco en el que
+    /// estaba. El marco no se libera: sigue siendo de quien lo mapeó.
+    pub fn unmap(&mut self, virt: u64) -> Result<u64, MapError> {
+        let entry = self.entry(virt, 1, None)?;
+        if *entry & Flags::PRESENT.0 == 0 {
+            return Err(MapError::NotMapped);
+        }
+        let physical = *entry & ADDRESS_MASK;
+        *entry = 0;
+        cpu::invlpg(virt);
+        Ok(physical)
+    }
+
+    /// Cambia los permisos de la página `virt`, ya mapeada.
+    pub fn protect(&mut self, virt: u64, flags: Flags) -> Result<(), MapError> {
+        let entry = self.entry(virt, 1, None)?;
+        if *entry & Flags::PRESENT.0 == 0 {
+            return Err(MapError::NotMapped);
+        }
+        *entry = (*entry & ADDRESS_MASK) | (flags | Flags::PRESENT).supported();
+        cpu::invlpg(virt);
+        Ok(())
+    }
+
+    /// Traduce una dirección virtual a la física y a los permisos de su página.
+    pub fn translate(&self, virt: u64) -> Option<(u64, Flags)> {
+        let mut current = table(self.root);
+        for level in (1..=4).rev() {
+            let entry = current[index(virt, level)];
+            if entry & Flags::PRESENT.0 == 0 {
+                return None;
+            }
+            let flags = Flags::of(entry);
+            if level == 1 || (level <= 3 && flags.contains(Flags::HUGE)) {
+                let page_size = 1u64 << (12 + 9 * (level - 1));
+                return Some(((entry & ADDRESS_MASK & !(page_size - 1)) | (virt & (page_size - 1)), flags));
+            }
+            current = table(entry & ADDRESS_MASK);
+        }
+        None
+    }
+}
+
+impl Drop for AddressSpace {
+    fn drop(&mut self) {
+        /// Libera las tablas que cuelgan de `physical`, del nivel `level`, y
+        /// los marcos que mapean.
+        fn free(physical: u64, level: u32) {
+            for &entry in table(physical).iter() {
+                if entry & Flags::PRESENT.0 != 0 && (level == 1 || !Flags::of(entry).contains(Flags::HUGE)) {
+                    if level > 1 {
+                        free(entry & ADDRESS_MASK, level - 1);
+                    } else {
+                        memory::free_frame(entry & ADDRESS_MASK);
+                    }
+                }
+            }
+            memory::free_frame(physical);
+        }
+
+        // La mitad superior es la del kernel y no se toca.
+        for &entry in &table(self.root)[..KERNEL_HALF] {
+            if entry & Flags::PRESENT.0 != 0 {
+                free(entry & ADDRESS_MASK, 3);
+            }
+        }
+        memory::free_frame(self.root);
+    }
+}
+
+/// Dirección física de la PML4 del kernel, para cambiar a ella sin el lock.
+static KERNEL_ROOT: AtomicU64 = AtomicU64::new(0);
+
+/// Espacio de direcciones del kernel; `None` hasta que se llama a [`init`].
+static KERNEL: IrqSpinlock<Option<AddressSpace>> = IrqSpinlock::new(&lock_order::PAGING, None);
+
+/// Construye las tablas de páginas del kernel y las activa.
+///
+/// Requiere el asignador de marcos físicos.
+pub fn init() {
+    let info = cpuid::info();
+    unsafe {
+        if info.has(Feature::Nx) {
+            cpu::wrmsr(EFER, cpu::rdmsr(EFER) | EFER_NXE);
+        }
+        cpu::write_cr0(cpu::read_cr0() | CR0_WP);
+    }
+    // Sin NXE, el bit de no ejecución es un bit reservado y provocaría fallos
+    // de página: ningún mapeo lo escribe a partir de aquí.
+    if !info.has(Feature::Nx) {
+        SUPPORTED_FLAGS.store(!Flags::NO_EXECUTE.0, Ordering::Relaxed);
+    }
+
+    let mut space = AddressSpace { root: allocate_table().expect("no hay memoria para la PML4 del kernel") };
+    let pml4 = table(space.root);
+    for entry in &mut pml4[KERNEL_HALF..] {
+        *entry = allocate_table().expect("no hay memoria para las tablas del kernel") | (Flags::PRESENT | Flags::WRITABLE).0;
+    }
+
+    // Mapeo directo de la memoria física.
+    let offset = memory::hhdm_offset();
+    let data = Flags::WRITABLE | Flags::GLOBAL | Flags::NO_EXECUTE;
+    let mut result = space.map_region(offset, 0, LOW_MEMORY, data);
+    for entry in MEMMAP_REQUEST.get_response().map(|response| response.entries()).unwrap_or(&[]) {
+        let start = entry.base / PAGE_SIZE * PAGE_SIZE;
+        let end = (entry.base + entry.length).next_multiple_of(PAGE_SIZE);
+        // El framebuffer no debe quedarse en la caché: se escribe directamente en memoria.
+        let flags = if entry.entry_type == limine::memory_map::EntryType::FRAMEBUFFER { data | Flags::WRITE_THROUGH } else { data };
+        result = result.and_then(|()| space.map_region(offset + start, start, end - start, flags));
+    }
+
+    // Imagen del kernel, segmento a segmento.
+    let kernel = EXECUTABLE_ADDRESS_REQUEST.get_response().expect("Limine no proporcionó la dirección del kernel");
+    let to_physical = |virt: u64| virt - kernel.virtual_base() + kernel.physical_base();
+    let text = &raw const __text_start as u64;
+    let rodata = &raw const __rodata_start as u64;
+    let data_start = &raw const __data_start as u64;
+    let end = (&raw const __kernel_end as u64).next_multiple_of(PAGE_SIZE);
+    let segments = [
+        (text, rodata, Flags::GLOBAL),
+        (rodata, data_start, Flags::GLOBAL | Flags::NO_EXECUTE),
+        (data_start, end, data),
+    ];
+    for (start, end, flags) in segments {
+        result = result.and_then(|()| space.map_region(start, to_physical(start), end - start, flags));
+    }
+    if let Err(error) = result {
+        panic!("no se pudieron construir las tablas de páginas del kernel: {}", error);
+    }
+
+    // El código que se está ejecutando, la pila y los datos siguen mapeados
+    // en las mismas direcciones, así que el cambio es transparente.
+    unsafe { cpu::write_cr3(space.root) };
+    log::info!("tablas de páginas del kernel activas (PML4 en {:#x})", space.root);
+    for (start, end, _) in segments {
+        if let Some((physical, flags)) = space.translate(start) {
+            log::debug!("  {:#x}-{:#x} -> {:#x} {}", start, end, physical, flags);
+        }
+    }
+    KERNEL_ROOT.store(space.root, Ordering::Relaxed);
+    *KERNEL.lock() = Some(space);
+}
+
+/// Dirección física de la PML4 del kernel.
+pub fn kernel_root() -> u64 {
+    KERNEL_ROOT.load(Ordering::Relaxed)
+}
+
+/// Ejecuta `f` sobre el espacio de direcciones del kernel.
+fn with_kernel<R>(f: impl FnOnce(&mut AddressSpace) -> R) -> R {
+    f(KERNEL.lock().as_mut().expect("paginación sin inicializar"))
+}
+
+/// Mapea en el espacio del kernel la página `virt` sobre el marco `physical`.
+pub fn map(virt: u64, physical: u64, flags: Flags) -> Result<(), MapError> {
+    with_kernel(|space| space.map(virt, physical, flags))
+}
+
+/// Elimina del espacio del kernel el mapeo de la página `virt` y devuelve su marco.
+pub fn unmap(virt: u64) -> Result<u64, MapError> {
+    with_kernel(|space| space.unmap(virt))
+}
+
+/// Cambia los permisos de la página `virt` del espacio del kernel.
+#[allow(dead_code)]
+pub fn protect(virt: u64, flags: Flags) -> Result<(), MapError> {
+    with_kernel(|space| space.protect(virt, flags))
+}
+
+/// Traduce una dirección virtual del espacio del kernel a la física y a sus permisos.
+#[allow(dead_code)]
+pub fn translate(virt: u64) -> Option<(u64, Flags)> {
+    with_kernel(|space| space.translate(virt))
+}
@@ -0,0 +1,46 @@
+//! Driver del temporizador de intervalos programable (PIT, Intel 8253/8254).
+//!
+//! Programa el canal 0 del PIT para que genere la IRQ0 a una frecuencia
+//! conocida y mantiene el contador monotónico de ticks del sistema.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+use super::cpu;
+use crate::log;
+
+/// Frecuencia del oscilador interno del PIT en Hz.
+const PIT_BASE_FREQUENCY: u32 = 1_193_182;
+/// Frecuencia a la que se programa la interrupción del temporizador (1 tick = 1 ms).
+pub const TICK_HZ: u64 = 1000;
+
+/// Puerto de datos del canal 0 del PIT.
+const CHANNEL_0_PORT: u16 = 0x40;
+/// Puerto del registro de modo/comando del PIT.
+const COMMAND_PORT: u16 = 0x43;
+/// Comando: canal 0, acceso byte bajo/byte alto, modo 2 (generador de frecuencia), binario.
+const COMMAND_CHANNEL_0_RATE_GENERATOR: u8 = 0b0011_0100;
+
+/// Número de ticks transcurridos desde que se programó el PIT.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Programa el canal 0 del PIT a `TICK_HZ`.
+///
+/// Debe llamarse con las interrupciones deshabilitadas.
+pub fn init() {
+    let divisor = (PIT_BASE_FREQUENCY / TICK_HZ as u32) as u16;
+    unsafe {
+        cpu::outb(COMMAND_PORT, COMMAND_CHANNEL_0_RATE_GENERATOR);
+        cpu::outb(CHANNEL_0_PORT, divisor as u8);
+        cpu::outb(CHANNEL_0_PORT, (divisor >> 8) as u8);
+    }
+    log::info!("PIT programado a {} Hz (divisor {})", TICK_HZ, divisor);
+}
+
+/// Llamado por el manejador de la interrupción del temporizador en cada tick.
+pub fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Devuelve el número de ticks transcurridos desde el arranque.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
@@ -0,0 +1,378 @@
+//! Driver de los puertos serie UART 16550 (COM1 a COM4).
+//!
+//! Cada puerto se configura con una velocidad en baudios, formato 8N1 y FIFO
+//! activada. La transmisión se hace por sondeo del registro de estado de línea;
+//! la recepción es por interrupciones: el manejador de la IRQ4 (COM1/COM3) o la
+//! IRQ3 (COM2/COM4) vacía la FIFO de la UART en un búfer circular del que lee
+//! el resto del kernel a través del trait `ByteStream`.
+
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use super::cpu;
+use crate::io::ByteStream;
+use crate::log;
+use crate::sync::{lock_order, IrqSpinlock};
+
+/// Velocidad por defecto con la que se inicializan los puertos.
+pub const DEFAULT_BAUD_RATE: u32 = 115_200;
+/// Frecuencia base del divisor de la UART (velocidad máxima en baudios).
+const UART_CLOCK: u32 = 115_200;
+/// Capacidad del búfer de recepción de cada puerto.
+const RX_BUFFER_SIZE: usize = 256;
+
+/// Desplazamientos de los registros de la UART respecto a su puerto base.
+mod reg {
+    /// Datos: lectura del búfer de recepción / escritura del de transmisión.
+    pub const DATA: u16 = 0;
+    /// Habilitación de interrupciones (o byte alto del divisor con DLAB=1).
+    pub const INTERRUPT_ENABLE: u16 = 1;
+    /// Control de la FIFO (escritura) / identificación de interrupción (lectura).
+    pub const FIFO_CONTROL: u16 = 2;
+    /// Control de línea: formato de trama y bit DLAB.
+    pub const LINE_CONTROL: u16 = 3;
+    /// Control del módem: DTR, RTS, OUT2 y modo loopback.
+    pub const MODEM_CONTROL: u16 = 4;
+    /// Estado de la línea.
+    pub const LINE_STATUS: u16 = 5;
+    /// Registro de uso libre, útil para detectar si la UART existe.
+    pub const SCRATCH: u16 = 7;
+}
+
+/// Identifica uno de los cuatro puertos serie estándar del PC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComPort {
+    Com1,
+    Com2,
+    Com3,
+    Com4,
+}
+
+impl ComPort {
+    /// Todos los puertos, en orden.
+    pub const ALL: [ComPort; 4] = [ComPort::Com1, ComPort::Com2, ComPort::Com3, ComPort::Com4];
+
+    /// Devuelve el puerto de E/S base de la UART.
+    pub const fn base(self) -> u16 {
+        match self {
+            ComPort::Com1 => 0x3F8,
+            ComPort::Com2 => 0x2F8,
+            ComPort::Com3 => 0x3E8,
+            ComPort::Com4 => 0x2E8,
+        }
+    }
+
+    /// Devuelve la línea de IRQ del PIC que usa el puerto.
+    pub fn irq(self) -> u8 {
+        match self {
+            ComPort::Com1 | ComPort::Com3 => 4,
+            ComPort::Com2 | ComPort::Com4 => 3,
+        }
+    }
+
+    /// Devuelve el nombre del puerto (ej. "COM1").
+    pub fn name(self) -> &'static str {
+        match self {
+            ComPort::Com1 => "COM1",
+            ComPort::Com2 => "COM2",
+            ComPort::Com3 => "COM3",
+            ComPort::Com4 => "COM4",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Errores de configuración de un puerto serie.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SerialError {
+    /// La UART no responde: el puerto no existe en esta máquina.
+    NotPresent,
+    /// La velocidad pedida no puede obtenerse con el divisor de la UART.
+    InvalidBaudRate,
+}
+
+/// Contenido del registro de estado de línea (LSR).
+#[derive(Debug, Clone, Copy)]
+pub struct LineStatus(u8);
+
+#[allow(dead_code)]
+impl LineStatus {
+    /// Hay al menos un byte recibido esperando a ser leído.
+    pub fn data_ready(self) -> bool { self.0 & 0x01 != 0 }
+    /// Se perdió un byte porque el búfer de recepción estaba lleno.
+    pub fn overrun_error(self) -> bool { self.0 & 0x02 != 0 }
+    /// El byte recibido tiene un error de paridad.
+    pub fn parity_error(self) -> bool { self.0 & 0x04 != 0 }
+    /// El byte recibido no tenía un bit de parada válido.
+    pub fn framing_error(self) -> bool { self.0 & 0x08 != 0 }
+    /// La línea se mantuvo en espacio más tiempo que una trama (break).
+    pub fn break_interrupt(self) -> bool { self.0 & 0x10 != 0 }
+    /// El registro de transmisión está vacío y acepta un nuevo byte.
+    pub fn transmit_ready(self) -> bool { self.0 & 0x20 != 0 }
+    /// El transmisor ha terminado de enviar todos los bytes.
+    pub fn transmitter_idle(self) -> bool { self.0 & 0x40 != 0 }
+}
+
+/// Estado interno de una UART.
+struct Uart {
+    base: u16,
+    baud_rate: u32,
+    rx: heapless::Deque<u8, RX_BUFFER_SIZE>,
+}
+
+impl Uart {
+    const fn new(base: u16) -> Self {
+        Uart { base, baud_rate: 0, rx: heapless::Deque::new() }
+    }
+
+    fn read_reg(&self, offset: u16) -> u8 {
+        unsafe { cpu::inb(self.base + offset) }
+    }
+
+    fn write_reg(&self, offset: u16, value: u8) {
+        unsafe { cpu::outb(self.base + offset, value) }
+    }
+
+    fn line_status(&self) -> LineStatus {
+        LineStatus(self.read_reg(reg::LINE_STATUS))
+    }
+
+    /// Programa la UART y comprueba en modo loopback que responde.
+    fn configure(&mut self, baud_rate: u32) -> Result<(), SerialError> {
+        if baud_rate == 0 || !UART_CLOCK.is_multiple_of(baud_rate) || UART_CLOCK / baud_rate > u16::MAX as u32 {
+            return Err(SerialError::InvalidBaudRate);
+        }
+
+        // Un puerto inexistente devuelve 0xFF en todas las lecturas.
+        self.write_reg(reg::SCRATCH, 0x5A);
+        if self.read_reg(reg::SCRATCH) != 0x5A {
+            return Err(SerialError::NotPresent);
+        }
+
+        let divisor = (UART_CLOCK / baud_rate) as u16;
+        self.write_reg(reg::INTERRUPT_ENABLE, 0x00); // Sin interrupciones durante la configuración.
+        self.write_reg(reg::LINE_CONTROL, 0x80); // DLAB=1 para acceder al divisor.
+        self.write_reg(reg::DATA, divisor as u8);
+        self.write_reg(reg::INTERRUPT_ENABLE, (divisor >> 8) as u8);
+        self.write_reg(reg::LINE_CONTROL, 0x03); // 8 bits, sin paridad, 1 bit de parada; DLAB=0.
+        self.write_reg(reg::FIFO_CONTROL, 0xC7); // FIFO activa y vaciada, umbral de 14 bytes.
+
+        // Prueba en loopback: lo que se envía debe volver tal cual.
+        self.write_reg(reg::MODEM_CONTROL, 0x1E);
+        self.write_reg(reg::DATA, 0xAE);
+        if self.read_reg(reg::DATA) != 0xAE {
+            return Err(SerialError::NotPresent);
+        }
+
+        // Modo normal: DTR, RTS y OUT2 (necesario para que la UART llegue al PIC).
+        self.write_reg(reg::MODEM_CONTROL, 0x0B);
+        // Interrupción al recibir datos.
+        self.write_reg(reg::INTERRUPT_ENABLE, 0x01);
+
+        self.baud_rate = baud_rate;
+        self.rx.clear();
+        Ok(())
+    }
+
+    /// Envía un byte esperando a que el registro de transmisión quede libre.
+    fn send(&self, byte: u8) {
+        while !self.line_status().transmit_ready() {
+            core::hint::spin_loop();
+        }
+        self.write_reg(reg::DATA, byte);
+    }
+
+    /// Pasa al búfer de recepción todos los bytes pendientes en la FIFO de la UART.
+    ///
+    /// Devuelve cuántos bytes se descartaron por tener el búfer lleno.
+    fn drain_fifo(&mut self) -> u64 {
+        let mut dropped = 0;
+        while self.line_status().data_ready() {
+            let byte = self.read_reg(reg::DATA);
+            if self.rx.push_back(byte).is_err() {
+                dropped += 1;
+            }
+        }
+        dropped
+    }
+}
+
+/// Estado de las cuatro UART, indexado por `ComPort`.
+static UARTS: [IrqSpinlock<Uart>; 4] = [
+    IrqSpinlock::new(&lock_order::UART, Uart::new(ComPort::Com1.base())),
+    IrqSpinlock::new(&lock_order::UART, Uart::new(ComPort::Com2.base())),
+    IrqSpinlock::new(&lock_order::UART, Uart::new(ComPort::Com3.base())),
+    IrqSpinlock::new(&lock_order::UART, Uart::new(ComPort::Com4.base())),
+];
+
+/// Indica, por puerto, si la UART se configuró correctamente.
+static PRESENT: [AtomicBool; 4] = [const { AtomicBool::new(false) }; 4];
+/// Bytes recibidos que se descartaron por tener el búfer lleno.
+static RX_DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Manejador de uno de los puertos serie.
+///
+/// Es un simple identificador: el estado real vive en una tabla global
+/// protegida por locks, así que puede copiarse y usarse desde cualquier módulo.
+#[derive(Debug, Clone, Copy)]
+pub struct SerialPort(ComPort);
+
+impl SerialPort {
+    /// Devuelve el manejador del puerto indicado.
+    pub const fn new(port: ComPort) -> Self {
+        SerialPort(port)
+    }
+
+    /// Devuelve qué puerto representa este manejador.
+    #[allow(dead_code)]
+    pub fn port(self) -> ComPort {
+        self.0
+    }
+
+    /// Configura la UART a `baud_rate` baudios, formato 8N1 y FIFO activa.
+    pub fn init(self, baud_rate: u32) -> Result<(), SerialError> {
+        let result = UARTS[self.0.index()].lock().configure(baud_rate);
+        PRESENT[self.0.index()].store(result.is_ok(), Ordering::SeqCst);
+        result
+    }
+
+    /// Indica si el puerto existe y está configurado.
+    pub fn is_present(self) -> bool {
+        PRESENT[self.0.index()].load(Ordering::SeqCst)
+    }
+
+    /// Devuelve la velocidad configurada en baudios (0 si el puerto no está configurado).
+    #[allow(dead_code)]
+    pub fn baud_rate(self) -> u32 {
+        UARTS[self.0.index()].lock().baud_rate
+    }
+
+    /// Lee el registro de estado de línea de la UART.
+    #[allow(dead_code)]
+    pub fn line_status(self) -> LineStatus {
+        UARTS[self.0.index()].lock().line_status()
+    }
+
+    /// Envía un byte. No hace nada si el puerto no está presente.
+    pub fn write_byte(self, byte: u8) {
+        if self.is_present() {
+            UARTS[self.0.index()].lock().send(byte);
+        }
+    }
+
+    /// Extrae el siguiente byte recibido, si lo hay.
+    #[allow(dead_code)]
+    pub fn read_byte(self) -> Option<u8> {
+        UARTS[self.0.index()].lock().rx.pop_front()
+    }
+}
+
+impl ByteStream for SerialPort {
+    fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut uart = UARTS[self.0.index()].lock();
+        let mut count = 0;
+        while count < buf.len() {
+            match uart.rx.pop_front() {
+                Some(byte) => buf[count] = byte,
+                None => break,
+            }
+            count += 1;
+        }
+        count
+    }
+
+    fn write(&mut self, buf: &[u8]) -> usize {
+        if !self.is_present() {
+            return 0;
+        }
+        let uart = UARTS[self.0.index()].lock();
+        for &byte in buf {
+            uart.send(byte);
+        }
+        buf.len()
+    }
+}
+
+/// Permite usar `write!` sobre un puerto serie. Los `\n` se envían como `\r\n`
+/// para que los terminales conectados al otro lado vuelvan al inicio de línea.
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+/// Escritor de emergencia sobre un puerto serie que no toma ningún lock.
+///
+/// Solo debe usarse en rutas que ya no volverán al flujo normal del kernel
+/// (pánicos, excepciones fatales), donde el lock del puerto podría haber
+/// quedado tomado por el código interrumpido.
+pub struct EmergencyWriter(ComPort);
+
+impl EmergencyWriter {
+    /// Crea el escritor de emergencia para el puerto indicado.
+    pub const fn new(port: ComPort) -> Self {
+        EmergencyWriter(port)
+    }
+}
+
+impl fmt::Write for EmergencyWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if !PRESENT[self.0.index()].load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let uart = Uart::new(self.0.base());
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                uart.send(b'\r');
+            }
+            uart.send(byte);
+        }
+        Ok(())
+    }
+}
+
+/// Inicializa los cuatro puertos a `DEFAULT_BAUD_RATE`.
+///
+/// Devuelve la máscara de las líneas de IRQ del PIC (bits 3 y 4) que usan los
+/// puertos presentes, para que el llamador las desenmascare.
+pub fn init() -> u8 {
+    let mut irq_mask = 0;
+    for port in ComPort::ALL {
+        match SerialPort::new(port).init(DEFAULT_BAUD_RATE) {
+            Ok(()) => {
+                irq_mask |= 1 << port.irq();
+                log::info!("{} inicializado a {} baudios (IRQ{})", port.name(), DEFAULT_BAUD_RATE, port.irq());
+            }
+            Err(error) => log::debug!("{} no disponible: {:?}", port.name(), error),
+        }
+    }
+    irq_mask
+}
+
+/// Llamado por los manejadores de la IRQ3 y la IRQ4.
+///
+/// Ambas líneas están compartidas por dos puertos, así que se vacían las FIFO
+/// de todos los puertos presentes que usan la línea.
+pub fn handle_interrupt(irq: u8) {
+    for port in ComPort::ALL.into_iter().filter(|port| port.irq() == irq) {
+        if PRESENT[port.index()].load(Ordering::Relaxed) {
+            let dropped = UARTS[port.index()].lock().drain_fifo();
+            RX_DROPPED.fetch_add(dropped, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Devuelve el número total de bytes recibidos descartados por falta de espacio.
+#[allow(dead_code)]
+pub fn dropped_bytes() -> u64 {
+    RX_DROPPED.load(Ordering::Relaxed)
+}
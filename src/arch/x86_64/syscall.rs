@@ -0,0 +1,110 @@
+//! Entrada al kernel desde modo usuario con `syscall`/`sysret`.
+//!
+//! La instrucción `syscall` salta a `syscall_entry` (en `syscall.S`), que
+//! cambia a la pila del kernel del hilo, guarda el estado de usuario en un
+//! [`SyscallFrame`] y llama a [`rust_syscall_handler`]. Este pasa el número y
+//! los argumentos a la tabla genérica de [`crate::syscall`] y deja el
+//! resultado en rax para `sysret`.
+//!
+//! Convenio de llamada, el mismo que el de Linux: número en rax, argumentos
+//! en rdi, rsi, rdx, r10, r8 y r9, y resultado en rax. rcx y r11 se pierden.
+
+use super::cpu;
+use super::gdt::{self, KERNEL_CODE_SELECTOR};
+
+/// MSR EFER y su bit SCE, que habilita `syscall`/`sysret`.
+const EFER: u32 = 0xC000_0080;
+const EFER_SCE: u64 = 1 << 0;
+/// MSR con los selectores que cargan `syscall` y `sysret`.
+const STAR: u32 = 0xC000_0081;
+/// MSR con la dirección de entrada de `syscall` en modo de 64 bits.
+const LSTAR: u32 = 0xC000_0082;
+/// MSR con los bits de RFLAGS que `syscall` borra al entrar.
+const FMASK: u32 = 0xC000_0084;
+/// Bits TF, IF y DF de RFLAGS: el kernel entra sin traza, sin interrupciones
+/// y con las instrucciones de cadena hacia delante.
+const RFLAGS_MASK: u64 = (1 << 8) | (1 << 9) | (1 << 10);
+
+unsafe extern "C" {
+    fn syscall_entry();
+    fn enter_user_mode(rip: u64, rsp: u64, code: u64, data: u64) -> !;
+}
+
+/// Pila del kernel del hilo actual, en la que empieza `syscall_entry`.
+#[unsafe(export_name = "syscall_kernel_rsp")]
+static mut KERNEL_RSP: u64 = 0;
+/// Hueco donde `syscall_entry` guarda un instante el rsp de usuario.
+#[unsafe(export_name = "syscall_user_rsp")]
+static mut USER_RSP: u64 = 0;
+
+/// Estado de usuario guardado por `syscall_entry`, de la dirección más baja
+/// a la más alta.
+#[derive(Debug)]
+#[repr(C)]
+pub struct SyscallFrame {
+    /// Número de la llamada al entrar; resultado al salir.
+    pub rax: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub r10: u64,
+    pub r8: u64,
+    pub r9: u64,
+    /// RFLAGS de usuario, que `syscall` deja en r11.
+    pub rflags: u64,
+    /// Dirección de retorno, que `syscall` deja en rcx.
+    pub rip: u64,
+    pub rsp: u64,
+}
+
+/// Habilita `syscall` y programa sus MSRs. Requiere la GDT del kernel.
+pub fn init() {
+    // `syscall` carga CS = STAR[47:32] y SS = CS + 8; `sysret` carga
+    // SS = STAR[63:48] + 8 y CS = STAR[63:48] + 16, ambos con RPL 3. Con la
+    // base en el selector de datos del kernel salen los de usuario de la GDT.
+    let star = (((gdt::USER_DATA_SELECTOR as u64 - 8) & !3) << 48) | ((KERNEL_CODE_SELECTOR as u64) << 32);
+    unsafe {
+        cpu::wrmsr(STAR, star);
+        cpu::wrmsr(LSTAR, syscall_entry as *const () as u64);
+        cpu::wrmsr(FMASK, RFLAGS_MASK);
+        cpu::wrmsr(EFER, cpu::rdmsr(EFER) | EFER_SCE);
+    }
+}
+
+/// Anota la pila del kernel del hilo que va a ejecutarse, tanto para
+/// `syscall` como para las interrupciones que lleguen en modo usuario.
+///
+/// Hay que llamarla con las interrupciones deshabilitadas.
+pub fn set_kernel_stack(top: u64) {
+    unsafe { KERNEL_RSP = top };
+    gdt::set_kernel_stack(top);
+}
+
+/// Salta a modo usuario en `rip` con la pila `rsp` y no vuelve.
+///
+/// # Safety
+///
+/// El espacio de direcciones activo tiene que mapear `rip` y la pila como
+/// páginas de usuario, y [`set_kernel_stack`] tiene que apuntar a la pila
+/// del kernel del hilo actual.
+pub unsafe fn enter_user(rip: u64, rsp: u64) -> ! {
+    unsafe { enter_user_mode(rip, rsp, gdt::USER_CODE_SELECTOR as u64, gdt::USER_DATA_SELECTOR as u64) }
+}
+
+/// Manejador común en Rust de las llamadas al sistema.
+/// Esta función es llamada desde `syscall_entry` en `syscall.S`.
+#[unsafe(no_mangle)] // Requerido por la edición 2024 para atributos `extern`.
+pub extern "C" fn rust_syscall_handler(frame: &mut SyscallFrame) {
+    // Las llamadas pueden bloquearse: se atienden con interrupciones. Hay
+    // que habilitarlas también antes de terminar el proceso, que puede
+    // esperar a locks de otros hilos.
+    unsafe { cpu::enable_interrupts() };
+    // `sysret` a una dirección no canónica fallaría ya en ring 0 con la pila
+    // de usuario; una dirección así solo puede venir de un programa roto.
+    if frame.rip >= crate::process::USER_END {
+        crate::process::exit(-1);
+    }
+    let arguments = [frame.rdi, frame.rsi, frame.rdx, frame.r10, frame.r8, frame.r9];
+    frame.rax = crate::syscall::dispatch(frame.rax, arguments) as u64;
+    cpu::disable_interrupts();
+}
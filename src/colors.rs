@@ -41,6 +41,8 @@ pub const BRIGHT_VIOLET: Color = 0x00A259FF;
 pub const SMOKE_WHITE: Color = 0x00EAEAEA;
 /// Color de acento secundario para destacar información. (Verde Neón)
 pub const NEON_GREEN: Color = 0x003DFFB4;
+/// Color para errores y avisos críticos. (Carmesí)
+pub const CRIMSON: Color = 0x00FF3B5C;
 
 // --- Colores Semánticos para UI ---
 
@@ -48,6 +50,10 @@ pub const NEON_GREEN: Color = 0x003DFFB4;
 pub const BACKGROUND_COLOR: Color = DEEP_BLACK;
 /// Color de acento por defecto.
 pub const ACCENT_COLOR: Color = BRIGHT_VIOLET;
+/// Color para señalar errores.
+pub const ERROR_COLOR: Color = CRIMSON;
+/// Color de fondo de las pantallas de fallo fatal.
+pub const FAULT_BACKGROUND: Color = DARK_PURPLE;
 
 /// Color primario para el texto (el más común).
 pub const TEXT_PRIMARY: Color = SMOKE_WHITE;
@@ -0,0 +1,545 @@
+//! Consola global del kernel.
+//!
+//! La consola es un objeto global protegido por un lock, de modo que cualquier
+//! módulo (drivers, manejadores de interrupción, el sistema de registro...)
+//! puede escribir en ella con las macros `print!`, `println!` y `eprintln!`.
+//!
+//! El texto no se dibuja directamente en píxeles: se guarda en una rejilla de
+//! celdas (`TextGrid`) y la consola pinta en el framebuffer solo las celdas que
+//! cambian. Gracias a ello puede repintarse por completo después de una
+//! aplicación a pantalla completa, mostrar un cursor parpadeante y recolocar
+//! el texto cuando cambian la fuente o la resolución.
+//!
+//! El texto admite secuencias de escape ANSI (colores, movimiento del cursor,
+//! borrado), que interpreta el módulo `ansi`.
+//!
+//! El código que necesita la pantalla durante un rato (la shell, las
+//! aplicaciones) la toma con [`lock`]. Si una interrupción intenta imprimir
+//! mientras tanto, no espera al lock (lo que bloquearía el sistema): su texto
+//! se guarda en una cola de pendientes que se vuelca en cuanto la consola
+//! queda libre.
+
+use core::fmt::{self, Write};
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+use embedded_graphics::mono_font::MonoFont;
+use profont::{
+    PROFONT_10_POINT, PROFONT_12_POINT, PROFONT_14_POINT, PROFONT_18_POINT, PROFONT_24_POINT,
+    PROFONT_7_POINT, PROFONT_9_POINT,
+};
+use spin::{Mutex, MutexGuard};
+
+use crate::arch;
+use crate::arch::target::serial::{ComPort, SerialPort};
+use crate::ansi::{Action, Parser};
+use crate::colors;
+use crate::log;
+use crate::sync::{lock_order, IrqSpinlock};
+use crate::task::timer;
+use crate::text_grid::{Cell, Damage, TextGrid};
+use crate::time;
+use crate::vga::FramebufferWriter;
+
+/// Número de caracteres que pueden quedar pendientes mientras la consola está ocupada.
+const PENDING_CAPACITY: usize = 1024;
+/// Margen entre el borde de la pantalla y la rejilla de texto, en píxeles.
+const MARGIN: usize = 20;
+/// Duración de cada fase (visible/oculto) del parpadeo del cursor, en milisegundos.
+const BLINK_INTERVAL_MS: u128 = 500;
+/// Espera antes de reintentar el parpadeo cuando la consola está ocupada, en milisegundos.
+const BLINK_RETRY_MS: u64 = 50;
+/// Alto del cursor (una barra en la parte inferior de la celda), en píxeles.
+const CURSOR_HEIGHT: usize = 2;
+
+/// Fuentes disponibles para la consola, por tamaño en puntos.
+const FONTS: [(u8, &MonoFont<'static>); 7] = [
+    (7, &PROFONT_7_POINT),
+    (9, &PROFONT_9_POINT),
+    (10, &PROFONT_10_POINT),
+    (12, &PROFONT_12_POINT),
+    (14, &PROFONT_14_POINT),
+    (18, &PROFONT_18_POINT),
+    (24, &PROFONT_24_POINT),
+];
+
+/// La consola global, sin framebuffer hasta que se llama a `init`.
+///
+/// No es un `IrqSpinlock`: se mantiene tomada mientras dura una aplicación a
+/// pantalla completa, y las interrupciones no pueden quedar deshabilitadas
+/// tanto tiempo. Las interrupciones nunca la esperan: usan `try_lock`.
+static CONSOLE: Mutex<Console> = Mutex::new(Console::new());
+
+/// Caracteres impresos mientras la consola estaba ocupada, con su color
+/// (`None` para usar el color actual de la consola).
+static PENDING: IrqSpinlock<heapless::Deque<(Option<colors::Color>, char), PENDING_CAPACITY>> =
+    IrqSpinlock::new(&lock_order::CONSOLE_PENDING, heapless::Deque::new());
+
+/// Indica si la consola dejó de pintar para siempre; ver [`stop`].
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// La consola de texto del kernel: una rejilla de celdas pintada sobre el framebuffer.
+pub struct Console {
+    /// Escritor del framebuffer en el que se pinta la rejilla.
+    writer: Option<FramebufferWriter>,
+    /// Contenido de texto de la consola.
+    grid: TextGrid,
+    /// Intérprete de las secuencias de escape del texto escrito.
+    parser: Parser,
+    /// Fuente con la que se pintan las celdas (`None` para la fuente por defecto).
+    font: Option<&'static MonoFont<'static>>,
+    /// Momento (en ms de actividad) en que empezó el ciclo de parpadeo actual.
+    blink_epoch: u128,
+    /// Indica si el cursor está en su fase visible.
+    cursor_visible: bool,
+    /// Celda sobre la que está pintado el cursor, si lo está.
+    drawn_cursor: Option<(usize, usize)>,
+    /// Contenido de esa celda en el momento de pintar el cursor.
+    cell_under_cursor: Cell,
+}
+
+impl Console {
+    /// Crea la consola sin framebuffer. Como la rejilla, empieza a cero.
+    const fn new() -> Self {
+        Self {
+            writer: None,
+            grid: TextGrid::new(),
+            parser: Parser::new(),
+            font: None,
+            blink_epoch: 0,
+            cursor_visible: false,
+            drawn_cursor: None,
+            cell_under_cursor: Cell::UNWRITTEN,
+        }
+    }
+
+    /// Fuente con la que se pintan las celdas.
+    fn font(&self) -> &'static MonoFont<'static> {
+        self.font.unwrap_or(&PROFONT_14_POINT)
+    }
+
+    /// Ancho y alto de una celda con la fuente actual, en píxeles.
+    fn cell_size(&self) -> (usize, usize) {
+        let font = self.font();
+        let width = font.character_size.width + font.character_spacing;
+        (width as usize, font.character_size.height as usize)
+    }
+
+    /// Cambia el framebuffer de la consola (por ejemplo, tras un cambio de
+    /// resolución) y recoloca el texto en la nueva pantalla.
+    pub fn set_framebuffer(&mut self, writer: FramebufferWriter) {
+        let first = self.writer.is_none();
+        self.writer = Some(writer);
+        if first {
+            self.grid.set_foreground(colors::TEXT_PRIMARY);
+            self.grid.clear(colors::BACKGROUND_COLOR);
+        }
+        self.relayout();
+    }
+
+    /// Cambia la fuente de la consola y recoloca el texto con el nuevo tamaño de celda.
+    pub fn set_font(&mut self, font: &'static MonoFont<'static>) {
+        self.font = Some(font);
+        self.relayout();
+    }
+
+    /// Recalcula las dimensiones de la rejilla a partir de la resolución y la
+    /// fuente actuales, y la repinta entera.
+    fn relayout(&mut self) {
+        let Some(writer) = &self.writer else { return };
+        let (cell_width, cell_height) = self.cell_size();
+        let columns = writer.width().saturating_sub(2 * MARGIN) / cell_width;
+        let rows = writer.height().saturating_sub(2 * MARGIN) / cell_height;
+        self.grid.resize(columns, rows);
+        self.drawn_cursor = None;
+        self.present();
+    }
+
+    /// Número de columnas de texto de la consola.
+    pub fn columns(&self) -> usize {
+        self.grid.columns()
+    }
+
+    /// Número de filas de texto de la consola.
+    pub fn rows(&self) -> usize {
+        self.grid.height()
+    }
+
+    /// Devuelve el color de texto actual.
+    pub fn color(&self) -> colors::Color {
+        self.grid.foreground()
+    }
+
+    /// Cambia el color de texto actual.
+    pub fn set_color(&mut self, color: colors::Color) {
+        self.grid.set_foreground(color);
+    }
+
+    /// Borra la pantalla con el color de fondo dado.
+    pub fn clear(&mut self, color: colors::Color) {
+        self.grid.clear(color);
+        self.restart_blink();
+        self.present();
+    }
+
+    /// Borra el carácter anterior al cursor.
+    pub fn backspace(&mut self) {
+        self.grid.backspace();
+        self.restart_blink();
+        self.present();
+    }
+
+    /// Número de filas que avanza la vista al pasar una página del historial.
+    pub fn page_rows(&self) -> usize {
+        self.grid.height().saturating_sub(1).max(1)
+    }
+
+    /// Desplaza la vista `rows` filas hacia atrás en el historial.
+    pub fn scroll_view_up(&mut self, rows: usize) {
+        self.grid.scroll_view_up(rows);
+        self.present();
+    }
+
+    /// Desplaza la vista `rows` filas hacia delante en el historial.
+    pub fn scroll_view_down(&mut self, rows: usize) {
+        self.grid.scroll_view_down(rows);
+        self.present();
+    }
+
+    /// Cede el framebuffer completo a una aplicación gráfica.
+    ///
+    /// Mientras `app` se ejecuta, la consola no pinta nada; al terminar, la
+    /// rejilla se repinta entera, de modo que la pantalla vuelve a mostrar el
+    /// texto que había antes. El escritor tiene búfer trasero: la aplicación
+    /// llama a `present()` cuando quiere que se vea lo que ha dibujado.
+    pub fn fullscreen<R>(&mut self, app: impl FnOnce(&mut FramebufferWriter) -> R) -> R {
+        // `lock` solo entrega la consola cuando ya tiene un framebuffer.
+        let result = app(self.writer.as_mut().unwrap());
+        self.drawn_cursor = None;
+        self.repaint();
+        result
+    }
+
+    /// Igual que [`fullscreen`](Self::fullscreen), para aplicaciones que
+    /// esperan eventos mientras ocupan la pantalla. La consola sigue tomada
+    /// durante la espera: lo que se imprima mientras tanto queda pendiente
+    /// y aparece al soltarla.
+    pub async fn fullscreen_async<R>(&mut self, app: impl AsyncFnOnce(&mut FramebufferWriter) -> R) -> R {
+        let result = app(self.writer.as_mut().unwrap()).await;
+        self.drawn_cursor = None;
+        self.repaint();
+        result
+    }
+
+    /// Vuelve a pintar toda la rejilla.
+    pub fn repaint(&mut self) {
+        self.grid.invalidate();
+        self.present();
+    }
+
+    /// Avanza el parpadeo del cursor y devuelve los milisegundos que faltan
+    /// para el próximo cambio.
+    pub fn blink(&mut self) -> u64 {
+        let elapsed = time::uptime().as_millis().saturating_sub(self.blink_epoch);
+        let visible = (elapsed / BLINK_INTERVAL_MS).is_multiple_of(2);
+        if visible != self.cursor_visible {
+            self.cursor_visible = visible;
+            self.present();
+        }
+        (BLINK_INTERVAL_MS - elapsed % BLINK_INTERVAL_MS) as u64
+    }
+
+    /// Hace visible el cursor y reinicia su parpadeo, para que no desaparezca
+    /// mientras se escribe.
+    fn restart_blink(&mut self) {
+        self.blink_epoch = time::uptime().as_millis();
+        self.cursor_visible = true;
+    }
+
+    /// Pinta en el framebuffer los cambios de la rejilla desde el último pintado.
+    fn present(&mut self) {
+        let font = self.font();
+        let (cell_width, cell_height) = self.cell_size();
+        let Some(writer) = &mut self.writer else { return };
+        let draw = |writer: &mut FramebufferWriter, column: usize, row: usize, cell: Cell| {
+            let (x, y) = (MARGIN + column * cell_width, MARGIN + row * cell_height);
+            writer.draw_cell(x, y, cell.c, font, cell.fg, cell.bg);
+        };
+
+        // Primero se borra el cursor, dejando la celda como estaba cuando se
+        // pintó: así el desplazamiento de píxeles que sigue mueve contenido coherente.
+        if let Some((column, row)) = self.drawn_cursor.take() {
+            draw(writer, column, row, self.cell_under_cursor);
+        }
+
+        let rows = self.grid.height();
+        match self.grid.take_damage() {
+            Damage::Full => {
+                let (width, height) = (writer.width(), writer.height());
+                writer.draw_rect(0, 0, width, height, self.grid.default_background());
+                for row in 0..rows {
+                    for column in 0..self.grid.columns() {
+                        draw(writer, column, row, self.grid.visible_cell(column, row));
+                    }
+                }
+            }
+            Damage::Partial { scrolled } => {
+                if scrolled > 0 {
+                    let bg = self.grid.default_background();
+                    writer.scroll_region(MARGIN, rows * cell_height, scrolled * cell_height, bg);
+                }
+                for row in 0..rows {
+                    if let Some((start, end)) = self.grid.take_dirty_span(row) {
+                        for column in start..end {
+                            draw(writer, column, row, self.grid.visible_cell(column, row));
+                        }
+                    }
+                }
+            }
+        }
+
+        if self.cursor_visible && self.grid.at_bottom() {
+            let (column, row) = self.grid.cursor();
+            let column = column.min(self.grid.columns() - 1);
+            let cell = self.grid.visible_cell(column, row);
+            let (x, y) = (MARGIN + column * cell_width, MARGIN + row * cell_height);
+            writer.draw_rect(x, y + cell_height - CURSOR_HEIGHT, cell_width, CURSOR_HEIGHT, cell.fg);
+            self.drawn_cursor = Some((column, row));
+            self.cell_under_cursor = cell;
+        }
+        writer.present();
+    }
+
+    /// Escribe texto en la rejilla sin pintarlo todavía.
+    fn put_str(&mut self, s: &str) {
+        for c in s.chars() {
+            self.put_char(c);
+        }
+    }
+
+    /// Pasa un carácter por el intérprete de escapes y aplica a la rejilla
+    /// las acciones que produzca.
+    fn put_char(&mut self, c: char) {
+        let grid = &mut self.grid;
+        self.parser.advance(c, |action| match action {
+            Action::Print(c) | Action::Control(c) => grid.write_char(c),
+            Action::Foreground(color) => grid.set_foreground(color.unwrap_or(colors::TEXT_PRIMARY)),
+            Action::Background(color) => {
+                let default = grid.default_background();
+                grid.set_background(color.unwrap_or(default));
+            }
+            Action::MoveCursor(columns, rows) => grid.move_cursor(columns, rows),
+            Action::CursorColumn(column) => {
+                let (_, row) = grid.cursor();
+                grid.set_cursor(column, row);
+            }
+            Action::CursorPosition(column, row) => grid.set_cursor(column, row),
+            Action::EraseInLine(erase) => grid.erase_in_line(erase),
+            Action::EraseInDisplay(erase) => grid.erase_in_display(erase),
+            Action::SaveCursor => grid.save_cursor(),
+            Action::RestoreCursor => grid.restore_cursor(),
+        });
+    }
+}
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.put_str(s);
+        self.restart_blink();
+        self.present();
+        Ok(())
+    }
+}
+
+/// Devuelve la fuente de la consola con el tamaño dado en puntos, si existe.
+pub fn font(points: u8) -> Option<&'static MonoFont<'static>> {
+    FONTS.iter().find(|(size, _)| *size == points).map(|(_, font)| *font)
+}
+
+/// Instala el framebuffer como pantalla de la consola global del kernel.
+pub fn init(writer: FramebufferWriter) {
+    let (width, height, format) = (writer.width(), writer.height(), writer.format());
+    arch::without_interrupts(|| CONSOLE.lock().set_framebuffer(writer));
+    log::info!("framebuffer {}x{}, formato {}", width, height, format);
+}
+
+/// Deja de pintar la consola para siempre, tenga o no alguien su lock.
+///
+/// Lo usa el informe de fallo antes de dibujar sobre el framebuffer con su
+/// propio escritor: a partir de aquí el texto impreso se descarta y el de la
+/// cola de pendientes ya no se vuelca, así que nada pinta encima del informe.
+pub fn stop() {
+    STOPPED.store(true, Ordering::SeqCst);
+}
+
+/// Acceso exclusivo a la consola, obtenido con [`lock`].
+///
+/// Al soltarlo se vuelca el texto que otros contextos hayan impreso mientras
+/// estaba tomado.
+pub struct ConsoleGuard {
+    guard: Option<MutexGuard<'static, Console>>,
+}
+
+/// Toma la consola para escribir o dibujar en ella.
+///
+/// Solo debe llamarse desde código normal, nunca desde un manejador de
+/// interrupción: las interrupciones deben usar las macros de impresión.
+/// Devuelve `None` si la consola aún no se ha inicializado.
+pub fn lock() -> Option<ConsoleGuard> {
+    let guard = CONSOLE.lock();
+    guard.writer.is_some().then_some(ConsoleGuard { guard: Some(guard) })
+}
+
+impl Deref for ConsoleGuard {
+    type Target = Console;
+
+    fn deref(&self) -> &Console {
+        // El guard solo se vacía al soltarlo.
+        self.guard.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for ConsoleGuard {
+    fn deref_mut(&mut self) -> &mut Console {
+        self.guard.as_mut().unwrap()
+    }
+}
+
+impl Drop for ConsoleGuard {
+    fn drop(&mut self) {
+        // El vaciado y la liberación del lock se hacen sin interrupciones, para
+        // que ningún texto quede en la cola justo después de vaciarla.
+        arch::without_interrupts(|| {
+            if let Some(mut console) = self.guard.take() {
+                flush_pending(&mut console);
+            }
+        });
+    }
+}
+
+/// Tarea que hace parpadear el cursor de la consola.
+///
+/// Duerme hasta el siguiente cambio de fase; si la consola está ocupada,
+/// lo vuelve a intentar al cabo de un rato.
+pub async fn blink_cursor() {
+    loop {
+        let wait = match CONSOLE.try_lock() {
+            Some(mut console) if console.writer.is_some() && !STOPPED.load(Ordering::SeqCst) => console.blink(),
+            _ => BLINK_RETRY_MS,
+        };
+        timer::sleep_ms(wait).await;
+    }
+}
+
+/// Escribe en la consola los caracteres pendientes.
+fn flush_pending(console: &mut Console) {
+    if STOPPED.load(Ordering::SeqCst) {
+        return;
+    }
+    let previous = console.color();
+    let mut flushed = false;
+    while let Some((color, c)) = PENDING.lock().pop_front() {
+        console.set_color(color.unwrap_or(previous));
+        console.put_char(c);
+        flushed = true;
+    }
+    console.set_color(previous);
+    if flushed {
+        console.present();
+    }
+}
+
+/// Escribe en la consola con el color indicado si está libre, o encola el
+/// texto si no lo está. Es seguro llamarla desde una interrupción.
+pub fn print_colored(color: colors::Color, args: fmt::Arguments) {
+    write_console(Some(color), args);
+}
+
+/// Destino común de las macros de impresión y de `print_colored`.
+fn write_console(color: Option<colors::Color>, args: fmt::Arguments) {
+    if STOPPED.load(Ordering::SeqCst) {
+        return;
+    }
+    arch::without_interrupts(|| match CONSOLE.try_lock() {
+        Some(mut console) => {
+            if console.writer.is_some() {
+                flush_pending(&mut console);
+                let previous = console.color();
+                console.set_color(color.unwrap_or(previous));
+                let _ = console.write_fmt(args);
+                console.set_color(previous);
+            }
+        }
+        None => {
+            let _ = Pending(color).write_fmt(args);
+        }
+    });
+}
+
+/// Escritor que añade texto a la cola de pendientes. Si la cola se llena, el
+/// resto del texto se descarta.
+struct Pending(Option<colors::Color>);
+
+impl fmt::Write for Pending {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut pending = PENDING.lock();
+        for c in s.chars() {
+            if pending.push_back((self.0, c)).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Implementación de `print!` y `println!`.
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    write_console(None, args);
+}
+
+/// Implementación de `eprint!` y `eprintln!`: el texto se muestra en el color
+/// de error y se copia también en el puerto serie COM1.
+#[doc(hidden)]
+pub fn _eprint(args: fmt::Arguments) {
+    let _ = SerialPort::new(ComPort::Com1).write_fmt(args);
+    write_console(Some(colors::ERROR_COLOR), args);
+}
+
+/// Imprime en la consola del kernel.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::console::_print(format_args!($($arg)*))
+    };
+}
+
+/// Imprime en la consola del kernel, seguido de un salto de línea.
+#[macro_export]
+macro_rules! println {
+    () => {
+        $crate::print!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::console::_print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+/// Imprime un mensaje de error en la consola del kernel y en el puerto serie.
+#[macro_export]
+macro_rules! eprint {
+    ($($arg:tt)*) => {
+        $crate::console::_eprint(format_args!($($arg)*))
+    };
+}
+
+/// Imprime un mensaje de error en la consola del kernel y en el puerto serie,
+/// seguido de un salto de línea.
+#[macro_export]
+macro_rules! eprintln {
+    () => {
+        $crate::eprint!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::console::_eprint(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
@@ -0,0 +1,125 @@
+//! Seguimiento de las zonas modificadas de la pantalla.
+//!
+//! Cuando se dibuja sobre un búfer trasero, solo hay que copiar a la memoria
+//! de vídeo las zonas que han cambiado. `DirtyRegion` las guarda como una
+//! lista corta de rectángulos: los que se solapan o se tocan se unen, y si
+//! la lista se llena, el nuevo rectángulo se une con el que menos crezca.
+
+/// Número máximo de rectángulos que se guardan por separado.
+const MAX_RECTS: usize = 16;
+
+/// Un rectángulo de píxeles. Todos los campos a cero es el rectángulo vacío.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    /// Rectángulo vacío.
+    pub const EMPTY: Rect = Rect::new(0, 0, 0, 0);
+
+    pub const fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Indica si el rectángulo no contiene ningún píxel.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Coordenada x siguiente al borde derecho.
+    pub fn right(&self) -> usize {
+        self.x + self.width
+    }
+
+    /// Coordenada y siguiente al borde inferior.
+    pub fn bottom(&self) -> usize {
+        self.y + self.height
+    }
+
+    /// Indica si el píxel `(x, y)` está dentro del rectángulo.
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// Número de píxeles del rectángulo.
+    fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Recorta el rectángulo a una superficie de `width` x `height` píxeles.
+    pub fn clip(&self, width: usize, height: usize) -> Rect {
+        let x = self.x.min(width);
+        let y = self.y.min(height);
+        Rect::new(x, y, self.right().min(width) - x, self.bottom().min(height) - y)
+    }
+
+    /// Menor rectángulo que contiene a los dos.
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect::new(x, y, self.right().max(other.right()) - x, self.bottom().max(other.bottom()) - y)
+    }
+
+    /// Indica si los rectángulos se solapan o comparten un borde.
+    fn touches(&self, other: &Rect) -> bool {
+        self.x <= other.right() && other.x <= self.right() && self.y <= other.bottom() && other.y <= self.bottom()
+    }
+}
+
+/// Conjunto de rectángulos modificados desde la última presentación.
+///
+/// Todos sus campos empiezan a cero, así que puede vivir en `.bss`.
+pub struct DirtyRegion {
+    rects: [Rect; MAX_RECTS],
+    len: usize,
+}
+
+impl DirtyRegion {
+    pub const fn new() -> Self {
+        Self { rects: [Rect::EMPTY; MAX_RECTS], len: 0 }
+    }
+
+    /// Añade un rectángulo a la región.
+    pub fn add(&mut self, rect: Rect) {
+        if rect.is_empty() {
+            return;
+        }
+        // Absorbe todos los rectángulos que toca; al crecer puede tocar otros
+        // que antes no tocaba, así que se repite hasta que no quede ninguno.
+        let mut rect = rect;
+        let mut i = 0;
+        while i < self.len {
+            if self.rects[i].touches(&rect) {
+                rect = rect.union(&self.rects[i]);
+                self.len -= 1;
+                self.rects[i] = self.rects[self.len];
+                i = 0;
+            } else {
+                i += 1;
+            }
+        }
+        if self.len < MAX_RECTS {
+            self.rects[self.len] = rect;
+            self.len += 1;
+            return;
+        }
+        // Lista llena: se une con el rectángulo que menos área añade.
+        let growth = |existing: &Rect| existing.union(&rect).area() - existing.area();
+        let (index, _) = self.rects.iter().enumerate().min_by_key(|(_, existing)| growth(existing)).unwrap();
+        self.rects[index] = self.rects[index].union(&rect);
+    }
+
+    /// Rectángulos de la región.
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects[..self.len]
+    }
+
+    /// Vacía la región.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
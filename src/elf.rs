@@ -0,0 +1,276 @@
+//! Cargador de ejecutables ELF64.
+//!
+//! Solo se admiten ejecutables estáticos de x86_64 (`ET_EXEC`): cada
+//! segmento `PT_LOAD` se copia en páginas nuevas del espacio de direcciones
+//! del proceso, con los permisos que declara, y la parte que no viene en el
+//! fichero (`.bss`) queda a cero. No hay enlazador dinámico, así que los
+//! ejecutables reubicables o con intérprete se rechazan.
+
+use core::fmt;
+
+use crate::arch::target::paging::{AddressSpace, Flags, MapError, PAGE_SIZE};
+use crate::process::{self, USER_STACK_BASE};
+
+/// Firma del principio de todo fichero ELF.
+const MAGIC: [u8; 4] = *b"\x7FELF";
+/// `e_ident[EI_CLASS]` de los ficheros de 64 bits.
+const CLASS_64: u8 = 2;
+/// `e_ident[EI_DATA]` de los ficheros little-endian.
+const DATA_LSB: u8 = 1;
+/// Única versión de ELF que existe.
+const VERSION_CURRENT: u8 = 1;
+/// `e_type` de un ejecutable de direcciones fijas.
+const TYPE_EXEC: u16 = 2;
+/// `e_type` de un objeto compartido o ejecutable reubicable (PIE).
+const TYPE_DYN: u16 = 3;
+/// `e_machine` de x86_64.
+const MACHINE_X86_64: u16 = 62;
+
+/// Tamaño de la cabecera del fichero.
+const HEADER_SIZE: usize = 64;
+/// Tamaño de una cabecera de programa.
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+/// Tipos de cabecera de programa que entiende el cargador.
+const PT_LOAD: u32 = 1;
+const PT_INTERP: u32 = 3;
+const PT_PHDR: u32 = 6;
+
+/// Permisos de un segmento (`p_flags`).
+const PF_X: u32 = 1 << 0;
+const PF_W: u32 = 1 << 1;
+
+/// Errores al cargar un ejecutable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// El fichero es más corto que lo que declaran sus cabeceras.
+    Truncated,
+    /// No empieza por la firma de ELF.
+    NotElf,
+    /// Es de 32 bits o big-endian.
+    UnsupportedFormat,
+    /// Es de otra arquitectura; contiene `e_machine`.
+    UnsupportedMachine(u16),
+    /// Es reubicable (PIE) o necesita un enlazador dinámico.
+    Dynamic,
+    /// No es un ejecutable; contiene `e_type`.
+    NotExecutable(u16),
+    /// Las cabeceras de programa tienen un tamaño inesperado.
+    BadProgramHeaders,
+    /// Un segmento es incoherente, se sale de la memoria de usuario o se
+    /// solapa con la pila.
+    BadSegment,
+    /// No tiene segmentos que cargar.
+    NoSegments,
+    /// El punto de entrada no está en ningún segmento ejecutable.
+    BadEntry,
+    /// Falló el mapeo de la memoria del proceso.
+    Map(MapError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Truncated => f.write_str("el fichero está truncado"),
+            Error::NotElf => f.write_str("no es un fichero ELF"),
+            Error::UnsupportedFormat => f.write_str("solo se admiten ELF de 64 bits little-endian"),
+            Error::UnsupportedMachine(machine) => write!(f, "arquitectura no soportada (e_machine {})", machine),
+            Error::Dynamic => f.write_str("los ejecutables dinámicos o reubicables no están soportados"),
+            Error::NotExecutable(kind) => write!(f, "no es un ejecutable (e_type {})", kind),
+            Error::BadProgramHeaders => f.write_str("cabeceras de programa inválidas"),
+            Error::BadSegment => f.write_str("segmento inválido, fuera de la memoria de usuario o sobre la pila"),
+            Error::NoSegments => f.write_str("no hay segmentos que cargar"),
+            Error::BadEntry => f.write_str("el punto de entrada no es ejecutable"),
+            Error::Map(error) => write!(f, "no se pudo mapear la memoria: {}", error),
+        }
+    }
+}
+
+impl From<MapError> for Error {
+    fn from(error: MapError) -> Error {
+        Error::Map(error)
+    }
+}
+
+/// Un ejecutable cargado en un espacio de direcciones.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadedImage {
+    /// Dirección del punto de entrada.
+    pub entry: u64,
+    /// Dirección de las cabeceras de programa en la memoria del proceso, si
+    /// están dentro de algún segmento.
+    pub program_headers: Option<u64>,
+    /// Tamaño de cada cabecera de programa.
+    pub program_header_size: u64,
+    /// Número de cabeceras de programa.
+    pub program_header_count: u64,
+}
+
+/// Una cabecera de programa.
+struct ProgramHeader {
+    kind: u32,
+    flags: u32,
+    offset: u64,
+    vaddr: u64,
+    file_size: u64,
+    memory_size: u64,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Un fichero ELF cuya cabecera ya se ha validado.
+struct Elf<'a> {
+    bytes: &'a [u8],
+    entry: u64,
+    program_header_offset: usize,
+    program_header_count: usize,
+}
+
+impl<'a> Elf<'a> {
+    /// Valida la cabecera del fichero y la tabla de cabeceras de programa.
+    fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+            return Err(Error::NotElf);
+        }
+        if bytes.len() < HEADER_SIZE {
+            return Err(Error::Truncated);
+        }
+        if bytes[4] != CLASS_64 || bytes[5] != DATA_LSB || bytes[6] != VERSION_CURRENT {
+            return Err(Error::UnsupportedFormat);
+        }
+        match u16_at(bytes, 16) {
+            TYPE_EXEC => {}
+            TYPE_DYN => return Err(Error::Dynamic),
+            kind => return Err(Error::NotExecutable(kind)),
+        }
+        let machine = u16_at(bytes, 18);
+        if machine != MACHINE_X86_64 {
+            return Err(Error::UnsupportedMachine(machine));
+        }
+        let entry = u64_at(bytes, 24);
+        let program_header_offset = usize::try_from(u64_at(bytes, 32)).map_err(|_| Error::Truncated)?;
+        let program_header_size = u16_at(bytes, 54) as usize;
+        let program_header_count = u16_at(bytes, 56) as usize;
+        if program_header_count > 0 && program_header_size != PROGRAM_HEADER_SIZE {
+            return Err(Error::BadProgramHeaders);
+        }
+        let table_end = program_header_count
+            .checked_mul(PROGRAM_HEADER_SIZE)
+            .and_then(|size| size.checked_add(program_header_offset));
+        if table_end.is_none_or(|end| end > bytes.len()) {
+            return Err(Error::Truncated);
+        }
+        Ok(Self { bytes, entry, program_header_offset, program_header_count })
+    }
+
+    /// Cabeceras de programa, en el orden del fichero.
+    fn program_headers(&self) -> impl Iterator<Item = ProgramHeader> + '_ {
+        (0..self.program_header_count).map(|index| {
+            let header = &self.bytes[self.program_header_offset + index * PROGRAM_HEADER_SIZE..];
+            ProgramHeader {
+                kind: u32_at(header, 0),
+                flags: u32_at(header, 4),
+                offset: u64_at(header, 8),
+                vaddr: u64_at(header, 16),
+                file_size: u64_at(header, 32),
+                memory_size: u64_at(header, 40),
+            }
+        })
+    }
+}
+
+impl ProgramHeader {
+    /// Comprueba que el segmento esté dentro del fichero y de la mitad de
+    /// usuario, por encima de la página cero y por debajo de la pila.
+    fn validate(&self, file_len: usize) -> Result<(), Error> {
+        let file_end = self.offset.checked_add(self.file_size);
+        let memory_end = self.vaddr.checked_add(self.memory_size);
+        if self.file_size > self.memory_size
+            || file_end.is_none_or(|end| end > file_len as u64)
+            || self.vaddr < PAGE_SIZE
+            || memory_end.is_none_or(|end| end > USER_STACK_BASE)
+        {
+            return Err(Error::BadSegment);
+        }
+        Ok(())
+    }
+
+    fn contains(&self, address: u64) -> bool {
+        (self.vaddr..self.vaddr + self.memory_size).contains(&address)
+    }
+}
+
+/// Carga el ejecutable `bytes` en `space`, que debe ser un espacio de
+/// usuario sin nada mapeado en la mitad inferior.
+pub fn load(space: &mut AddressSpace, bytes: &[u8]) -> Result<LoadedImage, Error> {
+    let elf = Elf::parse(bytes)?;
+    if elf.program_headers().any(|header| header.kind == PT_INTERP) {
+        return Err(Error::Dynamic);
+    }
+    let mut loaded = 0;
+    let mut entry_ok = false;
+    for header in elf.program_headers().filter(|header| header.kind == PT_LOAD) {
+        header.validate(bytes.len())?;
+        if header.memory_size == 0 {
+            continue;
+        }
+        let writable = header.flags & PF_W != 0;
+        let executable = header.flags & PF_X != 0;
+        map_segment(space, header.vaddr, header.memory_size, writable, executable)?;
+        let data = &bytes[header.offset as usize..(header.offset + header.file_size) as usize];
+        process::copy_to(space, header.vaddr, data)?;
+        entry_ok |= executable && header.contains(elf.entry);
+        loaded += 1;
+    }
+    if loaded == 0 {
+        return Err(Error::NoSegments);
+    }
+    if !entry_ok {
+        return Err(Error::BadEntry);
+    }
+
+    // Las cabeceras de programa, para el vector auxiliar: o las señala
+    // PT_PHDR, o están en el segmento que carga el principio del fichero.
+    let program_headers = elf.program_headers().find(|header| header.kind == PT_PHDR).map(|header| header.vaddr).or_else(|| {
+        let offset = elf.program_header_offset as u64;
+        elf.program_headers()
+            .find(|header| header.kind == PT_LOAD && (header.offset..header.offset + header.file_size).contains(&offset))
+            .map(|header| header.vaddr + offset - header.offset)
+    });
+    Ok(LoadedImage {
+        entry: elf.entry,
+        program_headers,
+        program_header_size: PROGRAM_HEADER_SIZE as u64,
+        program_header_count: elf.program_header_count as u64,
+    })
+}
+
+/// Mapea las páginas de un segmento. Dos segmentos pueden compartir una
+/// página en sus extremos: la página ya mapeada recibe la unión de permisos.
+fn map_segment(space: &mut AddressSpace, start: u64, len: u64, writable: bool, executable: bool) -> Result<(), Error> {
+    let first = start / PAGE_SIZE * PAGE_SIZE;
+    let end = (start + len).next_multiple_of(PAGE_SIZE);
+    for page in (first..end).step_by(PAGE_SIZE as usize) {
+        match space.translate(page) {
+            Some((_, flags)) => {
+                let shared = process::user_flags(
+                    writable || flags.contains(Flags::WRITABLE),
+                    executable || !flags.contains(Flags::NO_EXECUTE),
+                );
+                space.protect(page, shared)?;
+            }
+            None => process::map_zeroed(space, page, PAGE_SIZE, process::user_flags(writable, executable))?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! Informes de fallo fatal del kernel.
+//!
+//! Cuando el kernel ya no puede continuar (una excepción de la CPU o un
+//! pánico), este módulo toma el framebuffer y pinta un informe con la causa y
+//! el estado de la máquina, para que el fallo pueda diagnosticarse a partir de
+//! una simple captura de pantalla. Cada línea del informe se copia también en
+//! la consola de depuración y en el puerto serie COM1, de modo que queda
+//! registrada en el log del emulador aunque no haya pantalla.
+
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::arch::target::cpu;
+use crate::arch::target::debugcon::DebugCon;
+use crate::arch::target::serial::{ComPort, EmergencyWriter};
+use crate::colors;
+use crate::console;
+use crate::vga::FramebufferWriter;
+use crate::FRAMEBUFFER_REQUEST;
+
+/// Margen izquierdo y superior del informe, en píxeles.
+const MARGIN: usize = 40;
+/// Alto de la franja de título.
+const HEADER_HEIGHT: usize = 60;
+/// Separación vertical entre líneas del informe.
+const LINE_HEIGHT: usize = 20;
+/// Ancho de cada columna de la rejilla de registros.
+const COLUMN_WIDTH: usize = 260;
+
+/// Indica si ya hay un pánico en curso.
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Copia en texto plano del informe: consola de depuración y puerto COM1.
+///
+/// El puerto serie se escribe sin tomar su lock, porque el código que falló
+/// podría tenerlo tomado.
+struct FaultLog;
+
+impl fmt::Write for FaultLog {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let _ = DebugCon.write_str(s);
+        let _ = EmergencyWriter::new(ComPort::Com1).write_str(s);
+        Ok(())
+    }
+}
+
+/// Un informe de fallo que se va pintando línea a línea sobre el framebuffer
+/// y copiando en la consola de depuración y el puerto serie.
+pub struct FaultScreen {
+    /// Escritor propio sobre el framebuffer, si el gestor de arranque proporcionó uno.
+    writer: Option<FramebufferWriter>,
+    /// Copia del informe en texto plano.
+    log: FaultLog,
+    /// Posición vertical de la siguiente línea.
+    y: usize,
+    /// Columna de la rejilla de registros en la que se escribirá el siguiente.
+    column: usize,
+    /// Número de columnas de la rejilla que caben en la pantalla.
+    columns: usize,
+}
+
+impl FaultScreen {
+    /// Limpia la pantalla y dibuja la cabecera del informe con el `title` dado.
+    ///
+    /// Crea su propio `FramebufferWriter` a partir de la respuesta de Limine en
+    /// lugar de usar el del resto del kernel, de modo que funciona aunque este
+    /// haya quedado a medio dibujar o en un estado inconsistente. No toma el
+    /// lock de la consola, que puede tenerlo el código que falló: la detiene
+    /// con [`console::stop`] y deshabilita las interrupciones, así que ni
+    /// una interrupción ni el vaciado de los pendientes pintan encima, y el
+    /// código que tenía la consola no vuelve a ejecutarse.
+    pub fn open(title: &str) -> Self {
+        cpu::disable_interrupts();
+        console::stop();
+        let mut log = FaultLog;
+        let _ = writeln!(log, "\n=== VesperOS :: {} ===", title);
+
+        let writer = FRAMEBUFFER_REQUEST
+            .get_response()
+            .and_then(|response| response.framebuffers().next())
+            .map(|framebuffer| FramebufferWriter::new(&framebuffer));
+
+        let mut columns = 1;
+        let writer = writer.map(|mut writer| {
+            writer.clear(colors::FAULT_BACKGROUND);
+            let width = writer.width();
+            writer.draw_rect(0, 0, width, HEADER_HEIGHT, colors::ERROR_COLOR);
+            writer.set_cursor_position(MARGIN, (HEADER_HEIGHT - LINE_HEIGHT) / 2);
+            writer.set_color(colors::SMOKE_WHITE);
+            let _ = write!(writer, "VesperOS :: {}", title);
+            columns = ((width.saturating_sub(2 * MARGIN)) / COLUMN_WIDTH).max(1);
+            writer
+        });
+
+        Self {
+            writer,
+            log,
+            y: HEADER_HEIGHT + MARGIN / 2,
+            column: 0,
+            columns,
+        }
+    }
+
+    /// Escribe una línea de texto con el color indicado.
+    pub fn line(&mut self, color: colors::Color, args: fmt::Arguments) {
+        self.end_grid();
+        if let Some(writer) = &mut self.writer {
+            writer.set_cursor_position(MARGIN, self.y);
+            writer.set_color(color);
+            let _ = writer.write_fmt(args);
+        }
+        let _ = writeln!(self.log, "{}", args);
+        self.y += LINE_HEIGHT;
+    }
+
+    /// Escribe una línea con formato `etiqueta: valor`.
+    pub fn field(&mut self, label: &str, args: fmt::Arguments) {
+        self.end_grid();
+        if let Some(writer) = &mut self.writer {
+            writer.set_cursor_position(MARGIN, self.y);
+            writer.set_color(colors::TEXT_SECONDARY);
+            let _ = write!(writer, "{}: ", label);
+            writer.set_color(colors::TEXT_PRIMARY);
+            let _ = writer.write_fmt(args);
+        }
+        let _ = writeln!(self.log, "{}: {}", label, args);
+        self.y += LINE_HEIGHT;
+    }
+
+    /// Añade un registro a la rejilla de registros (varios por línea).
+    pub fn register(&mut self, name: &str, value: u64) {
+        if let Some(writer) = &mut self.writer {
+            writer.set_cursor_position(MARGIN + self.column * COLUMN_WIDTH, self.y);
+            writer.set_color(colors::TEXT_SECONDARY);
+            let _ = write!(writer, "{:>6}", name);
+            writer.set_color(colors::TEXT_PRIMARY);
+            let _ = write!(writer, " {:#018x}", value);
+        }
+        let _ = write!(self.log, "{:>6} {:#018x}", name, value);
+
+        self.column += 1;
+        if self.column == self.columns {
+            self.column = 0;
+            self.y += LINE_HEIGHT;
+            let _ = writeln!(self.log);
+        }
+    }
+
+    /// Deja una línea en blanco como separador.
+    pub fn gap(&mut self) {
+        self.end_grid();
+        self.y += LINE_HEIGHT / 2;
+    }
+
+    /// Cierra la fila de la rejilla de registros si quedó a medias.
+    fn end_grid(&mut self) {
+        if self.column != 0 {
+            self.column = 0;
+            self.y += LINE_HEIGHT;
+            let _ = writeln!(self.log);
+        }
+    }
+}
+
+/// Pinta el informe de un pánico del kernel con su mensaje y ubicación.
+///
+/// Si el propio informe provoca otro pánico, el segundo no intenta dibujar
+/// nada: solo deja constancia en el log de texto plano.
+pub fn report_panic(info: &PanicInfo) {
+    if PANICKING.swap(true, Ordering::SeqCst) {
+        let _ = writeln!(FaultLog, "\n=== Pánico durante el informe de pánico: {} ===", info.message());
+        return;
+    }
+
+    let mut screen = FaultScreen::open("Pánico del kernel");
+    screen.line(colors::ERROR_COLOR, format_args!("{}", info.message()));
+    screen.gap();
+    match info.location() {
+        Some(location) => {
+            screen.field("Archivo", format_args!("{}", location.file()));
+            screen.field("Línea", format_args!("{}:{}", location.line(), location.column()));
+        }
+        None => screen.field("Ubicación", format_args!("desconocida")),
+    }
+    screen.gap();
+    screen.line(colors::TEXT_ACCENT, format_args!("El sistema se ha detenido."));
+}
@@ -0,0 +1,148 @@
+//! Ficheros abiertos y tablas de descriptores.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+use super::{path, Error, Inode, Kind, Metadata};
+
+/// Descriptores que no se guardan en la tabla: entrada, salida y error
+/// estándar, que son la consola.
+pub const FIRST_DESCRIPTOR: u64 = 3;
+/// Máximo de ficheros abiertos en una tabla.
+const MAX_FILES: usize = 64;
+/// Tamaño de los bloques con que se lee un fichero entero.
+const CHUNK_SIZE: usize = 4096;
+
+/// Origen de un desplazamiento de [`File::seek`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    /// Desde el principio del fichero.
+    Start(u64),
+    /// Desde la posición actual.
+    Current(i64),
+    /// Desde el final del fichero.
+    End(i64),
+}
+
+/// Un fichero abierto: el nodo y la posición de la siguiente lectura o
+/// escritura.
+pub struct File {
+    inode: Arc<dyn Inode>,
+    position: u64,
+}
+
+impl File {
+    /// Abre el fichero `path`, relativo al directorio `cwd`. Los directorios
+    /// no se pueden abrir.
+    pub fn open(cwd: &str, path: &str) -> Result<File, Error> {
+        let resolved = path::resolve(cwd, path)?;
+        if resolved.inode.metadata().kind == Kind::Directory {
+            return Err(Error::IsDirectory);
+        }
+        Ok(File { inode: resolved.inode, position: 0 })
+    }
+
+    /// Abre el fichero `path` para escribirlo desde cero: lo crea si no
+    /// existe y, si existe, lo deja vacío.
+    pub fn create(cwd: &str, path: &str) -> Result<File, Error> {
+        let inode = match path::resolve(cwd, path) {
+            Ok(resolved) if resolved.inode.metadata().kind == Kind::Directory => return Err(Error::IsDirectory),
+            Ok(resolved) => {
+                resolved.inode.truncate(0)?;
+                resolved.inode
+            }
+            Err(Error::NotFound) => {
+                let (parent, name) = path::resolve_parent(cwd, path)?;
+                parent.inode.create(name, Kind::File)?
+            }
+            Err(error) => return Err(error),
+        };
+        Ok(File { inode, position: 0 })
+    }
+
+    pub fn metadata(&self) -> Metadata {
+        self.inode.metadata()
+    }
+
+    /// Lee a partir de la posición actual y la avanza lo leído.
+    pub fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let count = self.inode.read_at(self.position, buffer)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+
+    /// Escribe en la posición actual y la avanza lo escrito.
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        let count = self.inode.write_at(self.position, data)?;
+        self.position += count as u64;
+        Ok(count)
+    }
+
+    /// Lee desde la posición actual hasta el final del fichero.
+    pub fn read_to_end(&mut self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        loop {
+            let start = bytes.len();
+            bytes.resize(start + CHUNK_SIZE, 0);
+            let count = self.read(&mut bytes[start..])?;
+            bytes.truncate(start + count);
+            if count == 0 {
+                return Ok(bytes);
+            }
+        }
+    }
+
+    /// Cambia la posición y devuelve la nueva. Se puede ir más allá del
+    /// final, pero no antes del principio.
+    pub fn seek(&mut self, from: SeekFrom) -> Result<u64, Error> {
+        let position = match from {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.metadata().size.checked_add_signed(offset),
+        };
+        self.position = position.ok_or(Error::InvalidArgument)?;
+        Ok(self.position)
+    }
+}
+
+/// Ficheros abiertos de un proceso, por número de descriptor.
+///
+/// Cada fichero se comparte con un `Arc` para poder leerlo y escribirlo
+/// después de soltar la tabla, que está tras un `IrqSpinlock`. Solo lo usa
+/// el hilo del proceso, así que su `Mutex` nunca tiene que esperar.
+#[derive(Default)]
+pub struct FileTable {
+    files: Vec<Option<Arc<Mutex<File>>>>,
+}
+
+impl FileTable {
+    /// Guarda `file` con el menor descriptor libre y lo devuelve.
+    pub fn insert(&mut self, file: File) -> Result<u64, Error> {
+        let index = match self.files.iter().position(Option::is_none) {
+            Some(index) => index,
+            None if self.files.len() < MAX_FILES => {
+                self.files.push(None);
+                self.files.len() - 1
+            }
+            None => return Err(Error::TooManyFiles),
+        };
+        self.files[index] = Some(Arc::new(Mutex::new(file)));
+        Ok(index as u64 + FIRST_DESCRIPTOR)
+    }
+
+    /// Fichero abierto con el descriptor `fd`.
+    pub fn get(&mut self, fd: u64) -> Result<Arc<Mutex<File>>, Error> {
+        self.slot(fd)?.clone().ok_or(Error::BadDescriptor)
+    }
+
+    /// Cierra el descriptor `fd`.
+    pub fn remove(&mut self, fd: u64) -> Result<(), Error> {
+        self.slot(fd)?.take().map(drop).ok_or(Error::BadDescriptor)
+    }
+
+    fn slot(&mut self, fd: u64) -> Result<&mut Option<Arc<Mutex<File>>>, Error> {
+        let index = fd.checked_sub(FIRST_DESCRIPTOR).ok_or(Error::BadDescriptor)?;
+        self.files.get_mut(index as usize).ok_or(Error::BadDescriptor)
+    }
+}
@@ -0,0 +1,218 @@
+//! Sistema de ficheros inicial (initramfs).
+//!
+//! Limine carga como módulo un archivo tar (`initramfs.tar`) junto al kernel.
+//! Al arrancar se recorre una sola vez y se construye con él un árbol de
+//! solo lectura: los directorios se guardan en el heap, pero el contenido de
+//! los ficheros se sirve directamente desde la memoria del módulo, que el
+//! asignador de marcos nunca reutiliza.
+//!
+//! [`Initramfs`] expone ese árbol al VFS; todas sus instancias comparten el
+//! mismo.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Once;
+
+use super::ustar::{self, Archive};
+use super::{DirEntry, Error, FileSystem, Inode, Kind, Metadata, Timestamp};
+use crate::log;
+use crate::memory;
+use crate::MODULE_REQUEST;
+
+/// Sufijo de la ruta del módulo que contiene el initramfs.
+const MODULE_SUFFIX: &str = "initramfs.tar";
+
+/// Un nodo del árbol.
+#[derive(Debug)]
+pub struct Node {
+    /// Número del nodo, en orden de recorrido desde la raíz, que es el 1.
+    pub inode: u64,
+    /// Permisos Unix.
+    pub mode: u32,
+    /// Fecha de modificación, en segundos desde 1970.
+    pub mtime: u64,
+    pub content: Content,
+}
+
+/// Contenido de un nodo.
+#[derive(Debug)]
+pub enum Content {
+    File(&'static [u8]),
+    /// Hijos por nombre, en orden alfabético.
+    Directory(BTreeMap<String, Node>),
+}
+
+impl Node {
+    fn directory() -> Node {
+        Node { inode: 0, mode: 0o755, mtime: 0, content: Content::Directory(BTreeMap::new()) }
+    }
+
+    /// Numera este nodo y sus descendientes a partir de `next`.
+    fn number(&mut self, next: &mut u64) {
+        self.inode = *next;
+        *next += 1;
+        if let Content::Directory(children) = &mut self.content {
+            children.values_mut().for_each(|child| child.number(next));
+        }
+    }
+
+    /// Hijos del nodo, si es un directorio.
+    pub fn children(&self) -> Option<&BTreeMap<String, Node>> {
+        match &self.content {
+            Content::Directory(children) => Some(children),
+            Content::File(_) => None,
+        }
+    }
+
+    /// Contenido del nodo, si es un fichero.
+    pub fn data(&self) -> Option<&'static [u8]> {
+        match self.content {
+            Content::File(data) => Some(data),
+            Content::Directory(_) => None,
+        }
+    }
+
+    /// Crea, si no existen, los directorios de `components` y devuelve los
+    /// hijos del último, o `None` si alguno de ellos es un fichero.
+    fn make_directories<'a>(&mut self, components: impl Iterator<Item = &'a str>) -> Option<&mut BTreeMap<String, Node>> {
+        let Content::Directory(children) = &mut self.content else { return None };
+        let mut children = children;
+        for part in components {
+            let node = children.entry(String::from(part)).or_insert_with(Node::directory);
+            let Content::Directory(next) = &mut node.content else { return None };
+            children = next;
+        }
+        Some(children)
+    }
+}
+
+/// Raíz del initramfs; vacía si no se cargó ninguno.
+static ROOT: Once<Node> = Once::new();
+
+/// Busca el módulo del initramfs y construye su árbol. Requiere el heap.
+pub fn init() {
+    let module = MODULE_REQUEST
+        .get_response()
+        .and_then(|response| response.modules().iter().find(|module| module.path().to_bytes().ends_with(MODULE_SUFFIX.as_bytes())));
+    let mut root = Node::directory();
+    match module {
+        Some(module) => {
+            // Limine entrega la dirección del módulo dentro del HHDM, y su
+            // memoria queda reservada para siempre.
+            let bytes = unsafe { core::slice::from_raw_parts(module.addr() as *const u8, module.size() as usize) };
+            let (files, size) = load(&mut root, bytes);
+            let physical = module.addr() as u64 - memory::hhdm_offset();
+            log::info!("initramfs en {:#x}: {} ficheros, {} KiB", physical, files, size / 1024);
+        }
+        None => log::warn!("no se cargó ningún módulo {}: el initramfs está vacío", MODULE_SUFFIX),
+    }
+    root.number(&mut 1);
+    ROOT.call_once(|| root);
+}
+
+/// Añade al árbol las entradas del archivo `bytes` y devuelve cuántos
+/// ficheros y cuántos bytes se cargaron. Las entradas inválidas se registran
+/// y se omiten.
+fn load(root: &mut Node, bytes: &'static [u8]) -> (usize, usize) {
+    let (mut files, mut size) = (0, 0);
+    for entry in Archive::new(bytes) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                log::error!("initramfs: {}", error);
+                break;
+            }
+        };
+        let components: Vec<&str> =
+            entry.prefix.split('/').chain(entry.name.split('/')).filter(|part| !part.is_empty() && *part != ".").collect();
+        // La entrada de la propia raíz (`./`) no añade nada.
+        let Some((&path, parents)) = components.split_last() else { continue };
+        let Some(children) = root.make_directories(parents.iter().copied()) else {
+            log::warn!("initramfs: {} cuelga de un fichero; se omite", path);
+            continue;
+        };
+        let node = match entry.kind {
+            ustar::Kind::File => {
+                files += 1;
+                size += entry.data.len();
+                Node { inode: 0, mode: entry.mode, mtime: entry.mtime, content: Content::File(entry.data) }
+            }
+            ustar::Kind::Directory => Node { mode: entry.mode, mtime: entry.mtime, ..Node::directory() },
+            ustar::Kind::Other(kind) => {
+                log::debug!("initramfs: {} es de un tipo no soportado ({}); se omite", path, kind as char);
+                continue;
+            }
+        };
+        match children.get_mut(path) {
+            // Un directorio que ya se creó implícitamente solo recibe sus atributos.
+            Some(existing) if node.children().is_some() && existing.children().is_some() => {
+                existing.mode = node.mode;
+                existing.mtime = node.mtime;
+            }
+            _ => {
+                children.insert(String::from(path), node);
+            }
+        }
+    }
+    (files, size)
+}
+
+/// Raíz del initramfs.
+pub fn root() -> &'static Node {
+    ROOT.get().expect("initramfs sin inicializar")
+}
+
+/// El initramfs como sistema de ficheros de solo lectura.
+pub struct Initramfs;
+
+impl FileSystem for Initramfs {
+    fn name(&self) -> &'static str {
+        "initramfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(NodeRef(root()))
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+}
+
+/// Un nodo del árbol visto desde el VFS.
+struct NodeRef(&'static Node);
+
+impl Inode for NodeRef {
+    fn metadata(&self) -> Metadata {
+        let node = self.0;
+        let (kind, size) = match &node.content {
+            Content::File(data) => (Kind::File, data.len()),
+            Content::Directory(children) => (Kind::Directory, children.len()),
+        };
+        Metadata { kind, inode: node.inode, size: size as u64, mode: node.mode, mtime: Timestamp::Unix(node.mtime) }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, Error> {
+        let child = self.0.children().ok_or(Error::NotDirectory)?.get(name).ok_or(Error::NotFound)?;
+        Ok(Arc::new(NodeRef(child)))
+    }
+
+    fn read_dir(&self) -> Result<Vec<DirEntry>, Error> {
+        let children = self.0.children().ok_or(Error::NotDirectory)?;
+        let entries = children.iter().map(|(name, child)| DirEntry {
+            name: name.clone(),
+            kind: if child.children().is_some() { Kind::Directory } else { Kind::File },
+        });
+        Ok(entries.collect())
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, Error> {
+        let data = self.0.data().ok_or(Error::IsDirectory)?;
+        let start = usize::try_from(offset).unwrap_or(usize::MAX).min(data.len());
+        let count = buffer.len().min(data.len() - start);
+        buffer[..count].copy_from_slice(&data[start..start + count]);
+        Ok(count)
+    }
+}
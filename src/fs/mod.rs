@@ -0,0 +1,297 @@
+//! Sistemas de ficheros.
+//!
+//! El sistema de ficheros virtual (VFS) une varios sistemas de ficheros en
+//! un único árbol. Cada sistema implementa [`FileSystem`], que da acceso a
+//! su directorio raíz, e [`Inode`] para cada fichero o directorio; la tabla
+//! de montajes ([`mount`]) dice en qué directorio del árbol aparece la raíz
+//! de cada uno, y [`path`] recorre las rutas cruzando de uno a otro.
+//!
+//! Los ficheros abiertos ([`file::File`]) guardan su posición de lectura, y
+//! cada proceso tiene su tabla de descriptores ([`file::FileTable`]).
+//!
+//! La raíz es el initramfs, de solo lectura; en `/tmp` se monta un
+//! [`tmpfs`] en memoria donde se puede escribir.
+
+pub mod file;
+pub mod initramfs;
+pub mod mount;
+pub mod path;
+pub mod tmpfs;
+mod ustar;
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::log;
+use crate::time::DateTime;
+use self::path::Resolved;
+
+/// Punto de montaje del tmpfs que se crea al arrancar.
+const TMP_MOUNT: &str = "/tmp";
+
+/// Tipo de un nodo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Directory,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::File => "fichero",
+            Kind::Directory => "directorio",
+        })
+    }
+}
+
+/// Atributos de un nodo.
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata {
+    pub kind: Kind,
+    /// Número del nodo, único dentro de su sistema de ficheros.
+    pub inode: u64,
+    /// Tamaño en bytes; para los directorios, su número de entradas.
+    pub size: u64,
+    /// Permisos Unix.
+    pub mode: u32,
+    /// Fecha de modificación.
+    pub mtime: Timestamp,
+}
+
+/// Un instante en las fechas de los nodos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamp {
+    /// Segundos desde 1970, en UTC.
+    Unix(u64),
+    /// Segundos desde el arranque, para los sistemas de ficheros que no
+    /// tienen una fecha real.
+    Uptime(u64),
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timestamp::Unix(seconds) => write!(f, "{} UTC", DateTime(*seconds)),
+            Timestamp::Uptime(seconds) => write!(f, "{} s después del arranque", seconds),
+        }
+    }
+}
+
+/// Una entrada de un directorio.
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,
+    pub kind: Kind,
+}
+
+/// Errores del sistema de ficheros.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// No existe el fichero o directorio.
+    NotFound,
+    /// Un componente de la ruta no es un directorio.
+    NotDirectory,
+    /// Se esperaba un fichero y es un directorio.
+    IsDirectory,
+    /// Argumento inválido, como una posición negativa.
+    InvalidArgument,
+    /// El descriptor no corresponde a ningún fichero abierto.
+    BadDescriptor,
+    /// La tabla de descriptores está llena.
+    TooManyFiles,
+    /// Ya hay un sistema de ficheros montado en el directorio.
+    Busy,
+    /// No existe ese tipo de sistema de ficheros.
+    UnknownFileSystem,
+    /// El sistema de ficheros es de solo lectura.
+    ReadOnly,
+    /// Ya existe una entrada con ese nombre.
+    AlreadyExists,
+    /// El directorio no está vacío.
+    NotEmpty,
+    /// Origen y destino están en sistemas de ficheros distintos.
+    CrossDevice,
+    /// No hay memoria para el contenido.
+    NoSpace,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::NotFound => "no existe el fichero o directorio",
+            Error::NotDirectory => "no es un directorio",
+            Error::IsDirectory => "es un directorio",
+            Error::InvalidArgument => "argumento inválido",
+            Error::BadDescriptor => "descriptor inválido",
+            Error::TooManyFiles => "demasiados ficheros abiertos",
+            Error::Busy => "ya hay un sistema de ficheros montado",
+            Error::UnknownFileSystem => "tipo de sistema de ficheros desconocido",
+            Error::ReadOnly => "sistema de ficheros de solo lectura",
+            Error::AlreadyExists => "ya existe",
+            Error::NotEmpty => "el directorio no está vacío",
+            Error::CrossDevice => "no se puede mover entre sistemas de ficheros",
+            Error::NoSpace => "no queda memoria",
+        })
+    }
+}
+
+/// Un fichero o directorio de algún sistema de ficheros.
+///
+/// Las operaciones de lectura que no tienen sentido para el tipo del nodo
+/// tienen una implementación por defecto que devuelve el error
+/// correspondiente; las de escritura, una que devuelve
+/// [`Error::ReadOnly`]. Los nombres que reciben ya vienen validados: no
+/// están vacíos, no son `.` ni `..` y no contienen `/`.
+pub trait Inode: Send + Sync {
+    fn metadata(&self) -> Metadata;
+
+    /// Busca la entrada `name` de este directorio.
+    fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, Error> {
+        Err(Error::NotDirectory)
+    }
+
+    /// Entradas de este directorio, en orden alfabético.
+    fn read_dir(&self) -> Result<Vec<DirEntry>, Error> {
+        Err(Error::NotDirectory)
+    }
+
+    /// Copia en `buffer` el contenido del fichero a partir de `offset` y
+    /// devuelve cuántos bytes copió; 0 al final del fichero.
+    fn read_at(&self, _offset: u64, _buffer: &mut [u8]) -> Result<usize, Error> {
+        Err(Error::IsDirectory)
+    }
+
+    /// Escribe `data` en el fichero a partir de `offset`, que puede estar
+    /// más allá del final: el hueco se rellena con ceros.
+    fn write_at(&self, _offset: u64, _data: &[u8]) -> Result<usize, Error> {
+        Err(Error::ReadOnly)
+    }
+
+    /// Cambia el tamaño del fichero; lo que crece se rellena con ceros.
+    fn truncate(&self, _size: u64) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    /// Crea en este directorio una entrada vacía `name` del tipo `kind`.
+    fn create(&self, _name: &str, _kind: Kind) -> Result<Arc<dyn Inode>, Error> {
+        Err(Error::ReadOnly)
+    }
+
+    /// Borra el fichero `name` de este directorio.
+    fn unlink(&self, _name: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    /// Borra el directorio vacío `name` de este directorio.
+    fn rmdir(&self, _name: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+
+    /// Mueve la entrada `from` de este directorio a `to` en `target`, que es
+    /// un directorio del mismo sistema de ficheros. Si `to` existe y es del
+    /// mismo tipo se sustituye, siempre que no sea un directorio con
+    /// entradas.
+    fn rename(&self, _from: &str, _target: &dyn Inode, _to: &str) -> Result<(), Error> {
+        Err(Error::ReadOnly)
+    }
+}
+
+/// Un sistema de ficheros que se puede montar.
+pub trait FileSystem: Send + Sync {
+    /// Nombre del tipo de sistema de ficheros.
+    fn name(&self) -> &'static str;
+
+    /// Directorio raíz.
+    fn root(&self) -> Arc<dyn Inode>;
+
+    /// Si no admite modificaciones.
+    fn read_only(&self) -> bool;
+}
+
+/// Crea una instancia nueva de un tipo de sistema de ficheros.
+type Constructor = fn() -> Arc<dyn FileSystem>;
+
+/// Tipos de sistema de ficheros que se pueden montar por nombre.
+const FILESYSTEMS: [(&str, Constructor); 2] = [
+    ("initramfs", || Arc::new(initramfs::Initramfs)),
+    ("tmpfs", || Arc::new(tmpfs::Tmpfs::new())),
+];
+
+/// Crea un sistema de ficheros del tipo `name`.
+pub fn create(name: &str) -> Result<Arc<dyn FileSystem>, Error> {
+    let (_, constructor) = FILESYSTEMS.iter().find(|(kind, _)| *kind == name).ok_or(Error::UnknownFileSystem)?;
+    Ok(constructor())
+}
+
+/// Carga el initramfs, lo monta como raíz del árbol y monta un tmpfs en
+/// `/tmp`. Requiere el heap.
+pub fn init() {
+    initramfs::init();
+    mount::mount("/", Arc::new(initramfs::Initramfs)).expect("no se pudo montar la raíz");
+    if let Err(error) = mount::mount(TMP_MOUNT, Arc::new(tmpfs::Tmpfs::new())) {
+        log::warn!("no se pudo montar el tmpfs en {}: {}", TMP_MOUNT, error);
+    }
+}
+
+/// Crea el directorio `path`.
+pub fn mkdir(cwd: &str, path: &str) -> Result<(), Error> {
+    let (parent, name) = path::resolve_parent(cwd, path)?;
+    parent.inode.create(name, Kind::Directory).map(drop)
+}
+
+/// Crea el fichero vacío `path`, o lo deja como está si ya existe.
+pub fn touch(cwd: &str, path: &str) -> Result<(), Error> {
+    match path::resolve(cwd, path) {
+        Ok(_) => Ok(()),
+        Err(Error::NotFound) => {
+            let (parent, name) = path::resolve_parent(cwd, path)?;
+            parent.inode.create(name, Kind::File).map(drop)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Borra el fichero `path`.
+pub fn unlink(cwd: &str, path: &str) -> Result<(), Error> {
+    let (parent, name) = path::resolve_parent(cwd, path)?;
+    parent.inode.unlink(name)
+}
+
+/// Borra el directorio vacío `path`, que no puede ser un punto de montaje.
+pub fn rmdir(cwd: &str, path: &str) -> Result<(), Error> {
+    let target = path::resolve(cwd, path)?;
+    ensure_not_mounted(&target)?;
+    let (parent, name) = path::resolve_parent(cwd, path)?;
+    parent.inode.rmdir(name)
+}
+
+/// Mueve `from` a `to`, dentro del mismo sistema de ficheros.
+pub fn rename(cwd: &str, from: &str, to: &str) -> Result<(), Error> {
+    let source = path::resolve(cwd, from)?;
+    ensure_not_mounted(&source)?;
+    let (source_parent, source_name) = path::resolve_parent(cwd, from)?;
+    let (target_parent, target_name) = path::resolve_parent(cwd, to)?;
+    let target = path::join(&target_parent.path, target_name);
+    if mount::find(&target).is_some() {
+        return Err(Error::Busy);
+    }
+    if source_parent.mount != target_parent.mount {
+        return Err(Error::CrossDevice);
+    }
+    // Un directorio no puede moverse dentro de sí mismo.
+    if target.starts_with(&source.path) && target.as_bytes().get(source.path.len()) == Some(&b'/') {
+        return Err(Error::InvalidArgument);
+    }
+    source_parent.inode.rename(source_name, &*target_parent.inode, target_name)
+}
+
+/// Falla con [`Error::Busy`] si `resolved` es un punto de montaje.
+fn ensure_not_mounted(resolved: &Resolved) -> Result<(), Error> {
+    if mount::find(&resolved.path).is_some() {
+        return Err(Error::Busy);
+    }
+    Ok(())
+}
@@ -0,0 +1,53 @@
+//! Tabla de montajes.
+//!
+//! Cada entrada asocia la ruta canónica de un directorio con el sistema de
+//! ficheros cuya raíz lo sustituye. La raíz del árbol es el montaje de `/`.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::RwLock;
+
+use super::{path, Error, FileSystem, Inode, Kind};
+
+/// Un sistema de ficheros montado.
+#[derive(Clone)]
+pub struct Mount {
+    /// Ruta canónica del punto de montaje.
+    pub path: String,
+    pub fs: Arc<dyn FileSystem>,
+}
+
+/// Montajes, en el orden en que se hicieron.
+static MOUNTS: RwLock<Vec<Mount>> = RwLock::new(Vec::new());
+
+/// Monta `fs` en el directorio `target`, que tiene que existir salvo para
+/// la raíz, y devuelve la ruta canónica del punto de montaje.
+pub fn mount(target: &str, fs: Arc<dyn FileSystem>) -> Result<String, Error> {
+    let path = if find("/").is_none() && target == "/" {
+        String::from("/")
+    } else {
+        let resolved = path::resolve("/", target)?;
+        if resolved.inode.metadata().kind != Kind::Directory {
+            return Err(Error::NotDirectory);
+        }
+        resolved.path
+    };
+    let mut mounts = MOUNTS.write();
+    // Entre la resolución y el lock otro montaje pudo ocupar el directorio.
+    if mounts.iter().any(|mount| mount.path == path) {
+        return Err(Error::Busy);
+    }
+    mounts.push(Mount { path: path.clone(), fs });
+    Ok(path)
+}
+
+/// Raíz del sistema de ficheros montado en la ruta canónica `path`, si lo hay.
+pub fn find(path: &str) -> Option<Arc<dyn Inode>> {
+    MOUNTS.read().iter().find(|mount| mount.path == path).map(|mount| mount.fs.root())
+}
+
+/// Copia de la tabla de montajes.
+pub fn mounts() -> Vec<Mount> {
+    MOUNTS.read().clone()
+}
@@ -0,0 +1,95 @@
+//! Resolución de rutas.
+//!
+//! Las rutas que empiezan por `/` son absolutas; las demás son relativas a
+//! un directorio actual, que se da como ruta canónica. La resolución avanza
+//! componente a componente desde la raíz: `.` no hace nada, `..` vuelve al
+//! directorio anterior (el de la raíz es ella misma) y cada nombre se busca
+//! en el directorio actual. Al llegar a un punto de montaje se continúa por
+//! la raíz del sistema montado, y al salir de él con `..` se vuelve al
+//! directorio que lo contiene.
+//!
+//! Como no hay enlaces simbólicos, la ruta canónica que resulta es la
+//! secuencia de nombres que quedan, sin `.`, `..` ni barras repetidas.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::{mount, Error, Inode, Kind};
+
+/// Una ruta resuelta.
+pub struct Resolved {
+    /// Ruta canónica y absoluta.
+    pub path: String,
+    pub inode: Arc<dyn Inode>,
+    /// Punto de montaje del sistema de ficheros al que pertenece el nodo.
+    pub mount: String,
+}
+
+/// Resuelve `path` a partir del directorio `cwd`.
+pub fn resolve(cwd: &str, path: &str) -> Result<Resolved, Error> {
+    let root = mount::find("/").ok_or(Error::NotFound)?;
+    let start = if path.starts_with('/') { "" } else { cwd };
+    // Directorios recorridos, de la raíz al actual, cada uno con la longitud
+    // de la ruta de su punto de montaje, y la ruta canónica hasta el actual
+    // sin la barra final (vacía en la raíz).
+    let mut inodes: Vec<(Arc<dyn Inode>, usize)> = Vec::from([(root, 0)]);
+    let mut canonical = String::new();
+    for part in start.split('/').chain(path.split('/')) {
+        let (current, mount_len) = inodes.last().expect("la raíz no se saca nunca");
+        // `.` y `..` solo tienen sentido dentro de un directorio.
+        if matches!(part, "." | "..") && current.metadata().kind != Kind::Directory {
+            return Err(Error::NotDirectory);
+        }
+        match part {
+            "" | "." => {}
+            ".." => {
+                if inodes.len() > 1 {
+                    inodes.pop();
+                    canonical.truncate(canonical.rfind('/').unwrap_or(0));
+                }
+            }
+            name => {
+                canonical.push('/');
+                canonical.push_str(name);
+                let entry = match mount::find(&canonical) {
+                    Some(root) => (root, canonical.len()),
+                    None => (current.lookup(name)?, *mount_len),
+                };
+                inodes.push(entry);
+            }
+        }
+    }
+    let (inode, mount_len) = inodes.pop().expect("la raíz no se saca nunca");
+    let mount = if mount_len == 0 { String::from("/") } else { String::from(&canonical[..mount_len]) };
+    if canonical.is_empty() {
+        canonical.push('/');
+    }
+    Ok(Resolved { path: canonical, inode, mount })
+}
+
+/// Resuelve el directorio que contiene `path` y devuelve también el nombre
+/// del último componente, que no tiene por qué existir.
+pub fn resolve_parent<'a>(cwd: &str, path: &'a str) -> Result<(Resolved, &'a str), Error> {
+    let trimmed = path.trim_end_matches('/');
+    let (parent, name) = match trimmed.rfind('/') {
+        Some(index) => (&trimmed[..=index], &trimmed[index + 1..]),
+        None => (".", trimmed),
+    };
+    if matches!(name, "" | "." | "..") {
+        return Err(Error::InvalidArgument);
+    }
+    let parent = resolve(cwd, parent)?;
+    if parent.inode.metadata().kind != Kind::Directory {
+        return Err(Error::NotDirectory);
+    }
+    Ok((parent, name))
+}
+
+/// Ruta canónica de la entrada `name` del directorio de ruta canónica `parent`.
+pub fn join(parent: &str, name: &str) -> String {
+    let mut path = String::from(parent.trim_end_matches('/'));
+    path.push('/');
+    path.push_str(name);
+    path
+}
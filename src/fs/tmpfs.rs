@@ -0,0 +1,266 @@
+//! Sistema de ficheros en memoria (tmpfs).
+//!
+//! Todos los nodos de una instancia viven en una tabla indexada por número
+//! de nodo, tras un único lock: así las operaciones que tocan dos
+//! directorios, como `rename`, son atómicas sin tener que ordenar locks. Un
+//! directorio guarda, para cada nombre, el número del nodo hijo; el
+//! contenido de los ficheros está en el heap del kernel.
+//!
+//! No hay reloj de tiempo real: las fechas de modificación son los
+//! segundos desde el arranque. Un fichero borrado desaparece de la tabla
+//! aunque siga abierto, y leerlo a partir de entonces falla con
+//! [`Error::NotFound`].
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::Mutex;
+
+use super::{DirEntry, Error, FileSystem, Inode, Kind, Metadata, Timestamp};
+use crate::time;
+
+/// Número del directorio raíz.
+const ROOT_INODE: u64 = 1;
+
+/// Un tmpfs; cada instancia es un árbol independiente.
+pub struct Tmpfs {
+    tree: Arc<Mutex<Tree>>,
+}
+
+impl Tmpfs {
+    /// Crea un tmpfs con la raíz vacía.
+    pub fn new() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(ROOT_INODE, Node::new(Kind::Directory));
+        Self { tree: Arc::new(Mutex::new(Tree { nodes, next_inode: ROOT_INODE + 1 })) }
+    }
+}
+
+impl FileSystem for Tmpfs {
+    fn name(&self) -> &'static str {
+        "tmpfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(TmpInode { tree: self.tree.clone(), inode: ROOT_INODE })
+    }
+
+    fn read_only(&self) -> bool {
+        false
+    }
+}
+
+/// Los nodos de un tmpfs.
+struct Tree {
+    nodes: BTreeMap<u64, Node>,
+    next_inode: u64,
+}
+
+struct Node {
+    /// Permisos Unix.
+    mode: u32,
+    /// Fecha de modificación, en segundos desde el arranque.
+    mtime: u64,
+    content: Content,
+}
+
+enum Content {
+    File(Vec<u8>),
+    /// Número del nodo de cada entrada, por nombre.
+    Directory(BTreeMap<String, u64>),
+}
+
+impl Node {
+    fn new(kind: Kind) -> Node {
+        let (mode, content) = match kind {
+            Kind::File => (0o644, Content::File(Vec::new())),
+            Kind::Directory => (0o755, Content::Directory(BTreeMap::new())),
+        };
+        Node { mode, mtime: now(), content }
+    }
+
+    fn kind(&self) -> Kind {
+        match self.content {
+            Content::File(_) => Kind::File,
+            Content::Directory(_) => Kind::Directory,
+        }
+    }
+}
+
+impl Tree {
+    fn node(&mut self, inode: u64) -> Result<&mut Node, Error> {
+        self.nodes.get_mut(&inode).ok_or(Error::NotFound)
+    }
+
+    /// Contenido del fichero `inode`.
+    fn file(&mut self, inode: u64) -> Result<&mut Vec<u8>, Error> {
+        match &mut self.node(inode)?.content {
+            Content::File(data) => Ok(data),
+            Content::Directory(_) => Err(Error::IsDirectory),
+        }
+    }
+
+    /// Entradas del directorio `inode`.
+    fn directory(&mut self, inode: u64) -> Result<&mut BTreeMap<String, u64>, Error> {
+        match &mut self.node(inode)?.content {
+            Content::Directory(children) => Ok(children),
+            Content::File(_) => Err(Error::NotDirectory),
+        }
+    }
+
+    /// Marca como modificado el nodo `inode`.
+    fn touch(&mut self, inode: u64) {
+        if let Ok(node) = self.node(inode) {
+            node.mtime = now();
+        }
+    }
+
+    /// Comprueba que el nodo `inode` se pueda borrar o sustituir por otro
+    /// del tipo `kind`.
+    fn check_replaceable(&mut self, inode: u64, kind: Kind) -> Result<(), Error> {
+        match (&self.node(inode)?.content, kind) {
+            (Content::File(_), Kind::File) => Ok(()),
+            (Content::Directory(children), Kind::Directory) if children.is_empty() => Ok(()),
+            (Content::Directory(_), Kind::Directory) => Err(Error::NotEmpty),
+            (Content::Directory(_), Kind::File) => Err(Error::IsDirectory),
+            (Content::File(_), Kind::Directory) => Err(Error::NotDirectory),
+        }
+    }
+
+    /// Quita la entrada `name` del directorio `parent`, que tiene que ser
+    /// del tipo `kind`, y libera su nodo.
+    fn remove(&mut self, parent: u64, name: &str, kind: Kind) -> Result<(), Error> {
+        let inode = *self.directory(parent)?.get(name).ok_or(Error::NotFound)?;
+        self.check_replaceable(inode, kind)?;
+        self.directory(parent)?.remove(name);
+        self.nodes.remove(&inode);
+        self.touch(parent);
+        Ok(())
+    }
+}
+
+/// Un nodo de un tmpfs visto desde el VFS.
+struct TmpInode {
+    tree: Arc<Mutex<Tree>>,
+    inode: u64,
+}
+
+impl Inode for TmpInode {
+    fn metadata(&self) -> Metadata {
+        let mut tree = self.tree.lock();
+        // Un nodo borrado se ve como un fichero vacío.
+        let (kind, size, mode, mtime) = match tree.node(self.inode) {
+            Ok(node) => {
+                let size = match &node.content {
+                    Content::File(data) => data.len(),
+                    Content::Directory(children) => children.len(),
+                };
+                (node.kind(), size, node.mode, node.mtime)
+            }
+            Err(_) => (Kind::File, 0, 0, 0),
+        };
+        Metadata { kind, inode: self.inode, size: size as u64, mode, mtime: Timestamp::Uptime(mtime) }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, Error> {
+        let inode = *self.tree.lock().directory(self.inode)?.get(name).ok_or(Error::NotFound)?;
+        Ok(Arc::new(TmpInode { tree: self.tree.clone(), inode }))
+    }
+
+    fn read_dir(&self) -> Result<Vec<DirEntry>, Error> {
+        let mut tree = self.tree.lock();
+        let children = tree.directory(self.inode)?.clone();
+        let entries = children.into_iter().map(|(name, inode)| {
+            let kind = tree.nodes.get(&inode).map_or(Kind::File, Node::kind);
+            DirEntry { name, kind }
+        });
+        Ok(entries.collect())
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, Error> {
+        let mut tree = self.tree.lock();
+        let data = tree.file(self.inode)?;
+        let start = usize::try_from(offset).unwrap_or(usize::MAX).min(data.len());
+        let count = buffer.len().min(data.len() - start);
+        buffer[..count].copy_from_slice(&data[start..start + count]);
+        Ok(count)
+    }
+
+    fn write_at(&self, offset: u64, bytes: &[u8]) -> Result<usize, Error> {
+        let mut tree = self.tree.lock();
+        let data = tree.file(self.inode)?;
+        let start = usize::try_from(offset).map_err(|_| Error::NoSpace)?;
+        let end = start.checked_add(bytes.len()).ok_or(Error::NoSpace)?;
+        if end > data.len() {
+            resize(data, end)?;
+        }
+        data[start..end].copy_from_slice(bytes);
+        tree.touch(self.inode);
+        Ok(bytes.len())
+    }
+
+    fn truncate(&self, size: u64) -> Result<(), Error> {
+        let mut tree = self.tree.lock();
+        let data = tree.file(self.inode)?;
+        resize(data, usize::try_from(size).map_err(|_| Error::NoSpace)?)?;
+        tree.touch(self.inode);
+        Ok(())
+    }
+
+    fn create(&self, name: &str, kind: Kind) -> Result<Arc<dyn Inode>, Error> {
+        let mut tree = self.tree.lock();
+        if tree.directory(self.inode)?.contains_key(name) {
+            return Err(Error::AlreadyExists);
+        }
+        let inode = tree.next_inode;
+        tree.next_inode += 1;
+        tree.nodes.insert(inode, Node::new(kind));
+        tree.directory(self.inode)?.insert(String::from(name), inode);
+        tree.touch(self.inode);
+        Ok(Arc::new(TmpInode { tree: self.tree.clone(), inode }))
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), Error> {
+        self.tree.lock().remove(self.inode, name, Kind::File)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<(), Error> {
+        self.tree.lock().remove(self.inode, name, Kind::Directory)
+    }
+
+    fn rename(&self, from: &str, target: &dyn Inode, to: &str) -> Result<(), Error> {
+        // El número del destino se lee antes de tomar el lock, que
+        // `metadata` también necesita.
+        let target = target.metadata().inode;
+        let mut tree = self.tree.lock();
+        let inode = *tree.directory(self.inode)?.get(from).ok_or(Error::NotFound)?;
+        let kind = tree.node(inode)?.kind();
+        if let Some(&existing) = tree.directory(target)?.get(to) {
+            if existing == inode {
+                return Ok(());
+            }
+            tree.check_replaceable(existing, kind)?;
+            tree.nodes.remove(&existing);
+        }
+        tree.directory(self.inode)?.remove(from);
+        tree.directory(target)?.insert(String::from(to), inode);
+        tree.touch(self.inode);
+        tree.touch(target);
+        Ok(())
+    }
+}
+
+/// Cambia el tamaño de `data` a `len`, sin abortar si no hay memoria.
+fn resize(data: &mut Vec<u8>, len: usize) -> Result<(), Error> {
+    if len > data.len() {
+        data.try_reserve(len - data.len()).map_err(|_| Error::NoSpace)?;
+    }
+    data.resize(len, 0);
+    Ok(())
+}
+
+/// Fecha de modificación de lo que cambia ahora.
+fn now() -> u64 {
+    time::uptime().as_secs()
+}
@@ -0,0 +1,140 @@
+//! Lectura de archivos tar en formato USTAR.
+//!
+//! Un archivo tar es una sucesión de bloques de 512 bytes: cada entrada
+//! tiene un bloque de cabecera seguido de su contenido, rellenado hasta el
+//! siguiente múltiplo de 512. El archivo termina con dos bloques a cero.
+//! Los campos numéricos de la cabecera son octales en ASCII.
+
+use core::fmt;
+use core::str;
+
+/// Tamaño de un bloque del archivo.
+const BLOCK_SIZE: usize = 512;
+/// Firma de una cabecera USTAR (POSIX, terminada en nulo, o GNU, en espacio).
+const MAGIC: &[u8] = b"ustar";
+
+/// Tipo de una entrada del archivo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Directory,
+    /// Enlaces, dispositivos y demás: se describen pero no se cargan.
+    Other(u8),
+}
+
+/// Una entrada del archivo.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry<'a> {
+    /// Ruta dentro del archivo, repartida entre el campo `prefix` y `name`.
+    pub prefix: &'a str,
+    pub name: &'a str,
+    pub kind: Kind,
+    /// Permisos Unix.
+    pub mode: u32,
+    /// Fecha de modificación, en segundos desde 1970.
+    pub mtime: u64,
+    /// Contenido del fichero; vacío para los demás tipos.
+    pub data: &'a [u8],
+}
+
+/// Errores al leer un archivo tar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Una cabecera no tiene la firma USTAR.
+    BadMagic { offset: usize },
+    /// La suma de comprobación de una cabecera no coincide.
+    BadChecksum { offset: usize },
+    /// Un campo numérico o de texto no es válido.
+    BadField { offset: usize },
+    /// El contenido de una entrada se sale del archivo.
+    Truncated { offset: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BadMagic { offset } => write!(f, "cabecera sin firma USTAR en el byte {}", offset),
+            Error::BadChecksum { offset } => write!(f, "suma de comprobación incorrecta en el byte {}", offset),
+            Error::BadField { offset } => write!(f, "campo inválido en la cabecera del byte {}", offset),
+            Error::Truncated { offset } => write!(f, "la entrada del byte {} está truncada", offset),
+        }
+    }
+}
+
+/// Recorre las entradas de un archivo tar.
+pub struct Archive<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Archive<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    /// Lee la cabecera que empieza en `self.offset`.
+    fn read_entry(&mut self, header: &'a [u8]) -> Result<Entry<'a>, Error> {
+        let offset = self.offset;
+        if &header[257..262] != MAGIC {
+            return Err(Error::BadMagic { offset });
+        }
+        // La suma se calcula con el propio campo de la suma lleno de espacios.
+        let sum: u64 = header.iter().enumerate().map(|(i, &byte)| if (148..156).contains(&i) { b' ' } else { byte } as u64).sum();
+        if octal(&header[148..156]) != Some(sum) {
+            return Err(Error::BadChecksum { offset });
+        }
+        let field = |range: core::ops::Range<usize>| octal(&header[range]).ok_or(Error::BadField { offset });
+        let size = field(124..136)? as usize;
+        let mode = field(100..108)? as u32;
+        let mtime = field(136..148)?;
+        let text = |range: core::ops::Range<usize>| string(&header[range]).ok_or(Error::BadField { offset });
+        let name = text(0..100)?;
+        let prefix = text(345..500)?;
+        let kind = match header[156] {
+            b'0' | 0 => Kind::File,
+            b'5' => Kind::Directory,
+            other => Kind::Other(other),
+        };
+
+        let start = offset + BLOCK_SIZE;
+        let data = match kind {
+            Kind::File => self.bytes.get(start..start + size).ok_or(Error::Truncated { offset })?,
+            _ => &[],
+        };
+        self.offset = start + size.next_multiple_of(BLOCK_SIZE);
+        Ok(Entry { prefix, name, kind, mode, mtime, data })
+    }
+}
+
+impl<'a> Iterator for Archive<'a> {
+    type Item = Result<Entry<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Un bloque a cero, o el final de los datos, termina el archivo.
+        let header = self.bytes.get(self.offset..self.offset + BLOCK_SIZE)?;
+        if header.iter().all(|&byte| byte == 0) {
+            return None;
+        }
+        let entry = self.read_entry(header);
+        if entry.is_err() {
+            // Tras un error ya no se sabe dónde empieza la siguiente cabecera.
+            self.offset = self.bytes.len();
+        }
+        Some(entry)
+    }
+}
+
+/// Interpreta un campo octal, terminado en nulo o en espacios.
+fn octal(field: &[u8]) -> Option<u64> {
+    let digits = string(field)?.trim_matches(' ');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+/// Texto de un campo terminado en nulo (o que ocupa el campo entero).
+fn string(field: &[u8]) -> Option<&str> {
+    let len = field.iter().position(|&byte| byte == 0).unwrap_or(field.len());
+    str::from_utf8(&field[..len]).ok()
+}
@@ -0,0 +1,217 @@
+//! Caché de glifos ya rasterizados.
+//!
+//! Dibujar un carácter con `embedded-graphics` recorre la fuente píxel a
+//! píxel cada vez. La consola pinta una y otra vez los mismos caracteres con
+//! los mismos colores, así que cada combinación de fuente, carácter, color
+//! de texto y color de fondo se rasteriza una sola vez: se guarda su máscara
+//! y, si el formato de píxel lo permite, la celda ya codificada, lista para
+//! copiarse fila a fila en el framebuffer.
+
+use core::convert::Infallible;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embedded_graphics::{
+    mono_font::{MonoFont, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::{Baseline, Text},
+};
+
+use crate::colors;
+use crate::pixel_format::PixelFormat;
+
+/// Ancho máximo de una celda, en píxeles.
+pub const MAX_GLYPH_WIDTH: usize = 32;
+/// Alto máximo de una celda, en píxeles.
+pub const MAX_GLYPH_HEIGHT: usize = 32;
+/// Número de entradas de la caché.
+const SLOTS: usize = 256;
+
+/// Identifica un glifo rasterizado. Una fuente nula marca una entrada vacía.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Key {
+    font: usize,
+    c: char,
+    fg: colors::Color,
+    bg: colors::Color,
+}
+
+impl Key {
+    fn new(font: &MonoFont, c: char, fg: colors::Color, bg: colors::Color) -> Self {
+        Self { font: font as *const MonoFont as usize, c, fg, bg }
+    }
+
+    /// Entrada de la caché que le corresponde.
+    fn slot(&self) -> usize {
+        let mut hash = self.font as u64;
+        for value in [self.c as u64, self.fg as u64, self.bg as u64] {
+            hash = (hash.rotate_left(5) ^ value).wrapping_mul(0x517C_C1B7_2722_0A95);
+        }
+        (hash >> 32) as usize % SLOTS
+    }
+}
+
+/// Una celda de texto rasterizada: el carácter sobre su fondo.
+pub struct Glyph {
+    key: Key,
+    width: usize,
+    height: usize,
+    /// Bit `x` de la fila `y` a 1 si el píxel pertenece al carácter.
+    mask: [u32; MAX_GLYPH_HEIGHT],
+    /// Celda codificada en el formato del framebuffer, `width` píxeles por fila.
+    pixels: [u8; MAX_GLYPH_WIDTH * MAX_GLYPH_HEIGHT * 4],
+    /// Indica si `pixels` es válido. No lo es si el fondo es transparente o si
+    /// el formato usa tramado, porque entonces el valor depende de la posición.
+    encoded: bool,
+    /// Formato con que se codificó `pixels`: un glifo solo sirve para ese
+    /// formato, no para otro con la misma profundidad y los canales en otro
+    /// orden. `None` en una entrada vacía, para que empiece a cero.
+    format: Option<PixelFormat>,
+}
+
+impl Glyph {
+    const EMPTY: Glyph = Glyph {
+        key: Key { font: 0, c: '\0', fg: 0, bg: 0 },
+        width: 0,
+        height: 0,
+        mask: [0; MAX_GLYPH_HEIGHT],
+        pixels: [0; MAX_GLYPH_WIDTH * MAX_GLYPH_HEIGHT * 4],
+        encoded: false,
+        format: None,
+    };
+
+    /// Rasteriza el carácter `c` con colores `fg` sobre `bg`.
+    ///
+    /// Con `bg` igual a `colors::TRANSPARENT` solo se dibujan los píxeles del carácter.
+    pub fn new(font: &MonoFont, c: char, fg: colors::Color, bg: colors::Color, format: PixelFormat) -> Self {
+        let mut glyph = Self::EMPTY;
+        glyph.render(font, c, fg, bg, format);
+        glyph
+    }
+
+    /// Ancho de la celda, en píxeles.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Alto de la celda, en píxeles.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Color del píxel `(x, y)` de la celda, o `None` si es transparente.
+    pub fn color(&self, x: usize, y: usize) -> Option<colors::Color> {
+        if self.mask[y] & (1 << x) != 0 {
+            Some(self.key.fg)
+        } else if self.key.bg != colors::TRANSPARENT {
+            Some(self.key.bg)
+        } else {
+            None
+        }
+    }
+
+    /// Fila `y` ya codificada, si la celda se pudo codificar de antemano.
+    pub fn encoded_row(&self, y: usize) -> Option<&[u8]> {
+        let format = self.format.filter(|_| self.encoded)?;
+        let stride = self.width * format.bytes_per_pixel;
+        Some(&self.pixels[y * stride..(y + 1) * stride])
+    }
+
+    fn render(&mut self, font: &MonoFont, c: char, fg: colors::Color, bg: colors::Color, format: PixelFormat) {
+        self.key = Key::new(font, c, fg, bg);
+        self.width = (font.character_size.width + font.character_spacing) as usize;
+        self.width = self.width.min(MAX_GLYPH_WIDTH);
+        self.height = (font.character_size.height as usize).min(MAX_GLYPH_HEIGHT);
+        self.mask = [0; MAX_GLYPH_HEIGHT];
+
+        let mut buf = [0u8; 4];
+        let style = MonoTextStyle::new(font, BinaryColor::On);
+        let _ = Text::with_baseline(c.encode_utf8(&mut buf), Point::zero(), style, Baseline::Top).draw(self);
+
+        self.format = Some(format);
+        self.encoded = bg != colors::TRANSPARENT && !format.dithers();
+        if self.encoded {
+            let bpp = format.bytes_per_pixel;
+            let values = [format.encode(bg, 0, 0).to_le_bytes(), format.encode(fg, 0, 0).to_le_bytes()];
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    let value = &values[(self.mask[y] >> x & 1) as usize];
+                    let offset = (y * self.width + x) * bpp;
+                    self.pixels[offset..offset + bpp].copy_from_slice(&value[..bpp]);
+                }
+            }
+        }
+    }
+}
+
+/// El glifo se rasteriza dibujando el carácter sobre su propia máscara.
+impl DrawTarget for Glyph {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let (x, y) = (point.x as usize, point.y as usize);
+            if color.is_on() && point.x >= 0 && point.y >= 0 && x < self.width && y < self.height {
+                self.mask[y] |= 1 << x;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Glyph {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+/// Caché de glifos con correspondencia directa: cada glifo solo puede estar
+/// en una entrada, y un glifo nuevo reemplaza al que la ocupaba.
+pub struct GlyphCache {
+    slots: [Glyph; SLOTS],
+    hits: u64,
+    misses: u64,
+}
+
+impl GlyphCache {
+    const fn new() -> Self {
+        Self { slots: [Glyph::EMPTY; SLOTS], hits: 0, misses: 0 }
+    }
+
+    /// Devuelve el glifo pedido, rasterizándolo si no estaba en la caché.
+    pub fn get(&mut self, font: &MonoFont, c: char, fg: colors::Color, bg: colors::Color, format: PixelFormat) -> &Glyph {
+        let key = Key::new(font, c, fg, bg);
+        let glyph = &mut self.slots[key.slot()];
+        if glyph.key == key && glyph.format == Some(format) {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+            glyph.render(font, c, fg, bg, format);
+        }
+        glyph
+    }
+
+    /// Número de aciertos y de fallos desde el arranque.
+    pub fn stats(&self) -> (u64, u64) {
+        (self.hits, self.misses)
+    }
+}
+
+/// Caché del escritor de la consola. Empieza a cero, así que vive en `.bss`.
+static mut CACHE: GlyphCache = GlyphCache::new();
+/// Indica si algún escritor ya tomó `CACHE`.
+static CACHE_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Entrega la caché global a su único dueño; las llamadas siguientes devuelven `None`.
+pub fn take() -> Option<&'static mut GlyphCache> {
+    if CACHE_TAKEN.swap(true, Ordering::AcqRel) {
+        return None;
+    }
+    // `CACHE_TAKEN` garantiza que este es el único acceso a la caché.
+    let cache = &raw mut CACHE;
+    Some(unsafe { &mut *cache })
+}
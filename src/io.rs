@@ -0,0 +1,18 @@
+//! Interfaces genéricas de entrada/salida del kernel.
+//!
+//! Permiten que el resto del kernel lea y escriba en un dispositivo sin
+//! conocer el driver concreto que hay detrás (puerto serie, consola, etc.).
+
+/// Un dispositivo que transporta un flujo de bytes en ambos sentidos.
+#[allow(dead_code)]
+pub trait ByteStream {
+    /// Lee los bytes ya recibidos en `buf` sin bloquear.
+    ///
+    /// Devuelve cuántos bytes se copiaron; 0 si no había datos pendientes.
+    fn read(&mut self, buf: &mut [u8]) -> usize;
+
+    /// Escribe los bytes de `buf` en el dispositivo.
+    ///
+    /// Devuelve cuántos bytes se escribieron.
+    fn write(&mut self, buf: &[u8]) -> usize;
+}
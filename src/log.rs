@@ -0,0 +1,233 @@
+//! Sistema de registro (logging) del kernel.
+//!
+//! Cualquier módulo puede emitir mensajes con las macros `error!`, `warn!`,
+//! `info!`, `debug!` y `trace!`, que anotan el nivel, el módulo de origen y el
+//! tiempo de actividad. Los registros se guardan en un búfer circular en
+//! memoria (consultable con el comando `dmesg`) y se reenvían a dos destinos:
+//! el puerto serie COM1 y la consola global del kernel.
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::time::Duration;
+
+use crate::arch::target::serial::{ComPort, SerialPort};
+use crate::colors;
+use crate::console::{self, Console};
+use crate::sync::{lock_order, IrqSpinlock};
+use crate::time;
+
+/// Número de registros que conserva el búfer circular.
+const LOG_CAPACITY: usize = 128;
+/// Longitud máxima del mensaje de un registro; el resto se trunca.
+const MESSAGE_CAPACITY: usize = 120;
+/// Prefijo de `module_path!()` que se omite al mostrar el origen.
+const CRATE_PREFIX: &str = concat!(env!("CARGO_CRATE_NAME"), "::");
+
+/// Nivel de importancia de un registro. Cuanto menor, más grave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    /// Devuelve el nombre del nivel tal y como aparece en los registros.
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+
+    /// Interpreta un nombre de nivel sin distinguir mayúsculas (ej. "warn").
+    pub fn parse(name: &str) -> Option<Level> {
+        [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace]
+            .into_iter()
+            .find(|level| level.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Color con el que se muestra el nivel en la consola.
+    pub fn color(self) -> colors::Color {
+        match self {
+            Level::Error => colors::ERROR_COLOR,
+            Level::Warn => colors::BRIGHT_VIOLET,
+            Level::Info => colors::NEON_GREEN,
+            Level::Debug | Level::Trace => colors::TEXT_PRIMARY,
+        }
+    }
+}
+
+/// Un registro almacenado en el búfer del kernel.
+#[derive(Clone)]
+pub struct Record {
+    /// Número de secuencia del registro desde el arranque.
+    pub sequence: u64,
+    /// Tiempo de actividad en el momento de emitirlo.
+    pub timestamp: Duration,
+    /// Nivel del registro.
+    pub level: Level,
+    /// Módulo que lo emitió, sin el prefijo del crate.
+    pub target: &'static str,
+    /// Mensaje ya formateado (truncado a `MESSAGE_CAPACITY` bytes).
+    pub message: heapless::String<MESSAGE_CAPACITY>,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:>5}.{:03}] {:<5} {}: {}",
+            self.timestamp.as_secs(),
+            self.timestamp.subsec_millis(),
+            self.level.name(),
+            self.target,
+            self.message
+        )
+    }
+}
+
+/// Búfer circular de los registros más recientes.
+struct LogBuffer {
+    records: heapless::Deque<Record, LOG_CAPACITY>,
+    next_sequence: u64,
+}
+
+static LOG: IrqSpinlock<LogBuffer> = IrqSpinlock::new(&lock_order::LOG, LogBuffer {
+    records: heapless::Deque::new(),
+    next_sequence: 0,
+});
+
+/// Nivel máximo que se registra; los registros menos graves se descartan.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Debug as u8);
+/// Nivel máximo que se reenvía a la consola del framebuffer.
+static CONSOLE_LEVEL: AtomicU8 = AtomicU8::new(Level::Warn as u8);
+
+/// Cambia el nivel máximo que se registra.
+#[allow(dead_code)]
+pub fn set_max_level(level: Level) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Cambia el nivel máximo que se muestra en la consola del framebuffer.
+#[allow(dead_code)]
+pub fn set_console_level(level: Level) {
+    CONSOLE_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Emite un registro. Normalmente se usa a través de las macros del módulo.
+///
+/// Es seguro llamarla desde un manejador de interrupciones: el búfer está
+/// protegido por un [`IrqSpinlock`].
+pub fn log(level: Level, target: &'static str, args: fmt::Arguments) {
+    if level as u8 > MAX_LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let mut message = heapless::String::new();
+    // Si el mensaje no cabe, se conserva lo que haya entrado.
+    let _ = message.write_fmt(args);
+
+    let record = {
+        let mut log = LOG.lock();
+        let record = Record {
+            sequence: log.next_sequence,
+            timestamp: time::uptime(),
+            level,
+            target: target.strip_prefix(CRATE_PREFIX).unwrap_or(target),
+            message,
+        };
+        log.next_sequence += 1;
+        if log.records.is_full() {
+            log.records.pop_front();
+        }
+        let _ = log.records.push_back(record.clone());
+        record
+    };
+
+    // Destino serie: todos los registros aceptados.
+    let _ = writeln!(SerialPort::new(ComPort::Com1), "{}", record);
+
+    // Destino consola: solo los registros que alcanzan su umbral.
+    if level as u8 <= CONSOLE_LEVEL.load(Ordering::Relaxed) {
+        console::print_colored(colors::TEXT_SECONDARY, format_args!("[{:>5}.{:03}] ", record.timestamp.as_secs(), record.timestamp.subsec_millis()));
+        console::print_colored(level.color(), format_args!("{:<5} ", level.name()));
+        console::print_colored(colors::TEXT_PRIMARY, format_args!("{}: {}\n", record.target, record.message));
+    }
+}
+
+/// Devuelve el registro con el número de secuencia dado, si sigue en el búfer.
+pub fn record(sequence: u64) -> Option<Record> {
+    let log = LOG.lock();
+    let first = log.records.front()?.sequence;
+    let index = sequence.checked_sub(first)? as usize;
+    log.records.iter().nth(index).cloned()
+}
+
+/// Devuelve el rango de números de secuencia presentes en el búfer.
+pub fn sequence_range() -> core::ops::Range<u64> {
+    let log = LOG.lock();
+    let first = log.records.front().map_or(log.next_sequence, |record| record.sequence);
+    first..log.next_sequence
+}
+
+/// Escribe un registro en la consola con el nivel resaltado en su color.
+pub fn print_record(console: &mut Console, record: &Record) {
+    console.set_color(colors::TEXT_SECONDARY);
+    let _ = write!(console, "[{:>5}.{:03}] ", record.timestamp.as_secs(), record.timestamp.subsec_millis());
+    console.set_color(record.level.color());
+    let _ = write!(console, "{:<5} ", record.level.name());
+    console.set_color(colors::TEXT_PRIMARY);
+    let _ = writeln!(console, "{}: {}", record.target, record.message);
+}
+
+/// Registra un mensaje de nivel `Error`.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Error, module_path!(), format_args!($($arg)*))
+    };
+}
+
+/// Registra un mensaje de nivel `Warn`.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Warn, module_path!(), format_args!($($arg)*))
+    };
+}
+
+/// Registra un mensaje de nivel `Info`.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Info, module_path!(), format_args!($($arg)*))
+    };
+}
+
+/// Registra un mensaje de nivel `Debug`.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Debug, module_path!(), format_args!($($arg)*))
+    };
+}
+
+/// Registra un mensaje de nivel `Trace`.
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Trace, module_path!(), format_args!($($arg)*))
+    };
+}
+
+// Las macros se exportan en la raíz del crate; se reexportan aquí para poder
+// invocarlas como `log::info!(...)`.
+#[allow(unused_imports)]
+pub(crate) use crate::{debug, error, info, trace, warn};
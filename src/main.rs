@@ -8,6 +8,8 @@
 #![no_std]
 #![no_main]
 
+extern crate alloc;
+
 use core::arch::asm;
 use core::panic::PanicInfo;
 
@@ -18,13 +20,31 @@ mod colors;
 mod branding;
 mod shell;
 mod app;
+mod ansi;
 mod arch;
+mod console;
+mod dirty;
+mod elf;
+mod fault;
+mod fs;
+mod glyph_cache;
+mod io;
+mod log;
+mod memory;
+mod pixel_format;
+mod process;
+mod sched;
+mod sync;
+mod syscall;
+mod task;
+mod text_grid;
+mod time;
 
 /// Petición al gestor de arranque Limine para obtener un framebuffer.
 ///
 /// El framebuffer es una región de memoria que representa los píxeles de la pantalla,
 /// permitiendo el dibujo en modo gráfico.
-static FRAMEBUFFER_REQUEST: limine::request::FramebufferRequest = limine::request::FramebufferRequest::new();
+pub static FRAMEBUFFER_REQUEST: limine::request::FramebufferRequest = limine::request::FramebufferRequest::new();
 
 /// Petición al gestor de arranque Limine para obtener el mapa de memoria.
 ///
@@ -32,6 +52,19 @@ static FRAMEBUFFER_REQUEST: limine::request::FramebufferRequest = limine::reques
 /// disponibles para ser usadas por el kernel.
 pub static MEMMAP_REQUEST: limine::request::MemoryMapRequest = limine::request::MemoryMapRequest::new();
 
+/// Petición al gestor de arranque Limine para obtener el desplazamiento del
+/// mapeo directo de la memoria física (HHDM).
+pub static HHDM_REQUEST: limine::request::HhdmRequest = limine::request::HhdmRequest::new();
+
+/// Petición al gestor de arranque Limine para conocer las direcciones física
+/// y virtual en las que cargó el kernel.
+pub static EXECUTABLE_ADDRESS_REQUEST: limine::request::ExecutableAddressRequest =
+    limine::request::ExecutableAddressRequest::new();
+
+/// Petición al gestor de arranque Limine para obtener los módulos cargados
+/// junto al kernel, entre ellos el initramfs.
+pub static MODULE_REQUEST: limine::request::ModuleRequest = limine::request::ModuleRequest::new();
+
 /// Punto de entrada del kernel, llamado por el gestor de arranque.
 ///
 /// Esta función no debe retornar nunca, por eso su tipo de retorno es `!`.
@@ -49,10 +82,29 @@ pub extern "C" fn _start() -> ! {
     // un framebuffer para poder dibujar en la pantalla.
     if let Some(framebuffer_response) = FRAMEBUFFER_REQUEST.get_response() {
         if let Some(framebuffer) = framebuffer_response.framebuffers().next() {
-            let mut writer = vga::FramebufferWriter::new(&framebuffer); 
+            // El framebuffer pasa a ser la consola global del kernel, de modo que
+            // cualquier módulo pueda imprimir en ella con `println!`.
+            console::init(vga::FramebufferWriter::double_buffered(&framebuffer));
+
+            // Configura la GDT, la IDT, el PIC y el temporizador, y habilita las
+            // interrupciones. Se hace antes de la pantalla de carga para que la
+            // animación pueda medir el tiempo con el temporizador del sistema.
+            arch::init();
+
+            // Construye el asignador de memoria física a partir del mapa de
+            // memoria y prepara el heap del kernel.
+            memory::init();
+
+            // A partir de aquí, este código es el hilo `kernel` y el
+            // temporizador reparte la CPU entre los hilos.
+            sched::init();
+
+            // Monta el initramfs como raíz del sistema de ficheros.
+            fs::init();
 
             // --- Etapa 1: Pantalla de bienvenida y carga ---
-            {
+            // La pantalla de carga toma el framebuffer completo de la consola.
+            console::lock().unwrap().fullscreen(|writer| {
                 writer.clear(colors::DEEP_BLACK);
                 
                 let logo_x = (framebuffer.width() as usize - branding::VESPER_LOGO_IMAGE.width) / 2;
@@ -62,6 +114,7 @@ pub extern "C" fn _start() -> ! {
                 let text_y_start = writer.y_pos();
                 writer.draw_centered_text(text_y_start, "Vesper OS v0.1.0", colors::TEXT_PRIMARY);
                 writer.draw_centered_text(text_y_start + 25, "Portable WebAssembly OS", colors::NEON_GREEN);
+                writer.present();
 
                 // --- Barra de carga ---
                 let bar_width = 300;
@@ -77,32 +130,22 @@ pub extern "C" fn _start() -> ! {
                 for i in 0..=inner_bar_width {
                     // Dibuja la parte de progreso de la barra
                     writer.draw_rect(bar_x + 2, bar_y + 2, i, bar_height - 4, colors::NEON_GREEN);
+                    writer.present();
                     // Pequeña pausa para que la animación sea visible. Ajusta el valor para cambiar la velocidad.
-                    for _ in 0..1_500_000 { unsafe { asm!("nop"); } }
+                    time::sleep_ms(5);
                 }
-            }
-
-            // --- Etapa 2: Inicializar Interrupts y Shell ---
-            arch::init(); // Configura la IDT, el PIC y habilita las interrupciones.
+            });
 
-            writer.clear(colors::BACKGROUND_COLOR);
-            let mut shell = shell::Shell::new();
-            shell.draw_prompt(&mut writer);
+            // --- Etapa 2: Tareas del kernel ---
+            // La shell y el parpadeo del cursor son tareas asíncronas: duermen
+            // hasta que las despierta la interrupción del teclado o la del
+            // temporizador, y mientras tanto la CPU queda para otros hilos.
+            let mut executor = task::Executor::new();
+            executor.spawn(task::Task::new(shell::Shell::new().run()));
+            executor.spawn(task::Task::new(console::blink_cursor()));
 
             // --- Etapa 3: Bucle principal del Kernel ---
-            // Ahora que las interrupciones están habilitadas, podemos
-            // recibir entrada del teclado.
-            loop {
-                // Sondea en busca de una tecla presionada.
-                if let Some(key) = arch::target::keyboard::poll_key() {
-                    if let pc_keyboard::DecodedKey::Unicode(character) = key {
-                        // Pasa el carácter a la shell para que lo procese.
-                        shell.handle_input_char(character, &mut writer);
-                    }
-                }
-                // Detiene la CPU hasta la próxima interrupción para ahorrar energía.
-                arch::wait_for_interrupt();
-            }
+            executor.run();
         }
     }
 
@@ -112,12 +155,14 @@ pub extern "C" fn _start() -> ! {
 
 /// Manejador de pánicos.
 ///
-/// Esta función se llama cuando el kernel entra en pánico.
-/// Su única tarea es detener la CPU de forma segura para prevenir más daños.
+/// Esta función se llama cuando el kernel entra en pánico. Deshabilita las
+/// interrupciones, muestra en pantalla (y en la consola de depuración) el
+/// mensaje y la ubicación del pánico, y detiene la CPU para prevenir más daños.
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    // Por ahora, simplemente llamamos a hcf(). En el futuro, podría imprimir
-    // la información del pánico en la pantalla.
+fn panic(info: &PanicInfo) -> ! {
+    // Ninguna interrupción debe volver a dibujar ni a tocar el estado del kernel.
+    unsafe { asm!("cli", options(nomem, nostack)); }
+    fault::report_panic(info);
     hcf();
 }
 
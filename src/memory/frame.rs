@@ -0,0 +1,217 @@
+//! Asignador de marcos de memoria física.
+//!
+//! La memoria física se reparte en marcos de 4 KiB. Un mapa de bits guarda el
+//! estado de cada marco, desde la dirección 0 hasta el final de la última
+//! región utilizable: un bit a 1 indica que el marco está ocupado (o que no
+//! es memoria utilizable), y un bit a 0 que está libre.
+//!
+//! El propio mapa de bits se guarda en la primera región utilizable en la que
+//! cabe, y se accede a él a través del mapeo directo de la memoria física
+//! (HHDM) que prepara Limine.
+
+use core::fmt;
+
+use super::FRAME_SIZE;
+
+/// Bits de cada palabra del mapa de bits.
+const BITS: usize = u64::BITS as usize;
+
+/// Una región de memoria física, en bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Region {
+    pub const fn new(start: u64, length: u64) -> Self {
+        Self { start, end: start + length }
+    }
+
+    /// Marcos contenidos por completo en la región.
+    fn frames_inside(&self) -> core::ops::Range<usize> {
+        (self.start.div_ceil(FRAME_SIZE) as usize)..((self.end / FRAME_SIZE) as usize)
+    }
+
+    /// Marcos que tocan la región, aunque sea en parte.
+    fn frames_touched(&self) -> core::ops::Range<usize> {
+        ((self.start / FRAME_SIZE) as usize)..(self.end.div_ceil(FRAME_SIZE) as usize)
+    }
+}
+
+/// Estadísticas del asignador de marcos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Marcos de memoria utilizable, ocupados o no.
+    pub total: usize,
+    /// Marcos libres.
+    pub free: usize,
+}
+
+impl FrameStats {
+    /// Marcos ocupados.
+    pub fn used(&self) -> usize {
+        self.total - self.free
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total as u64 * FRAME_SIZE
+    }
+
+    pub fn free_bytes(&self) -> u64 {
+        self.free as u64 * FRAME_SIZE
+    }
+
+    pub fn used_bytes(&self) -> u64 {
+        self.used() as u64 * FRAME_SIZE
+    }
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} KiB libres de {} KiB ({} marcos ocupados de {})",
+            self.free_bytes() / 1024,
+            self.total_bytes() / 1024,
+            self.used(),
+            self.total
+        )
+    }
+}
+
+/// Asignador de marcos basado en un mapa de bits.
+pub struct FrameAllocator {
+    /// Un bit por marco; 1 es ocupado.
+    bitmap: &'static mut [u64],
+    /// Número de marcos que cubre el mapa de bits.
+    frames: usize,
+    stats: FrameStats,
+    /// Palabra del mapa de bits por la que empezar a buscar un marco libre.
+    next: usize,
+}
+
+impl FrameAllocator {
+    /// Crea el asignador a partir de las regiones de memoria utilizable.
+    ///
+    /// El mapa de bits se coloca al principio de la primera región en la que
+    /// cabe; `to_virtual` traduce su dirección física a una dirección
+    /// accesible. Devuelve `None` si ninguna región es lo bastante grande.
+    pub fn new(usable: impl Iterator<Item = Region> + Clone, to_virtual: impl Fn(u64) -> *mut u8) -> Option<Self> {
+        let end = usable.clone().map(|region| region.end).max()?;
+        let frames = end.div_ceil(FRAME_SIZE) as usize;
+        let words = frames.div_ceil(BITS);
+        let bitmap_bytes = (words * size_of::<u64>()) as u64;
+
+        let home = usable.clone().find_map(|region| {
+            let start = region.start.next_multiple_of(FRAME_SIZE);
+            (start + bitmap_bytes <= region.end).then_some(start)
+        })?;
+        // La región es memoria utilizable que nadie más usa todavía, y el
+        // mapeo directo la hace accesible.
+        let bitmap = unsafe { core::slice::from_raw_parts_mut(to_virtual(home) as *mut u64, words) };
+        bitmap.fill(u64::MAX);
+
+        let mut allocator = Self { bitmap, frames, stats: FrameStats { total: 0, free: 0 }, next: 0 };
+        for region in usable {
+            for frame in region.frames_inside() {
+                // Las regiones no deberían solaparse, pero así un marco nunca cuenta dos veces.
+                if allocator.is_used(frame) {
+                    allocator.set_free(frame);
+                }
+            }
+        }
+        allocator.stats.total = allocator.stats.free;
+        allocator.reserve(Region::new(home, bitmap_bytes));
+        Some(allocator)
+    }
+
+    /// Marca como ocupados los marcos que toca `region`, para que nunca se
+    /// entreguen. Los que ya estaban ocupados no cambian.
+    pub fn reserve(&mut self, region: Region) {
+        for frame in region.frames_touched() {
+            if frame < self.frames && !self.is_used(frame) {
+                self.set_used(frame);
+            }
+        }
+    }
+
+    /// Entrega un marco libre y devuelve su dirección física.
+    pub fn allocate(&mut self) -> Option<u64> {
+        let words = self.bitmap.len();
+        for i in 0..words {
+            let word = (self.next + i) % words;
+            let bits = self.bitmap[word];
+            if bits != u64::MAX {
+                let frame = word * BITS + bits.trailing_ones() as usize;
+                if frame >= self.frames {
+                    continue;
+                }
+                self.set_used(frame);
+                self.next = word;
+                return Some(frame as u64 * FRAME_SIZE);
+            }
+        }
+        None
+    }
+
+    /// Entrega `count` marcos contiguos cuyo primero está alineado a `align`
+    /// marcos, y devuelve la dirección física del primero.
+    pub fn allocate_contiguous(&mut self, count: usize, align: usize) -> Option<u64> {
+        if count == 0 {
+            return None;
+        }
+        let align = align.max(1);
+        let mut start = 0;
+        while start + count <= self.frames {
+            // Busca el último marco ocupado del tramo; si no hay, el tramo sirve.
+            match (start..start + count).rev().find(|&frame| self.is_used(frame)) {
+                Some(used) => start = (used + 1).next_multiple_of(align),
+                None => {
+                    for frame in start..start + count {
+                        self.set_used(frame);
+                    }
+                    return Some(start as u64 * FRAME_SIZE);
+                }
+            }
+        }
+        None
+    }
+
+    /// Devuelve un marco entregado por `allocate`.
+    pub fn free(&mut self, address: u64) {
+        self.free_contiguous(address, 1);
+    }
+
+    /// Devuelve `count` marcos contiguos entregados por `allocate_contiguous`.
+    ///
+    /// Liberar un marco que no estaba ocupado es un error del kernel y provoca un pánico.
+    pub fn free_contiguous(&mut self, address: u64, count: usize) {
+        assert!(address.is_multiple_of(FRAME_SIZE), "dirección de marco no alineada: {:#x}", address);
+        let first = (address / FRAME_SIZE) as usize;
+        for frame in first..first + count {
+            assert!(frame < self.frames && self.is_used(frame), "marco liberado dos veces: {:#x}", frame as u64 * FRAME_SIZE);
+            self.set_free(frame);
+        }
+        self.next = self.next.min(first / BITS);
+    }
+
+    /// Estadísticas actuales.
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    fn is_used(&self, frame: usize) -> bool {
+        self.bitmap[frame / BITS] & (1 << (frame % BITS)) != 0
+    }
+
+    fn set_used(&mut self, frame: usize) {
+        self.bitmap[frame / BITS] |= 1 << (frame % BITS);
+        self.stats.free -= 1;
+    }
+
+    fn set_free(&mut self, frame: usize) {
+        self.bitmap[frame / BITS] &= !(1 << (frame % BITS));
+        self.stats.free += 1;
+    }
+}
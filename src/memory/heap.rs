@@ -0,0 +1,269 @@
+//! Heap del kernel.
+//!
+//! Implementa el `#[global_allocator]` que usan `alloc::boxed::Box`,
+//! `alloc::vec::Vec`, `alloc::string::String` y el resto de colecciones de la
+//! crate `alloc`. La memoria libre se guarda en una lista enlazada de bloques
+//! ordenada por dirección: se asigna el primer bloque en el que cabe la
+//! petición y, al liberar, el bloque se une con sus vecinos libres.
+//!
+//! El heap empieza con unos pocos marcos físicos y, cuando una petición no
+//! cabe en ningún bloque libre, pide más al asignador de marcos. Los marcos
+//! se usan a través del mapeo directo de la memoria física, así que no tienen
+//! que ser contiguos entre sí.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::fmt;
+use core::ptr;
+
+use super::FRAME_SIZE;
+use crate::log;
+use crate::sync::{lock_order, IrqSpinlock};
+
+/// Tamaño inicial del heap.
+const INITIAL_SIZE: usize = 256 * 1024;
+/// Tamaño mínimo de cada ampliación del heap.
+const GROW_SIZE: usize = 64 * 1024;
+/// Tamaño y alineación mínimos de un bloque: debe caber la cabecera de un
+/// bloque libre.
+const MIN_BLOCK: usize = size_of::<FreeBlock>();
+
+/// Cabecera de un bloque libre, guardada al principio del propio bloque.
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+/// Estadísticas del heap.
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    /// Bytes que el heap ha obtenido del asignador de marcos.
+    pub size: usize,
+    /// Bytes entregados y aún no liberados (redondeados al tamaño de bloque).
+    pub used: usize,
+    /// Asignaciones vivas.
+    pub live: usize,
+    /// Asignaciones desde el arranque.
+    pub allocations: u64,
+    /// Peticiones que no se pudieron atender.
+    pub failures: u64,
+    /// Veces que el heap ha crecido.
+    pub grows: u64,
+    /// Bloques libres.
+    pub free_blocks: usize,
+    /// Tamaño del mayor bloque libre.
+    pub largest_free: usize,
+}
+
+impl HeapStats {
+    /// Bytes libres dentro del heap.
+    pub fn free(&self) -> usize {
+        self.size - self.used
+    }
+}
+
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} KiB en uso de {} KiB, {} asignaciones vivas", self.used / 1024, self.size / 1024, self.live)
+    }
+}
+
+/// Estado del heap, protegido por el lock de [`KernelHeap`].
+struct Heap {
+    /// Primer bloque libre; la lista está ordenada por dirección.
+    head: *mut FreeBlock,
+    stats: HeapStats,
+}
+
+// Los bloques libres solo se tocan con el lock del heap tomado.
+unsafe impl Send for Heap {}
+
+impl Heap {
+    const fn new() -> Self {
+        Self {
+            head: ptr::null_mut(),
+            stats: HeapStats {
+                size: 0,
+                used: 0,
+                live: 0,
+                allocations: 0,
+                failures: 0,
+                grows: 0,
+                free_blocks: 0,
+                largest_free: 0,
+            },
+        }
+    }
+
+    /// Tamaño y alineación reales de un bloque para `layout`.
+    fn block_layout(layout: Layout) -> (usize, usize) {
+        let align = layout.align().max(MIN_BLOCK);
+        let size = layout.size().max(MIN_BLOCK).next_multiple_of(MIN_BLOCK);
+        (size, align)
+    }
+
+    /// Busca un bloque libre para `size` bytes alineados a `align` y lo
+    /// retira de la lista, devolviendo a ella los sobrantes.
+    unsafe fn take(&mut self, size: usize, align: usize) -> *mut u8 {
+        let mut previous: *mut FreeBlock = ptr::null_mut();
+        let mut current = self.head;
+        while !current.is_null() {
+            let (start, block_size, next) = unsafe { (current as usize, (*current).size, (*current).next) };
+            let end = start + block_size;
+            // El hueco delante del bloque asignado ha de poder ser un bloque libre.
+            let mut address = start.next_multiple_of(align);
+            if address != start && address - start < MIN_BLOCK {
+                address = (start + MIN_BLOCK).next_multiple_of(align);
+            }
+            let tail = end.checked_sub(address + size);
+            if let Some(tail) = tail
+                && (tail == 0 || tail >= MIN_BLOCK)
+            {
+                // Se quita el bloque de la lista y se devuelven el hueco
+                // delantero y el sobrante trasero.
+                unsafe {
+                    self.unlink(previous, next);
+                    if address > start {
+                        self.insert(start, address - start);
+                    }
+                    if tail > 0 {
+                        self.insert(address + size, tail);
+                    }
+                }
+                return address as *mut u8;
+            }
+            previous = current;
+            current = next;
+        }
+        ptr::null_mut()
+    }
+
+    /// Quita de la lista el bloque que sigue a `previous` (o el primero, si
+    /// `previous` es nulo); `next` es el bloque que lo seguía.
+    unsafe fn unlink(&mut self, previous: *mut FreeBlock, next: *mut FreeBlock) {
+        if previous.is_null() {
+            self.head = next;
+        } else {
+            unsafe { (*previous).next = next };
+        }
+        self.stats.free_blocks -= 1;
+    }
+
+    /// Añade a la lista el bloque libre `[address, address + size)`, uniéndolo
+    /// con los bloques vecinos si son contiguos.
+    unsafe fn insert(&mut self, address: usize, size: usize) {
+        let mut previous: *mut FreeBlock = ptr::null_mut();
+        let mut next = self.head;
+        while !next.is_null() && (next as usize) < address {
+            previous = next;
+            next = unsafe { (*next).next };
+        }
+
+        let block = address as *mut FreeBlock;
+        unsafe {
+            block.write(FreeBlock { size, next });
+            self.stats.free_blocks += 1;
+            if previous.is_null() {
+                self.head = block;
+            } else {
+                (*previous).next = block;
+            }
+            // Une con el siguiente.
+            if !next.is_null() && address + size == next as usize {
+                (*block).size += (*next).size;
+                (*block).next = (*next).next;
+                self.stats.free_blocks -= 1;
+            }
+            // Une con el anterior.
+            if !previous.is_null() && previous as usize + (*previous).size == address {
+                (*previous).size += (*block).size;
+                (*previous).next = (*block).next;
+                self.stats.free_blocks -= 1;
+            }
+        }
+    }
+
+    /// Pide al asignador de marcos al menos `bytes` bytes y los añade al heap.
+    fn grow(&mut self, bytes: usize) -> bool {
+        let frames = bytes.div_ceil(FRAME_SIZE as usize);
+        let Some(physical) = super::allocate_frames(frames, 1) else { return false };
+        let size = frames * FRAME_SIZE as usize;
+        unsafe { self.insert(super::phys_to_virt(physical) as usize, size) };
+        self.stats.size += size;
+        self.stats.grows += 1;
+        true
+    }
+
+    fn allocate(&mut self, layout: Layout) -> *mut u8 {
+        let (size, align) = Self::block_layout(layout);
+        let mut block = unsafe { self.take(size, align) };
+        // No cabe: se amplía el heap con espacio de sobra para la alineación.
+        if block.is_null() && self.grow((size + align).max(GROW_SIZE)) {
+            block = unsafe { self.take(size, align) };
+        }
+        if block.is_null() {
+            self.stats.failures += 1;
+            return block;
+        }
+        self.stats.used += size;
+        self.stats.live += 1;
+        self.stats.allocations += 1;
+        block
+    }
+
+    fn deallocate(&mut self, block: *mut u8, layout: Layout) {
+        let (size, _) = Self::block_layout(layout);
+        unsafe { self.insert(block as usize, size) };
+        self.stats.used -= size;
+        self.stats.live -= 1;
+    }
+
+    /// Estadísticas actuales, con el recorrido de la lista de bloques libres.
+    fn stats(&self) -> HeapStats {
+        let mut stats = self.stats;
+        let mut current = self.head;
+        while !current.is_null() {
+            unsafe {
+                stats.largest_free = stats.largest_free.max((*current).size);
+                current = (*current).next;
+            }
+        }
+        stats
+    }
+}
+
+/// El asignador global del kernel.
+pub struct KernelHeap {
+    heap: IrqSpinlock<Heap>,
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let block = self.heap.lock().allocate(layout);
+        if block.is_null() {
+            // Devolver nulo hace que `alloc` invoque su manejador de errores,
+            // que provoca un pánico; las colecciones con `try_reserve` pueden
+            // recuperarse.
+            log::error!("heap: sin memoria para {} bytes (alineación {})", layout.size(), layout.align());
+        }
+        block
+    }
+
+    unsafe fn dealloc(&self, block: *mut u8, layout: Layout) {
+        self.heap.lock().deallocate(block, layout);
+    }
+}
+
+#[global_allocator]
+static HEAP: KernelHeap = KernelHeap { heap: IrqSpinlock::new(&lock_order::HEAP, Heap::new()) };
+
+/// Da al heap su memoria inicial. Requiere el asignador de marcos.
+pub fn init() {
+    let grown = HEAP.heap.lock().grow(INITIAL_SIZE);
+    assert!(grown, "no hay memoria física para el heap del kernel");
+    log::info!("heap del kernel: {} KiB iniciales", INITIAL_SIZE / 1024);
+}
+
+/// Estadísticas del heap.
+pub fn stats() -> HeapStats {
+    HEAP.heap.lock().stats()
+}
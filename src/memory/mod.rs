@@ -0,0 +1,116 @@
+//! Gestión de la memoria del kernel.
+//!
+//! Limine deja toda la memoria física mapeada a partir de un desplazamiento
+//! fijo (el HHDM, *higher half direct map*), de modo que el kernel puede leer
+//! y escribir cualquier dirección física sumándole ese desplazamiento. Sobre
+//! el mapa de memoria que entrega Limine se construye el asignador de marcos
+//! físicos, y sobre este el heap del kernel.
+
+pub mod frame;
+pub mod heap;
+pub mod stack;
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use limine::memory_map::EntryType;
+
+use self::frame::{FrameAllocator, FrameStats, Region};
+use crate::arch;
+use crate::log;
+use crate::sync::{lock_order, IrqSpinlock};
+use crate::{EXECUTABLE_ADDRESS_REQUEST, FRAMEBUFFER_REQUEST, HHDM_REQUEST, MEMMAP_REQUEST};
+
+/// Tamaño de un marco de memoria física.
+pub const FRAME_SIZE: u64 = 4096;
+
+/// Desplazamiento del mapeo directo de la memoria física.
+static HHDM_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+/// Asignador de marcos físicos; `None` hasta que se llama a [`init`].
+static FRAMES: IrqSpinlock<Option<FrameAllocator>> = IrqSpinlock::new(&lock_order::FRAMES, None);
+
+unsafe extern "C" {
+    /// Final de la imagen del kernel en memoria, definido en `linker.ld`.
+    static __kernel_end: u8;
+}
+
+/// Inicializa el asignador de marcos a partir del mapa de memoria de Limine
+/// y, a continuación, las tablas de páginas del kernel y el heap.
+///
+/// Además de lo que el mapa de memoria ya marca como no utilizable, se
+/// reservan de forma explícita la imagen del kernel, los módulos, los
+/// framebuffers y el primer marco (para que la dirección física 0 nunca sea
+/// un marco válido).
+pub fn init() {
+    let offset = HHDM_REQUEST.get_response().expect("Limine no proporcionó el HHDM").offset();
+    HHDM_OFFSET.store(offset, Ordering::Relaxed);
+
+    let entries = MEMMAP_REQUEST.get_response().expect("Limine no proporcionó el mapa de memoria").entries();
+    let usable = entries
+        .iter()
+        .filter(|entry| entry.entry_type == EntryType::USABLE)
+        .map(|entry| Region::new(entry.base, entry.length));
+    let mut frames = FrameAllocator::new(usable, phys_to_virt).expect("no hay memoria para el mapa de bits de marcos");
+
+    frames.reserve(Region::new(0, FRAME_SIZE));
+    for entry in entries.iter().filter(|entry| entry.entry_type == EntryType::EXECUTABLE_AND_MODULES) {
+        frames.reserve(Region::new(entry.base, entry.length));
+    }
+    if let Some(kernel) = EXECUTABLE_ADDRESS_REQUEST.get_response() {
+        let end = &raw const __kernel_end as u64;
+        frames.reserve(Region::new(kernel.physical_base(), end - kernel.virtual_base()));
+    }
+    if let Some(response) = FRAMEBUFFER_REQUEST.get_response() {
+        for framebuffer in response.framebuffers() {
+            let start = framebuffer.addr() as u64 - offset;
+            frames.reserve(Region::new(start, framebuffer.pitch() * framebuffer.height()));
+        }
+    }
+
+    log::info!("memoria física: {}", frames.stats());
+    *FRAMES.lock() = Some(frames);
+
+    arch::init_paging();
+    heap::init();
+}
+
+/// Desplazamiento del mapeo directo de la memoria física.
+pub fn hhdm_offset() -> u64 {
+    HHDM_OFFSET.load(Ordering::Relaxed)
+}
+
+/// Dirección virtual a través de la que se accede a la dirección física `address`.
+pub fn phys_to_virt(address: u64) -> *mut u8 {
+    (address + hhdm_offset()) as *mut u8
+}
+
+/// Ejecuta `f` sobre el asignador de marcos.
+fn with_frames<R>(f: impl FnOnce(&mut FrameAllocator) -> R) -> R {
+    f(FRAMES.lock().as_mut().expect("asignador de marcos sin inicializar"))
+}
+
+/// Reserva un marco físico de 4 KiB y devuelve su dirección física.
+pub fn allocate_frame() -> Option<u64> {
+    with_frames(|frames| frames.allocate())
+}
+
+/// Reserva `count` marcos físicos contiguos, alineados a `align` marcos.
+pub fn allocate_frames(count: usize, align: usize) -> Option<u64> {
+    with_frames(|frames| frames.allocate_contiguous(count, align))
+}
+
+/// Libera un marco obtenido con [`allocate_frame`].
+pub fn free_frame(address: u64) {
+    with_frames(|frames| frames.free(address));
+}
+
+/// Libera `count` marcos obtenidos con [`allocate_frames`].
+#[allow(dead_code)]
+pub fn free_frames(address: u64, count: usize) {
+    with_frames(|frames| frames.free_contiguous(address, count));
+}
+
+/// Estadísticas de la memoria física, o `None` si aún no se ha inicializado.
+pub fn frame_stats() -> Option<FrameStats> {
+    FRAMES.lock().as_ref().map(|frames| frames.stats())
+}
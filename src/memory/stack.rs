@@ -0,0 +1,77 @@
+//! Pilas de los hilos del kernel.
+//!
+//! Cada pila ocupa una ranura fija de una zona virtual reservada para ellas.
+//! Solo la parte alta de la ranura se mapea; el resto queda sin mapear como
+//! zona de guarda, de modo que un desbordamiento provoca un fallo de página en
+//! lugar de pisar en silencio la pila de otro hilo.
+
+use alloc::vec::Vec;
+
+use super::FRAME_SIZE;
+use crate::arch::target::paging::{self, Flags};
+use crate::sync::{lock_order, IrqSpinlock};
+
+/// Principio de la zona de pilas: la entrada 510 de la PML4, que no usan ni
+/// el HHDM ni la imagen del kernel.
+const STACK_AREA: u64 = 0xFFFF_FF00_0000_0000;
+/// Tamaño de cada ranura, incluida la zona de guarda.
+const SLOT_SIZE: u64 = 64 * 1024;
+/// Tamaño de la parte mapeada de cada pila.
+pub const STACK_SIZE: u64 = 32 * 1024;
+
+/// Ranuras entregadas y ranuras devueltas que se pueden reutilizar.
+struct Slots {
+    next: u64,
+    free: Vec<u64>,
+}
+
+static SLOTS: IrqSpinlock<Slots> = IrqSpinlock::new(&lock_order::STACKS, Slots { next: 0, free: Vec::new() });
+
+/// Una pila del kernel; al soltarla se desmapea y se liberan sus marcos.
+pub struct KernelStack {
+    slot: u64,
+}
+
+impl KernelStack {
+    /// Reserva una pila nueva, o devuelve `None` si no hay memoria física.
+    pub fn new() -> Option<Self> {
+        let slot = {
+            let mut slots = SLOTS.lock();
+            slots.free.pop().unwrap_or_else(|| {
+                slots.next += 1;
+                slots.next - 1
+            })
+        };
+        let stack = Self { slot };
+        for page in stack.pages() {
+            // Si falta memoria a mitad, `Drop` deshace lo que se llegó a mapear.
+            let frame = super::allocate_frame()?;
+            if paging::map(page, frame, Flags::WRITABLE | Flags::NO_EXECUTE).is_err() {
+                super::free_frame(frame);
+                return None;
+            }
+        }
+        Some(stack)
+    }
+
+    /// Dirección de la cima de la pila, donde empieza a crecer hacia abajo.
+    pub fn top(&self) -> u64 {
+        STACK_AREA + (self.slot + 1) * SLOT_SIZE
+    }
+
+    /// Direcciones de las páginas mapeadas de la pila.
+    fn pages(&self) -> impl Iterator<Item = u64> + use<> {
+        (self.top() - STACK_SIZE..self.top()).step_by(FRAME_SIZE as usize)
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        for page in self.pages() {
+            if let Ok(frame) = paging::unmap(page) {
+                super::free_frame(frame);
+            }
+        }
+        SLOTS.lock().free.push(self.slot);
+    }
+}
@@ -0,0 +1,152 @@
+//! Formato de los píxeles del framebuffer.
+//!
+//! Limine informa de cuántos bits ocupa cada píxel y de la posición y tamaño
+//! de los canales rojo, verde y azul. Este módulo convierte los colores del
+//! kernel (`colors::Color`, siempre 0x00RRGGBB) al valor que espera el
+//! framebuffer. Cuando un canal tiene menos de 8 bits (por ejemplo en RGB565)
+//! se aplica un tramado ordenado para disimular las bandas de color.
+
+use core::fmt;
+
+use limine::framebuffer::Framebuffer;
+
+use crate::colors;
+
+/// Matriz de Bayer 4x4 para el tramado ordenado, con umbrales de 0 a 15.
+const BAYER_4X4: [[u32; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// Posición y tamaño de un canal de color dentro del valor de un píxel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Channel {
+    /// Número de bits del canal.
+    pub size: u8,
+    /// Posición del bit menos significativo del canal.
+    pub shift: u8,
+}
+
+impl Channel {
+    const fn new(size: u8, shift: u8) -> Self {
+        Self { size, shift }
+    }
+
+    /// Convierte un valor de 8 bits al tamaño del canal y lo coloca en su posición.
+    ///
+    /// `threshold` (0-15) es el umbral de tramado de la posición del píxel.
+    fn encode(self, value: u32, threshold: u32) -> u32 {
+        let size = self.size.min(8) as u32;
+        if size == 0 {
+            return 0;
+        }
+        let dropped = 8 - size;
+        // Se suma una fracción del escalón antes de truncar: según la posición,
+        // algunos píxeles redondean hacia arriba y otros hacia abajo.
+        let value = if dropped == 0 { value } else { (value + ((threshold << dropped) >> 4)).min(255) };
+        (value >> dropped) << self.shift
+    }
+}
+
+/// Distribuciones de píxel conocidas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// 32 bits, 0x00RRGGBB.
+    Rgb888,
+    /// 32 bits, 0x00BBGGRR.
+    Bgr888,
+    /// 24 bits empaquetados, en orden RGB o BGR.
+    Packed24,
+    /// 16 bits, 5 bits de rojo, 6 de verde y 5 de azul.
+    Rgb565,
+    /// Cualquier otra combinación de máscaras.
+    Other,
+}
+
+impl Layout {
+    /// Nombre de la distribución, para los mensajes del sistema.
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Rgb888 => "RGB888",
+            Layout::Bgr888 => "BGR888",
+            Layout::Packed24 => "24 bpp",
+            Layout::Rgb565 => "RGB565",
+            Layout::Other => "desconocido",
+        }
+    }
+}
+
+/// Formato de píxel de un framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelFormat {
+    /// Bytes que ocupa cada píxel en memoria.
+    pub bytes_per_pixel: usize,
+    pub red: Channel,
+    pub green: Channel,
+    pub blue: Channel,
+}
+
+impl PixelFormat {
+    /// Formato 0x00RRGGBB de 32 bits, el que usan los colores del kernel.
+    pub const RGB888: PixelFormat = PixelFormat {
+        bytes_per_pixel: 4,
+        red: Channel::new(8, 16),
+        green: Channel::new(8, 8),
+        blue: Channel::new(8, 0),
+    };
+
+    /// Lee el formato de píxel de un framebuffer de Limine.
+    pub fn from_framebuffer(fb: &Framebuffer) -> Self {
+        Self {
+            bytes_per_pixel: (fb.bpp() as usize).div_ceil(8),
+            red: Channel::new(fb.red_mask_size(), fb.red_mask_shift()),
+            green: Channel::new(fb.green_mask_size(), fb.green_mask_shift()),
+            blue: Channel::new(fb.blue_mask_size(), fb.blue_mask_shift()),
+        }
+    }
+
+    /// Identifica la distribución de los canales.
+    pub fn layout(&self) -> Layout {
+        let channels = (self.red, self.green, self.blue);
+        let rgb = (Channel::new(8, 16), Channel::new(8, 8), Channel::new(8, 0));
+        let bgr = (Channel::new(8, 0), Channel::new(8, 8), Channel::new(8, 16));
+        match self.bytes_per_pixel {
+            4 if channels == rgb => Layout::Rgb888,
+            4 if channels == bgr => Layout::Bgr888,
+            3 if channels == rgb || channels == bgr => Layout::Packed24,
+            2 if channels == (Channel::new(5, 11), Channel::new(6, 5), Channel::new(5, 0)) => Layout::Rgb565,
+            _ => Layout::Other,
+        }
+    }
+
+    /// Indica si algún canal tiene menos de 8 bits y, por tanto, se aplica tramado.
+    pub fn dithers(&self) -> bool {
+        [self.red, self.green, self.blue].iter().any(|channel| channel.size < 8)
+    }
+
+    /// Convierte un color al valor de píxel que se escribe en la posición `(x, y)`.
+    ///
+    /// La posición solo influye en los formatos con tramado. Los primeros
+    /// `bytes_per_pixel` bytes del resultado en little-endian son el píxel.
+    pub fn encode(&self, color: colors::Color, x: usize, y: usize) -> u32 {
+        if *self == Self::RGB888 {
+            return color & 0x00FF_FFFF;
+        }
+        let threshold = BAYER_4X4[y & 3][x & 3];
+        self.red.encode((color >> 16) & 0xFF, threshold)
+            | self.green.encode((color >> 8) & 0xFF, threshold)
+            | self.blue.encode(color & 0xFF, threshold)
+    }
+}
+
+impl fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} bpp", self.layout().name(), self.bytes_per_pixel * 8)?;
+        if self.dithers() {
+            f.write_str(", con tramado")?;
+        }
+        f.write_str(")")
+    }
+}
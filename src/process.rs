@@ -0,0 +1,254 @@
+//! Procesos de usuario.
+//!
+//! Un proceso es un hilo del planificador con su propio espacio de
+//! direcciones: la mitad inferior, hasta [`USER_END`], es del programa y se
+//! ejecuta en ring 3; la superior es la del kernel, compartida por todos. El
+//! hilo entra en modo usuario nada más arrancar y vuelve al kernel solo por
+//! llamadas al sistema ([`crate::syscall`]), interrupciones o excepciones.
+//!
+//! Todos los marcos de la mitad de usuario son del proceso y se liberan con
+//! su espacio de direcciones cuando el planificador elimina el hilo. El
+//! resto de su estado, el directorio actual y los ficheros abiertos, está
+//! en la tabla de procesos y se libera al terminar.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future;
+use core::task::Poll;
+
+use crate::arch;
+use crate::arch::target::paging::{AddressSpace, Flags, MapError, PAGE_SIZE};
+use crate::elf;
+use crate::fs::file::FileTable;
+use crate::log;
+use crate::memory;
+use crate::sched::{self, ThreadId};
+use crate::sync::{lock_order, IrqSpinlock};
+use crate::task::WakerSlot;
+
+/// Final (exclusivo) de la mitad de usuario. La última página por debajo de
+/// la dirección no canónica queda sin mapear: `syscall` al final de ella
+/// dejaría una dirección de retorno no canónica.
+pub const USER_END: u64 = 0x0000_7FFF_FFFF_F000;
+/// Cima de la pila de usuario de cada proceso.
+pub const USER_STACK_TOP: u64 = USER_END;
+/// Tamaño de la pila de usuario.
+pub const USER_STACK_SIZE: u64 = 64 * 1024;
+/// Base de la pila de usuario: los segmentos del programa quedan por debajo.
+pub const USER_STACK_BASE: u64 = USER_STACK_TOP - USER_STACK_SIZE;
+
+/// Claves del vector auxiliar que recibe el programa en su pila.
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_ENTRY: u64 = 9;
+/// Máximo de entradas del vector auxiliar, incluida la final.
+const AUXILIARY_ENTRIES: usize = 6;
+
+/// Tarea que espera a que termine algún proceso.
+static EXITED: WakerSlot = WakerSlot::new();
+
+/// Estado de un proceso que no está en su hilo.
+pub struct Process {
+    /// Ruta canónica del directorio actual.
+    pub cwd: String,
+    pub files: FileTable,
+}
+
+/// Procesos vivos, por el identificador de su hilo.
+static PROCESSES: IrqSpinlock<BTreeMap<ThreadId, Process>> = IrqSpinlock::new(&lock_order::PROCESSES, BTreeMap::new());
+
+/// Permisos de usuario para una región con los accesos indicados.
+pub fn user_flags(writable: bool, executable: bool) -> Flags {
+    let mut flags = Flags::USER;
+    if writable {
+        flags = flags | Flags::WRITABLE;
+    }
+    if !executable {
+        flags = flags | Flags::NO_EXECUTE;
+    }
+    flags
+}
+
+/// Mapea en `space` marcos nuevos y a cero para las páginas de
+/// `[start, start + len)`. Si falla a mitad, deshace lo que llegó a mapear.
+pub fn map_zeroed(space: &mut AddressSpace, start: u64, len: u64, flags: Flags) -> Result<(), MapError> {
+    let end = start.checked_add(len).map(|end| end.next_multiple_of(PAGE_SIZE)).ok_or(MapError::Unaligned)?;
+    let pages = (start..end).step_by(PAGE_SIZE as usize);
+    for page in pages.clone() {
+        let result = match memory::allocate_frame() {
+            Some(frame) => {
+                // El marco es nuevo y aún no lo ve nadie más que el kernel.
+                unsafe { memory::phys_to_virt(frame).write_bytes(0, PAGE_SIZE as usize) };
+                space.map(page, frame, flags).inspect_err(|_| memory::free_frame(frame))
+            }
+            None => Err(MapError::OutOfMemory),
+        };
+        if let Err(error) = result {
+            for mapped in pages.take_while(|&mapped| mapped != page) {
+                if let Ok(frame) = space.unmap(mapped) {
+                    memory::free_frame(frame);
+                }
+            }
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// Copia `bytes` en la memoria de `space` a partir de `virt`, a través del
+/// mapeo directo de los marcos. Las páginas tienen que estar mapeadas.
+pub fn copy_to(space: &AddressSpace, virt: u64, bytes: &[u8]) -> Result<(), MapError> {
+    let mut copied = 0;
+    while copied < bytes.len() {
+        let address = virt + copied as u64;
+        let chunk = (PAGE_SIZE - address % PAGE_SIZE).min((bytes.len() - copied) as u64) as usize;
+        let (physical, _) = space.translate(address).ok_or(MapError::NotMapped)?;
+        // El marco es del espacio de usuario y el rango no cruza su final.
+        unsafe { memory::phys_to_virt(physical).copy_from_nonoverlapping(bytes[copied..].as_ptr(), chunk) };
+        copied += chunk;
+    }
+    Ok(())
+}
+
+/// Errores al crear un proceso.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// El ejecutable no se pudo cargar.
+    Load(elf::Error),
+    /// Los argumentos no caben en la pila del proceso.
+    ArgumentsTooLong,
+    /// No hay memoria para la pila o el hilo del proceso.
+    OutOfMemory,
+    /// Falló el mapeo de la pila por otra causa que la falta de memoria.
+    Map(MapError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Load(error) => write!(f, "{}", error),
+            Error::ArgumentsTooLong => f.write_str("los argumentos son demasiado largos"),
+            Error::OutOfMemory => f.write_str("no hay memoria para el proceso"),
+            Error::Map(error) => write!(f, "no se pudo mapear la pila: {}", error),
+        }
+    }
+}
+
+impl From<elf::Error> for Error {
+    fn from(error: elf::Error) -> Error {
+        Error::Load(error)
+    }
+}
+
+impl From<MapError> for Error {
+    fn from(error: MapError) -> Error {
+        match error {
+            MapError::OutOfMemory => Error::OutOfMemory,
+            error => Error::Map(error),
+        }
+    }
+}
+
+/// Crea un proceso que ejecuta el ELF `image` con los argumentos `args`,
+/// el primero de los cuales es por convenio el nombre del programa, y con
+/// `cwd` como directorio actual.
+pub fn exec(image: &[u8], args: &[&str], cwd: &str) -> Result<ThreadId, Error> {
+    let name = args.first().copied().unwrap_or("?");
+    let mut space = AddressSpace::new_user()?;
+    let loaded = elf::load(&mut space, image)?;
+    map_zeroed(&mut space, USER_STACK_BASE, USER_STACK_SIZE, user_flags(true, false))?;
+    let stack = initial_stack(&space, args, &loaded)?;
+    let entry = loaded.entry;
+    // Con la tabla tomada, el proceso no puede hacer llamadas al sistema
+    // hasta que conste en ella.
+    let mut processes = PROCESSES.lock();
+    let id = sched::spawn_user(name, space, entry, stack).ok_or(Error::OutOfMemory)?;
+    processes.insert(id, Process { cwd: String::from(cwd), files: FileTable::default() });
+    drop(processes);
+    log::info!("proceso {} creado: {} (entrada en {:#x})", id, name, entry);
+    Ok(id)
+}
+
+/// Prepara la pila inicial que espera el ABI System V y devuelve su cima.
+///
+/// De la dirección más baja a la más alta: `argc`, los punteros de `argv`
+/// terminados en nulo, un `envp` vacío, el vector auxiliar y, por encima,
+/// las cadenas de los argumentos.
+fn initial_stack(space: &AddressSpace, args: &[&str], image: &elf::LoadedImage) -> Result<u64, Error> {
+    let strings: usize = args.iter().map(|arg| arg.len() + 1).sum();
+    let words = 1 + args.len() + 1 + 1 + 2 * AUXILIARY_ENTRIES;
+    if (strings + words * 8) as u64 > USER_STACK_SIZE / 2 {
+        return Err(Error::ArgumentsTooLong);
+    }
+
+    let mut top = USER_STACK_TOP;
+    let mut stack: Vec<u64> = Vec::with_capacity(words);
+    stack.push(args.len() as u64);
+    for arg in args {
+        top -= arg.len() as u64 + 1;
+        copy_to(space, top, arg.as_bytes())?;
+        copy_to(space, top + arg.len() as u64, &[0])?;
+        stack.push(top);
+    }
+    stack.push(0);
+    stack.push(0);
+    if let Some(headers) = image.program_headers {
+        stack.extend([AT_PHDR, headers]);
+    }
+    stack.extend([
+        AT_PHENT, image.program_header_size,
+        AT_PHNUM, image.program_header_count,
+        AT_PAGESZ, PAGE_SIZE,
+        AT_ENTRY, image.entry,
+        AT_NULL, 0,
+    ]);
+
+    // Al entrar en el programa, rsp tiene que estar alineado a 16 bytes.
+    let rsp = (top - stack.len() as u64 * 8) & !0xF;
+    let bytes: Vec<u8> = stack.iter().flat_map(|word| word.to_le_bytes()).collect();
+    copy_to(space, rsp, &bytes)?;
+    Ok(rsp)
+}
+
+/// Termina el proceso actual con el código `code`.
+pub fn exit(code: i64) -> ! {
+    if let Some(id) = sched::current() {
+        log::info!("proceso {} terminado con código {}", id, code);
+        // Cierra sus ficheros, ya sin la tabla tomada.
+        let process = PROCESSES.lock().remove(&id);
+        drop(process);
+    }
+    // Sin interrupciones, quien espera no puede ejecutarse hasta que el hilo
+    // ya conste como terminado.
+    arch::without_interrupts(|| {
+        EXITED.wake();
+        sched::exit();
+    });
+    unreachable!("un proceso terminado ha vuelto a la CPU");
+}
+
+/// Ejecuta `f` con el estado del proceso actual; `None` si el hilo actual
+/// no es un proceso. `f` se ejecuta con las interrupciones deshabilitadas:
+/// no puede leer ni escribir ficheros.
+pub fn with_current<R>(f: impl FnOnce(&mut Process) -> R) -> Option<R> {
+    let id = sched::current()?;
+    PROCESSES.lock().get_mut(&id).map(f)
+}
+
+/// Espera a que termine el proceso `id`.
+pub async fn wait(id: ThreadId) {
+    future::poll_fn(|context| {
+        if !sched::is_alive(id) {
+            return Poll::Ready(());
+        }
+        EXITED.register(context.waker());
+        // Si terminó entre la comprobación y el registro, no llegará otro aviso.
+        if sched::is_alive(id) { Poll::Pending } else { Poll::Ready(()) }
+    })
+    .await
+}
@@ -0,0 +1,466 @@
+//! Planificador de hilos del kernel.
+//!
+//! Cada hilo tiene su propia pila del kernel y se ejecuta hasta que se
+//! bloquea (`sleep`, `join`, `block_on`), cede la CPU (`yield_now`), termina, o agota su
+//! porción de tiempo: la interrupción del temporizador lo expulsa cada
+//! [`TIME_SLICE_TICKS`] ticks y se pasa al siguiente hilo listo, por turno
+//! rotatorio (*round robin*).
+//!
+//! El código que arrancó el kernel se convierte en el hilo `kernel`. Cuando
+//! no hay ningún hilo listo se ejecuta el hilo `idle`, que detiene la CPU
+//! hasta la siguiente interrupción.
+//!
+//! Un hilo puede tener además su propio espacio de direcciones de usuario:
+//! es lo que hace de él un proceso (ver [`crate::process`]). El espacio se
+//! activa al cambiar al hilo y se libera con él.
+//!
+//! El estado del planificador está protegido por un `IrqSpinlock`, y el
+//! cambio de contexto se hace con el lock ya soltado pero con las
+//! interrupciones aún deshabilitadas: el hilo al que se cambia puede volver a
+//! tomarlo.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context as TaskContext, Poll, Waker};
+use core::time::Duration;
+
+use crate::arch;
+use crate::arch::target::context::{self, Context};
+use crate::arch::target::paging::AddressSpace;
+use crate::arch::target::syscall;
+use crate::log;
+use crate::memory::stack::KernelStack;
+use crate::sync::{lock_order, IrqSpinlock};
+
+/// Ticks del temporizador que un hilo puede ejecutarse antes de ser expulsado.
+pub const TIME_SLICE_TICKS: u32 = 10;
+
+/// Identificador de un hilo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ThreadId(u64);
+
+impl fmt::Display for ThreadId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Estado de un hilo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Se está ejecutando.
+    Running,
+    /// Espera su turno.
+    Ready,
+    /// Duerme hasta el tick indicado.
+    Sleeping(u64),
+    /// Espera a que termine otro hilo.
+    Joining(ThreadId),
+    /// Espera a que lo despierte un `Waker` (ver [`block_on`]).
+    Parked,
+    /// Ha terminado; se eliminará en el próximo cambio de contexto.
+    Finished,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Running => f.write_str("ejecutando"),
+            State::Ready => f.write_str("listo"),
+            State::Sleeping(_) => f.write_str("durmiendo"),
+            State::Joining(id) => write!(f, "esperando a {}", id),
+            State::Parked => f.write_str("bloqueado"),
+            State::Finished => f.write_str("terminado"),
+        }
+    }
+}
+
+/// Información de un hilo, para mostrarla.
+#[derive(Debug, Clone)]
+pub struct ThreadInfo {
+    pub id: ThreadId,
+    pub name: String,
+    pub state: State,
+    /// Tiempo de CPU consumido.
+    pub cpu_time: Duration,
+}
+
+/// Un hilo del kernel.
+struct Thread {
+    id: ThreadId,
+    name: String,
+    state: State,
+    context: Context,
+    /// Pila propia, que se libera al eliminar el hilo; `None` para el hilo de
+    /// arranque, que usa la que le dio Limine.
+    #[allow(dead_code)]
+    stack: Option<KernelStack>,
+    /// Espacio de direcciones de usuario, o `None` en los hilos del kernel.
+    address_space: Option<AddressSpace>,
+    /// Ticks del temporizador en los que el hilo estaba en la CPU.
+    cpu_ticks: u64,
+    /// Se le despertó sin que estuviera bloqueado: el próximo `park` no
+    /// debe dormirlo.
+    unpark_pending: bool,
+}
+
+/// Función de un hilo, tal y como se pasa a [`thread_entry`].
+type Entry = Box<dyn FnOnce() + Send + 'static>;
+
+/// Estado global del planificador.
+struct Scheduler {
+    /// Los hilos viven en cajas para que su contexto no se mueva al crecer el vector.
+    #[allow(clippy::vec_box)]
+    threads: Vec<Box<Thread>>,
+    current: ThreadId,
+    idle: ThreadId,
+    /// Hilos listos, en orden de turno. El hilo `idle` nunca está aquí.
+    ready: VecDeque<ThreadId>,
+    next_id: u64,
+    /// Ticks que le quedan al hilo actual antes de ser expulsado.
+    slice_left: u32,
+}
+
+impl Scheduler {
+    fn thread(&mut self, id: ThreadId) -> Option<&mut Thread> {
+        self.threads.iter_mut().find(|thread| thread.id == id).map(|thread| &mut **thread)
+    }
+
+    fn current(&mut self) -> &mut Thread {
+        let id = self.current;
+        self.thread(id).expect("el hilo actual no existe")
+    }
+
+    fn add(&mut self, name: &str, state: State, mut context: Context, stack: Option<KernelStack>, address_space: Option<AddressSpace>) -> ThreadId {
+        let id = ThreadId(self.next_id);
+        self.next_id += 1;
+        if let Some(space) = &address_space {
+            context.set_address_space(space.root());
+        }
+        self.threads.push(Box::new(Thread { id, name: String::from(name), state, context, stack, address_space, cpu_ticks: 0, unpark_pending: false }));
+        if state == State::Ready {
+            self.ready.push_back(id);
+        }
+        id
+    }
+
+    /// Pasa a listos los hilos bloqueados para los que `woken` devuelve `true`.
+    fn wake(&mut self, woken: impl Fn(State) -> bool) {
+        for thread in &mut self.threads {
+            if matches!(thread.state, State::Sleeping(_) | State::Joining(_)) && woken(thread.state) {
+                thread.state = State::Ready;
+                self.ready.push_back(thread.id);
+            }
+        }
+    }
+
+    /// Elige el siguiente hilo y devuelve los contextos entre los que hay que
+    /// cambiar, o `None` si el hilo actual sigue en la CPU.
+    fn pick_next(&mut self) -> Option<(*mut Context, *const Context)> {
+        // Los hilos terminados ya no están en la CPU y se pueden eliminar,
+        // salvo el actual, cuya pila se está usando todavía.
+        let current = self.current;
+        self.threads.retain(|thread| thread.state != State::Finished || thread.id == current);
+
+        let idle = self.idle;
+        let thread = self.current();
+        if thread.state == State::Running {
+            thread.state = State::Ready;
+            if current != idle {
+                self.ready.push_back(current);
+            }
+        }
+        let next = self.ready.pop_front().unwrap_or(idle);
+        self.slice_left = TIME_SLICE_TICKS;
+        self.current = next;
+        let to = self.current();
+        to.state = State::Running;
+        let to = &raw const to.context;
+        if next == current {
+            return None;
+        }
+        let from = &raw mut self.thread(current).expect("el hilo anterior no existe").context;
+        Some((from, to))
+    }
+}
+
+static SCHEDULER: IrqSpinlock<Option<Scheduler>> = IrqSpinlock::new(&lock_order::SCHEDULER, None);
+
+/// Convierte el código en ejecución en el hilo `kernel` y crea el hilo
+/// `idle`. Requiere el heap y las tablas de páginas del kernel.
+pub fn init() {
+    let mut scheduler = Scheduler {
+        threads: Vec::new(),
+        current: ThreadId(0),
+        idle: ThreadId(0),
+        ready: VecDeque::new(),
+        next_id: 0,
+        slice_left: TIME_SLICE_TICKS,
+    };
+    scheduler.current = scheduler.add("kernel", State::Running, Context::current(), None, None);
+    let (context, stack) = prepare(thread_entry, Box::new(idle) as Entry).expect("no hay memoria para el hilo idle");
+    scheduler.idle = scheduler.add("idle", State::Ready, context, Some(stack), None);
+    // El hilo idle no pasa por la cola de listos: se elige cuando está vacía.
+    scheduler.ready.clear();
+    *SCHEDULER.lock() = Some(scheduler);
+    log::info!("planificador iniciado: porción de {} ticks", TIME_SLICE_TICKS);
+}
+
+/// Cuerpo del hilo idle.
+fn idle() {
+    loop {
+        arch::wait_for_interrupt();
+    }
+}
+
+/// Reserva la pila de un hilo nuevo y la prepara para ejecutar `entry`,
+/// que recibe `argument` en una caja.
+fn prepare<T>(entry: extern "C" fn(usize) -> !, argument: T) -> Option<(Context, KernelStack)> {
+    let stack = KernelStack::new()?;
+    let argument = Box::into_raw(Box::new(argument)) as usize;
+    // La pila es nueva y está mapeada; su cima está alineada a página.
+    let context = unsafe { Context::new(stack.top(), entry, argument) };
+    Some((context, stack))
+}
+
+/// Primera función que ejecuta un hilo nuevo.
+extern "C" fn thread_entry(argument: usize) -> ! {
+    // El argumento es la caja que creó `prepare` para este hilo.
+    let entry = unsafe { Box::from_raw(argument as *mut Entry) };
+    // Al hilo se llega desde un cambio de contexto, con las interrupciones deshabilitadas.
+    unsafe { arch::target::cpu::enable_interrupts() };
+    entry();
+    exit();
+}
+
+/// Punto de entrada en modo usuario de un hilo de proceso.
+struct UserStart {
+    rip: u64,
+    rsp: u64,
+}
+
+/// Primera función que ejecuta el hilo de un proceso.
+extern "C" fn user_entry(argument: usize) -> ! {
+    // La caja que creó `prepare` se libera antes de saltar a modo usuario,
+    // del que el hilo ya no vuelve a esta función.
+    let UserStart { rip, rsp } = *unsafe { Box::from_raw(argument as *mut UserStart) };
+    // El cambio de contexto ya ha activado el espacio del proceso y su pila
+    // del kernel; `iretq` habilita las interrupciones.
+    unsafe { syscall::enter_user(rip, rsp) }
+}
+
+/// Crea un hilo que ejecuta `f` y lo pone en la cola de listos. Devuelve
+/// `None` si no hay memoria para su pila.
+#[allow(dead_code)]
+pub fn spawn(name: &str, f: impl FnOnce() + Send + 'static) -> Option<ThreadId> {
+    let (context, stack) = prepare(thread_entry, Box::new(f) as Entry)?;
+    Some(add_ready(name, context, stack, None))
+}
+
+/// Crea un hilo que entra en modo usuario en `rip`, con la pila `rsp`,
+/// dentro del espacio de direcciones `space`, que pasa a ser suyo. La pila
+/// de usuario ya tiene que estar preparada. Devuelve `None` si no hay
+/// memoria para su pila del kernel.
+pub fn spawn_user(name: &str, space: AddressSpace, rip: u64, rsp: u64) -> Option<ThreadId> {
+    let (context, stack) = prepare(user_entry, UserStart { rip, rsp })?;
+    Some(add_ready(name, context, stack, Some(space)))
+}
+
+fn add_ready(name: &str, context: Context, stack: KernelStack, space: Option<AddressSpace>) -> ThreadId {
+    let mut scheduler = SCHEDULER.lock();
+    let scheduler = scheduler.as_mut().expect("planificador sin inicializar");
+    scheduler.add(name, State::Ready, context, Some(stack), space)
+}
+
+/// Elige el siguiente hilo y cambia a él. Hay que llamarla con las
+/// interrupciones deshabilitadas, tras dejar el hilo actual en el estado
+/// que corresponda.
+fn reschedule() {
+    let switch = SCHEDULER.lock().as_mut().and_then(|scheduler| scheduler.pick_next());
+    if let Some((from, to)) = switch {
+        // Los contextos están en cajas que solo se eliminan cuando el hilo
+        // ha terminado y ya no es el actual.
+        unsafe { context::switch(from, to) };
+    }
+}
+
+/// Llamada desde la interrupción del temporizador en cada tick: contabiliza
+/// el tiempo de CPU, despierta a los hilos cuyo plazo ha vencido y expulsa al
+/// hilo actual si ha agotado su porción.
+pub fn timer_tick() {
+    let now = arch::timer_ticks();
+    let preempt = {
+        let mut scheduler = SCHEDULER.lock();
+        let Some(scheduler) = scheduler.as_mut() else { return };
+        scheduler.current().cpu_ticks += 1;
+        scheduler.wake(|state| matches!(state, State::Sleeping(deadline) if deadline <= now));
+        scheduler.slice_left = scheduler.slice_left.saturating_sub(1);
+        let idle = scheduler.current == scheduler.idle;
+        !scheduler.ready.is_empty() && (idle || scheduler.slice_left == 0)
+    };
+    if preempt {
+        reschedule();
+    }
+}
+
+/// Cede la CPU al siguiente hilo listo. Devuelve `false` sin hacer nada si
+/// no hay ninguno esperando.
+pub fn yield_now() -> bool {
+    arch::without_interrupts(|| {
+        let waiting = SCHEDULER.lock().as_ref().is_some_and(|scheduler| !scheduler.ready.is_empty());
+        if waiting {
+            reschedule();
+        }
+        waiting
+    })
+}
+
+/// Duerme el hilo actual durante al menos `ticks` ticks del temporizador.
+///
+/// Antes de que arranque el planificador, espera con la CPU detenida.
+pub fn sleep_ticks(ticks: u64) {
+    let deadline = arch::timer_ticks() + ticks;
+    let blocked = arch::without_interrupts(|| {
+        let mut guard = SCHEDULER.lock();
+        let Some(scheduler) = guard.as_mut() else { return false };
+        scheduler.current().state = State::Sleeping(deadline);
+        drop(guard);
+        reschedule();
+        true
+    });
+    if !blocked {
+        while arch::timer_ticks() < deadline {
+            arch::wait_for_interrupt();
+        }
+    }
+}
+
+/// Espera a que termine el hilo `id`.
+#[allow(dead_code)]
+pub fn join(id: ThreadId) {
+    arch::without_interrupts(|| loop {
+        let mut guard = SCHEDULER.lock();
+        let Some(scheduler) = guard.as_mut() else { return };
+        if scheduler.current == id || scheduler.thread(id).is_none_or(|thread| thread.state == State::Finished) {
+            return;
+        }
+        scheduler.current().state = State::Joining(id);
+        drop(guard);
+        reschedule();
+    });
+}
+
+/// Ejecuta `future` en el hilo actual hasta que termine. Mientras está
+/// pendiente, el hilo se bloquea hasta que su `Waker` lo despierte, que puede
+/// ser desde una interrupción.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let id = current().expect("planificador sin inicializar");
+    let waker = Waker::from(Arc::new(ThreadWaker(id)));
+    let mut context = TaskContext::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+        park();
+    }
+}
+
+/// Despierta al hilo de [`block_on`].
+struct ThreadWaker(ThreadId);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        unpark(self.0);
+    }
+}
+
+/// Bloquea el hilo actual hasta que se llame a [`unpark`] con él, salvo que
+/// ya se hiciera desde el último `park`.
+fn park() {
+    arch::without_interrupts(|| {
+        let mut guard = SCHEDULER.lock();
+        let Some(scheduler) = guard.as_mut() else { return };
+        let thread = scheduler.current();
+        if core::mem::take(&mut thread.unpark_pending) {
+            return;
+        }
+        thread.state = State::Parked;
+        drop(guard);
+        reschedule();
+    });
+}
+
+/// Pasa a listo el hilo `id` si está bloqueado en [`park`], o hace que su
+/// próximo `park` no lo bloquee. Es seguro llamarla desde una interrupción.
+fn unpark(id: ThreadId) {
+    let mut guard = SCHEDULER.lock();
+    let Some(scheduler) = guard.as_mut() else { return };
+    let Some(thread) = scheduler.thread(id) else { return };
+    match thread.state {
+        State::Parked => {
+            thread.state = State::Ready;
+            scheduler.ready.push_back(id);
+        }
+        State::Finished => {}
+        _ => thread.unpark_pending = true,
+    }
+}
+
+/// Termina el hilo actual y despierta a los que esperaban a que terminase.
+pub fn exit() -> ! {
+    arch::without_interrupts(|| {
+        let mut guard = SCHEDULER.lock();
+        let scheduler = guard.as_mut().expect("planificador sin inicializar");
+        let id = scheduler.current;
+        scheduler.current().state = State::Finished;
+        scheduler.wake(|state| state == State::Joining(id));
+        drop(guard);
+        reschedule();
+    });
+    unreachable!("un hilo terminado ha vuelto a la CPU");
+}
+
+/// Ejecuta `f` sobre el espacio de direcciones de usuario del hilo actual.
+/// Devuelve `None` si es un hilo del kernel.
+pub fn with_address_space<R>(f: impl FnOnce(&mut AddressSpace) -> R) -> Option<R> {
+    let mut scheduler = SCHEDULER.lock();
+    scheduler.as_mut()?.current().address_space.as_mut().map(f)
+}
+
+/// Indica si el hilo `id` existe y no ha terminado.
+pub fn is_alive(id: ThreadId) -> bool {
+    let mut scheduler = SCHEDULER.lock();
+    scheduler
+        .as_mut()
+        .and_then(|scheduler| scheduler.thread(id))
+        .is_some_and(|thread| thread.state != State::Finished)
+}
+
+/// Identificador del hilo actual.
+pub fn current() -> Option<ThreadId> {
+    SCHEDULER.lock().as_ref().map(|scheduler| scheduler.current)
+}
+
+/// Lista de los hilos que existen, en orden de creación.
+pub fn threads() -> Vec<ThreadInfo> {
+    let scheduler = SCHEDULER.lock();
+    let Some(scheduler) = scheduler.as_ref() else { return Vec::new() };
+    scheduler
+        .threads
+        .iter()
+        .map(|thread| ThreadInfo {
+            id: thread.id,
+            name: thread.name.clone(),
+            state: thread.state,
+            cpu_time: Duration::from_millis(thread.cpu_ticks * 1000 / arch::TIMER_HZ),
+        })
+        .collect()
+}
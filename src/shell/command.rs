@@ -1,25 +1,71 @@
 //! Define los comandos y el parser para la shell de VesperOS.
 
-use heapless::String;
+use alloc::string::String;
+use crate::log::Level;
 
 /// Representa un comando que puede ser ejecutado por la shell.
 ///
-/// Se utiliza `heapless::String` para evitar alocaciones de memoria en el heap,
-/// lo cual es crucial en un entorno de kernel.
+/// Los textos que acompañan a un comando se copian en `String`s del heap del
+/// kernel, así que no tienen una longitud máxima.
 #[derive(Debug, PartialEq)]
 pub enum Command {
     /// Limpia la pantalla.
     Clear,
     /// Imprime los argumentos de vuelta en la pantalla.
-    Echo(String<256>),
+    Echo(String),
     /// Muestra la pantalla de VesperFetch.
     VesperFetch,
     /// Muestra la pantalla de información del sistema (alias de vesperfetch).
     Info,
+    /// Muestra la información del procesador.
+    CpuInfo,
+    /// Muestra el registro del kernel, opcionalmente solo hasta un nivel dado.
+    Dmesg(Option<Level>),
+    /// Cambia la fuente de la consola al tamaño dado, en puntos.
+    Font(u8),
+    /// Mide el rendimiento del dibujo en el framebuffer.
+    FbBench,
+    /// Muestra el uso de la memoria física.
+    Free,
+    /// Muestra las estadísticas del heap del kernel.
+    Heap,
+    /// Lista los hilos del kernel.
+    Ps,
+    /// Ejecuta un programa de usuario; contiene la ruta y sus argumentos.
+    Exec(String),
+    /// Lista un directorio; contiene la ruta, vacía para el actual.
+    Ls(String),
+    /// Cambia el directorio actual; contiene la ruta, vacía para la raíz.
+    Cd(String),
+    /// Muestra el directorio actual.
+    Pwd,
+    /// Muestra el contenido de los ficheros; contiene sus rutas.
+    Cat(String),
+    /// Muestra los atributos de un fichero o directorio.
+    Stat(String),
+    /// Lista los montajes o, con un tipo y un directorio, monta un sistema
+    /// de ficheros nuevo.
+    Mount(Option<(String, String)>),
+    /// Crea ficheros vacíos; contiene sus rutas.
+    Touch(String),
+    /// Crea directorios; contiene sus rutas.
+    Mkdir(String),
+    /// Borra ficheros; contiene sus rutas.
+    Rm(String),
+    /// Borra directorios vacíos; contiene sus rutas.
+    Rmdir(String),
+    /// Mueve o renombra un fichero o directorio: origen y destino.
+    Mv(String, String),
+    /// Copia un fichero: origen y destino.
+    Cp(String, String),
+    /// Sustituye el contenido de un fichero por un texto: ruta y texto.
+    Write(String, String),
     /// Muestra información de ayuda.
     Help,
+    /// Comando reconocido con argumentos inválidos; contiene el texto de uso.
+    Usage(&'static str),
     /// Comando no reconocido.
-    Unknown(String<32>),
+    Unknown(String),
     /// Entrada vacía.
     None,
 }
@@ -43,22 +89,118 @@ pub fn parse(input: &str) -> Command {
     if command.eq_ignore_ascii_case("clear") {
         Command::Clear
     } else if command.eq_ignore_ascii_case("echo") {
-        // Creamos el string para los argumentos de forma segura.
-        let mut s = String::new();
-        // push_str puede fallar si el string es demasiado largo, pero lo ignoramos con .ok()
-        // para que simplemente se trunque, que es un comportamiento aceptable aquí.
-        let _ = s.push_str(args_str); // El `_` indica que ignoramos el Result deliberadamente.
-        Command::Echo(s)
+        Command::Echo(String::from(args_str))
     } else if command.eq_ignore_ascii_case("vesperfetch") {
         Command::VesperFetch
     } else if command.eq_ignore_ascii_case("info") {
         Command::Info
+    } else if command.eq_ignore_ascii_case("cpuinfo") {
+        Command::CpuInfo
+    } else if command.eq_ignore_ascii_case("dmesg") {
+        parse_dmesg(args_str)
+    } else if command.eq_ignore_ascii_case("font") {
+        match args_str.trim().parse() {
+            Ok(points) => Command::Font(points),
+            Err(_) => Command::Usage("font <7|9|10|12|14|18|24>"),
+        }
+    } else if command.eq_ignore_ascii_case("fbbench") {
+        Command::FbBench
+    } else if command.eq_ignore_ascii_case("free") {
+        Command::Free
+    } else if command.eq_ignore_ascii_case("heap") {
+        Command::Heap
+    } else if command.eq_ignore_ascii_case("ps") {
+        Command::Ps
+    } else if command.eq_ignore_ascii_case("exec") {
+        match args_str.trim() {
+            "" => Command::Usage("exec <programa> [argumentos...]"),
+            line => Command::Exec(String::from(line)),
+        }
+    } else if command.eq_ignore_ascii_case("ls") {
+        Command::Ls(String::from(args_str.trim()))
+    } else if command.eq_ignore_ascii_case("cd") {
+        Command::Cd(String::from(args_str.trim()))
+    } else if command.eq_ignore_ascii_case("pwd") {
+        Command::Pwd
+    } else if command.eq_ignore_ascii_case("cat") {
+        match args_str.trim() {
+            "" => Command::Usage("cat <fichero> [ficheros...]"),
+            paths => Command::Cat(String::from(paths)),
+        }
+    } else if command.eq_ignore_ascii_case("stat") {
+        match args_str.trim() {
+            "" => Command::Usage("stat <ruta>"),
+            path => Command::Stat(String::from(path)),
+        }
+    } else if command.eq_ignore_ascii_case("mount") {
+        let mut parts = args_str.split_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (None, _, _) => Command::Mount(None),
+            (Some(kind), Some(target), None) => Command::Mount(Some((String::from(kind), String::from(target)))),
+            _ => Command::Usage("mount [<tipo> <directorio>]"),
+        }
+    } else if command.eq_ignore_ascii_case("touch") {
+        match args_str.trim() {
+            "" => Command::Usage("touch <fichero> [ficheros...]"),
+            paths => Command::Touch(String::from(paths)),
+        }
+    } else if command.eq_ignore_ascii_case("mkdir") {
+        match args_str.trim() {
+            "" => Command::Usage("mkdir <directorio> [directorios...]"),
+            paths => Command::Mkdir(String::from(paths)),
+        }
+    } else if command.eq_ignore_ascii_case("rm") {
+        match args_str.trim() {
+            "" => Command::Usage("rm <fichero> [ficheros...]"),
+            paths => Command::Rm(String::from(paths)),
+        }
+    } else if command.eq_ignore_ascii_case("rmdir") {
+        match args_str.trim() {
+            "" => Command::Usage("rmdir <directorio> [directorios...]"),
+            paths => Command::Rmdir(String::from(paths)),
+        }
+    } else if command.eq_ignore_ascii_case("mv") {
+        match parse_pair(args_str) {
+            Some((from, to)) => Command::Mv(from, to),
+            None => Command::Usage("mv <origen> <destino>"),
+        }
+    } else if command.eq_ignore_ascii_case("cp") {
+        match parse_pair(args_str) {
+            Some((from, to)) => Command::Cp(from, to),
+            None => Command::Usage("cp <origen> <destino>"),
+        }
+    } else if command.eq_ignore_ascii_case("write") {
+        // El texto es el resto de la línea, con sus espacios interiores.
+        match args_str.trim().split_once(char::is_whitespace) {
+            Some((path, text)) => Command::Write(String::from(path), String::from(text.trim_start())),
+            None => Command::Usage("write <fichero> <texto>"),
+        }
     } else if command.eq_ignore_ascii_case("help") {
         Command::Help
     } else {
-        let mut s = String::new();
-        // Ignoramos el resultado, truncando el nombre del comando si es demasiado largo.
-        let _ = s.push_str(command);
-        Command::Unknown(s)
+        Command::Unknown(String::from(command))
     }
-}
\ No newline at end of file
+}
+
+/// Parsea exactamente dos argumentos.
+fn parse_pair(args: &str) -> Option<(String, String)> {
+    let mut parts = args.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(first), Some(second), None) => Some((String::from(first), String::from(second))),
+        _ => None,
+    }
+}
+
+/// Parsea los argumentos de `dmesg`: vacío o `-l <nivel>`.
+fn parse_dmesg(args: &str) -> Command {
+    const USAGE: &str = "dmesg [-l error|warn|info|debug|trace]";
+    let mut parts = args.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (None, _, _) => Command::Dmesg(None),
+        (Some("-l"), Some(level), None) => match Level::parse(level) {
+            Some(level) => Command::Dmesg(Some(level)),
+            None => Command::Usage(USAGE),
+        },
+        _ => Command::Usage(USAGE),
+    }
+}
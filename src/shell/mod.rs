@@ -7,21 +7,34 @@ pub mod command;
 
 use crate::app;
 use crate::colors;
-use crate::vga::FramebufferWriter;
+use crate::log;
+use crate::memory;
+use crate::process;
+use crate::fs::{self, file::File, mount, path, Kind};
+use crate::sched::{self, ThreadId};
+use crate::console::{self, Console};
 use core::fmt::Write;
 use self::command::{parse, Command};
-use crate::arch::target::keyboard;
-use heapless::String;
+use crate::arch::target::keyboard::{self, KeyStream};
+use pc_keyboard::{DecodedKey, KeyCode};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
 
 const PROMPT: &str = "vesper> ";
-const MAX_BUFFER_SIZE: usize = 256;
 
 /// Representa el estado de la shell.
 pub struct Shell {
     /// Búfer que almacena la línea de comando actual que el usuario está escribiendo.
-    buffer: String<MAX_BUFFER_SIZE>,
+    buffer: String,
+    /// Teclas pulsadas, que la shell espera de forma asíncrona.
+    keys: KeyStream,
+    /// Proceso lanzado con `exec` al que hay que esperar antes del prompt.
+    foreground: Option<ThreadId>,
+    /// Ruta canónica del directorio actual.
+    cwd: String,
     // Historial de comandos para uso futuro (ej. flechas arriba/abajo).
-    // history: heapless::Vec<String<MAX_BUFFER_SIZE>, 16>,
+    // history: alloc::vec::Vec<String>,
 }
 
 impl Shell {
@@ -29,17 +42,54 @@ impl Shell {
     pub fn new() -> Self {
         Self {
             buffer: String::new(),
-            // history: heapless::Vec::new(),
+            keys: KeyStream::new(),
+            foreground: None,
+            cwd: String::from("/"),
+            // history: alloc::vec::Vec::new(),
+        }
+    }
+
+    /// Tarea de la shell: dibuja el prompt y atiende el teclado para siempre.
+    pub async fn run(mut self) {
+        if let Some(mut console) = console::lock() {
+            console.clear(colors::BACKGROUND_COLOR);
+            self.draw_prompt(&mut console);
+        }
+        loop {
+            let key = self.keys.next().await;
+            let Some(mut console) = console::lock() else { continue };
+            match key {
+                // Pasa el carácter a la shell para que lo procese.
+                DecodedKey::Unicode(character) => self.handle_input_char(character, &mut console).await,
+                // Shift+RePág / Shift+AvPág recorren el historial de la consola.
+                DecodedKey::RawKey(KeyCode::PageUp) if keyboard::shift_pressed() => {
+                    let page = console.page_rows();
+                    console.scroll_view_up(page);
+                }
+                DecodedKey::RawKey(KeyCode::PageDown) if keyboard::shift_pressed() => {
+                    let page = console.page_rows();
+                    console.scroll_view_down(page);
+                }
+                _ => {}
+            }
+            // Mientras se ejecuta el proceso, la consola y el teclado son suyos.
+            drop(console);
+            if let Some(id) = self.foreground.take() {
+                process::wait(id).await;
+                if let Some(mut console) = console::lock() {
+                    self.draw_prompt(&mut console);
+                }
+            }
         }
     }
 
     /// Dibuja el prompt de la shell en la posición actual del cursor.
-    pub fn draw_prompt(&self, writer: &mut FramebufferWriter) {
-        writer.set_color(colors::NEON_GREEN);
+    pub fn draw_prompt(&self, console: &mut Console) {
+        console.set_color(colors::NEON_GREEN);
         // El resultado se ignora porque la escritura en el framebuffer no debería fallar.
-        let _ = write!(writer, "{}", PROMPT);
-        writer.set_color(colors::TEXT_PRIMARY);
-        let _ = write!(writer, "{}", self.buffer);
+        let _ = write!(console, "{}", PROMPT);
+        console.set_color(colors::TEXT_PRIMARY);
+        let _ = write!(console, "{}", self.buffer);
     }
 
     /// Procesa un carácter de entrada recibido desde el teclado.
@@ -47,70 +97,272 @@ impl Shell {
     /// # Arguments
     ///
     /// * `c`: El carácter Unicode recibido.
-    /// * `writer`: Una referencia mutable al `FramebufferWriter` para dibujar en la pantalla.
-    pub fn handle_input_char(&mut self, c: char, writer: &mut FramebufferWriter) {
+    /// * `console`: La consola en la que se muestra la entrada y la salida.
+    async fn handle_input_char(&mut self, c: char, console: &mut Console) {
         match c {
             '\n' => { // Tecla Enter
-                let _ = writer.write_char('\n');
-                self.run_command(writer);
+                let _ = console.write_char('\n');
+                self.run_command(console).await;
                 self.buffer.clear();
-                self.draw_prompt(writer);
+                if self.foreground.is_none() {
+                    self.draw_prompt(console);
+                }
             }
             '\x08' => { // Backspace
                 if self.buffer.pop().is_some() {
-                    writer.backspace();
+                    console.backspace();
                 }
             }
             // Caracteres imprimibles
             c if c.is_ascii_graphic() || c == ' ' => {
-                if self.buffer.push(c).is_ok() {
-                    let _ = writer.write_char(c);
-                }
+                self.buffer.push(c);
+                let _ = console.write_char(c);
             }
             _ => {}
         }
     }
 
     /// Ejecuta el comando que está actualmente en el búfer.
-    fn run_command(&mut self, writer: &mut FramebufferWriter) {
+    async fn run_command(&mut self, console: &mut Console) {
         let command = parse(self.buffer.as_str());
         match command {
             Command::Clear => {
-                writer.clear(colors::BACKGROUND_COLOR);
+                console.clear(colors::BACKGROUND_COLOR);
             },
             Command::Echo(args) => {
                 // El unwrap es seguro aquí porque la escritura en el framebuffer no debería fallar.
-                writeln!(writer, "{}", args).unwrap();
+                writeln!(console, "{}", args).unwrap();
             },
             Command::VesperFetch | Command::Info => {
-                // Ejecuta la aplicación VesperFetch.
-                app::vesperfetch_app::run(writer);
-
-                // Pausa hasta que el usuario presione una tecla.
-                writer.set_cursor_position(20, writer.height() - 40);
-                writer.set_color(colors::TEXT_SECONDARY);
-                let _ = write!(writer, "[Presiona cualquier tecla para continuar]");
-
-                // Espera a que se presione y suelte una tecla.
-                while keyboard::poll_key().is_some() {} // Drena eventos viejos si los hay.
-                while keyboard::poll_key().is_none() {
-                    crate::arch::wait_for_interrupt();
+                // VesperFetch ocupa toda la pantalla; al volver, la consola se repinta.
+                let keys = &mut self.keys;
+                console.fullscreen_async(async |writer| app::vesperfetch_app::run(writer, keys).await).await;
+            },
+            Command::CpuInfo => {
+                app::cpuinfo_app::run(console);
+            },
+            Command::Dmesg(max_level) => {
+                for sequence in log::sequence_range() {
+                    if let Some(record) = log::record(sequence)
+                        && max_level.is_none_or(|max| record.level <= max)
+                    {
+                        log::print_record(console, &record);
+                    }
+                }
+            },
+            Command::Font(points) => match console::font(points) {
+                Some(font) => {
+                    console.set_font(font);
+                    writeln!(console, "Fuente ProFont de {} puntos: {}x{} caracteres.", points, console.columns(), console.rows()).unwrap();
+                }
+                None => writeln!(console, "Tamaños disponibles: 7, 9, 10, 12, 14, 18, 24.").unwrap(),
+            },
+            Command::FbBench => {
+                app::fbbench_app::run(console);
+            },
+            Command::Free => match memory::frame_stats() {
+                Some(stats) => {
+                    writeln!(console, "         {:>12} {:>12} {:>12}", "total", "usada", "libre").unwrap();
+                    writeln!(
+                        console,
+                        "Memoria: {:>8} KiB {:>8} KiB {:>8} KiB",
+                        stats.total_bytes() / 1024,
+                        stats.used_bytes() / 1024,
+                        stats.free_bytes() / 1024
+                    )
+                    .unwrap();
+                    writeln!(console, "Marcos:  {:>12} {:>12} {:>12}", stats.total, stats.used(), stats.free).unwrap();
+                }
+                None => writeln!(console, "El asignador de memoria no está inicializado.").unwrap(),
+            },
+            Command::Heap => {
+                let stats = memory::heap::stats();
+                writeln!(console, "Heap del kernel: {}", stats).unwrap();
+                writeln!(console, "  tamaño:           {:>8} KiB ({} ampliaciones)", stats.size / 1024, stats.grows).unwrap();
+                writeln!(console, "  en uso:           {:>8} KiB", stats.used / 1024).unwrap();
+                writeln!(console, "  libre:            {:>8} KiB en {} bloques", stats.free() / 1024, stats.free_blocks).unwrap();
+                writeln!(console, "  mayor bloque:     {:>8} KiB", stats.largest_free / 1024).unwrap();
+                writeln!(console, "  asignaciones:     {:>8} ({} fallidas)", stats.allocations, stats.failures).unwrap();
+            },
+            Command::Ps => {
+                writeln!(console, "{:>4}  {:<16} {:<16} {:>10}", "ID", "NOMBRE", "ESTADO", "CPU").unwrap();
+                for thread in sched::threads() {
+                    let state = format!("{}", thread.state);
+                    let cpu_time = thread.cpu_time.as_millis();
+                    writeln!(console, "{:>4}  {:<16} {:<16} {:>7} ms", thread.id, thread.name, state, cpu_time).unwrap();
+                }
+            },
+            Command::Exec(line) => {
+                let args: Vec<&str> = line.split_whitespace().collect();
+                match File::open(&self.cwd, args[0]).and_then(|mut file| file.read_to_end()) {
+                    Ok(image) => match process::exec(&image, &args, &self.cwd) {
+                        Ok(id) => self.foreground = Some(id),
+                        Err(error) => writeln!(console, "exec: {}: {}", args[0], error).unwrap(),
+                    },
+                    Err(error) => writeln!(console, "exec: {}: {}", args[0], error).unwrap(),
+                }
+            },
+            Command::Ls(target) => match path::resolve(&self.cwd, &target) {
+                Ok(resolved) => match resolved.inode.read_dir() {
+                    Ok(entries) => {
+                        for entry in entries {
+                            if entry.kind == Kind::Directory {
+                                console.set_color(colors::TEXT_SECONDARY);
+                                writeln!(console, "{}/", entry.name).unwrap();
+                                console.set_color(colors::TEXT_PRIMARY);
+                            } else {
+                                writeln!(console, "{}", entry.name).unwrap();
+                            }
+                        }
+                    }
+                    // Un fichero se lista a sí mismo.
+                    Err(fs::Error::NotDirectory) => writeln!(console, "{}", target).unwrap(),
+                    Err(error) => writeln!(console, "ls: {}: {}", target, error).unwrap(),
+                },
+                Err(error) => writeln!(console, "ls: {}: {}", target, error).unwrap(),
+            },
+            Command::Cd(target) => {
+                let target = if target.is_empty() { "/" } else { target.as_str() };
+                match path::resolve(&self.cwd, target) {
+                    Ok(resolved) if resolved.inode.metadata().kind == Kind::Directory => self.cwd = resolved.path,
+                    Ok(_) => writeln!(console, "cd: {}: {}", target, fs::Error::NotDirectory).unwrap(),
+                    Err(error) => writeln!(console, "cd: {}: {}", target, error).unwrap(),
+                }
+            },
+            Command::Pwd => {
+                writeln!(console, "{}", self.cwd).unwrap();
+            },
+            Command::Cat(paths) => {
+                for target in paths.split_whitespace() {
+                    match File::open(&self.cwd, target).and_then(|mut file| file.read_to_end()) {
+                        Ok(bytes) => write!(console, "{}", String::from_utf8_lossy(&bytes)).unwrap(),
+                        Err(error) => writeln!(console, "cat: {}: {}", target, error).unwrap(),
+                    }
+                }
+            },
+            Command::Stat(target) => match path::resolve(&self.cwd, &target) {
+                Ok(resolved) => {
+                    let metadata = resolved.inode.metadata();
+                    writeln!(console, "  Ruta:        {}", resolved.path).unwrap();
+                    writeln!(console, "  Tipo:        {}", metadata.kind).unwrap();
+                    match metadata.kind {
+                        Kind::File => writeln!(console, "  Tamaño:      {} bytes", metadata.size).unwrap(),
+                        Kind::Directory => writeln!(console, "  Entradas:    {}", metadata.size).unwrap(),
+                    }
+                    writeln!(console, "  Inodo:       {}", metadata.inode).unwrap();
+                    writeln!(console, "  Permisos:    {:04o} ({})", metadata.mode & 0o7777, permissions(metadata.mode)).unwrap();
+                    writeln!(console, "  Modificado:  {}", metadata.mtime).unwrap();
+                }
+                Err(error) => writeln!(console, "stat: {}: {}", target, error).unwrap(),
+            },
+            Command::Mount(None) => {
+                for mount in mount::mounts() {
+                    let access = if mount.fs.read_only() { "ro" } else { "rw" };
+                    writeln!(console, "{} en {} ({})", mount.fs.name(), mount.path, access).unwrap();
+                }
+            },
+            Command::Mount(Some((kind, target))) => {
+                // La tabla de montajes solo resuelve rutas absolutas.
+                let target = if target.starts_with('/') { target } else { path::join(&self.cwd, &target) };
+                match fs::create(&kind).and_then(|fs| mount::mount(&target, fs)) {
+                    Ok(path) => writeln!(console, "{} montado en {}", kind, path).unwrap(),
+                    Err(error) => writeln!(console, "mount: {}: {}", target, error).unwrap(),
+                }
+            },
+            Command::Touch(paths) => {
+                for_each_path(console, "touch", &paths, |target| fs::touch(&self.cwd, target));
+            },
+            Command::Mkdir(paths) => {
+                for_each_path(console, "mkdir", &paths, |target| fs::mkdir(&self.cwd, target));
+            },
+            Command::Rm(paths) => {
+                for_each_path(console, "rm", &paths, |target| fs::unlink(&self.cwd, target));
+            },
+            Command::Rmdir(paths) => {
+                for_each_path(console, "rmdir", &paths, |target| fs::rmdir(&self.cwd, target));
+            },
+            Command::Mv(from, to) => {
+                let to = destination(&self.cwd, &from, &to);
+                if let Err(error) = fs::rename(&self.cwd, &from, &to) {
+                    writeln!(console, "mv: {} -> {}: {}", from, to, error).unwrap();
+                }
+            },
+            Command::Cp(from, to) => {
+                let to = destination(&self.cwd, &from, &to);
+                let copied = File::open(&self.cwd, &from)
+                    .and_then(|mut source| source.read_to_end())
+                    .and_then(|bytes| File::create(&self.cwd, &to)?.write(&bytes));
+                if let Err(error) = copied {
+                    writeln!(console, "cp: {} -> {}: {}", from, to, error).unwrap();
+                }
+            },
+            Command::Write(target, text) => {
+                let written = File::create(&self.cwd, &target).and_then(|mut file| file.write(format!("{}\n", text).as_bytes()));
+                if let Err(error) = written {
+                    writeln!(console, "write: {}: {}", target, error).unwrap();
                 }
-
-                // Limpia la pantalla y vuelve a la shell.
-                writer.clear(colors::BACKGROUND_COLOR);
             },
             Command::Help => {
-                writeln!(writer, "Comandos de VesperOS:").unwrap();
-                writeln!(writer, "  help         - Muestra esta ayuda.").unwrap();
-                writeln!(writer, "  clear        - Limpia la pantalla.").unwrap();
-                writeln!(writer, "  echo [msg]   - Imprime un mensaje.").unwrap();
-                writeln!(writer, "  info         - Muestra la información del sistema.").unwrap();
+                writeln!(console, "Comandos de VesperOS:").unwrap();
+                writeln!(console, "  help         - Muestra esta ayuda.").unwrap();
+                writeln!(console, "  clear        - Limpia la pantalla.").unwrap();
+                writeln!(console, "  echo [msg]   - Imprime un mensaje.").unwrap();
+                writeln!(console, "  info         - Muestra la información del sistema.").unwrap();
+                writeln!(console, "  cpuinfo      - Muestra la información del procesador.").unwrap();
+                writeln!(console, "  dmesg [-l n] - Muestra el registro del kernel hasta el nivel n.").unwrap();
+                writeln!(console, "  font <pt>    - Cambia el tamaño de la fuente de la consola.").unwrap();
+                writeln!(console, "  fbbench      - Mide el rendimiento del dibujo en pantalla.").unwrap();
+                writeln!(console, "  free         - Muestra el uso de la memoria física.").unwrap();
+                writeln!(console, "  heap         - Muestra las estadísticas del heap del kernel.").unwrap();
+                writeln!(console, "  ps           - Lista los hilos del kernel.").unwrap();
+                writeln!(console, "  exec <prog>  - Ejecuta un programa de usuario (ej. /bin/hello).").unwrap();
+                writeln!(console, "  ls [ruta]    - Lista el contenido de un directorio.").unwrap();
+                writeln!(console, "  cd [ruta]    - Cambia el directorio actual.").unwrap();
+                writeln!(console, "  pwd          - Muestra el directorio actual.").unwrap();
+                writeln!(console, "  cat <fich>   - Muestra el contenido de ficheros.").unwrap();
+                writeln!(console, "  stat <ruta>  - Muestra los atributos de un fichero o directorio.").unwrap();
+                writeln!(console, "  mount        - Lista los montajes; mount <tipo> <dir> monta uno.").unwrap();
+                writeln!(console, "  touch <fich> - Crea ficheros vacíos.").unwrap();
+                writeln!(console, "  mkdir <dir>  - Crea directorios.").unwrap();
+                writeln!(console, "  rm <fich>    - Borra ficheros.").unwrap();
+                writeln!(console, "  rmdir <dir>  - Borra directorios vacíos.").unwrap();
+                writeln!(console, "  mv <a> <b>   - Mueve o renombra un fichero o directorio.").unwrap();
+                writeln!(console, "  cp <a> <b>   - Copia un fichero.").unwrap();
+                writeln!(console, "  write <f> <t> - Escribe el texto <t> en el fichero <f> (ej. en /tmp).").unwrap();
+            },
+            Command::Usage(usage) => {
+                writeln!(console, "Uso: {}", usage).unwrap();
             },
             Command::Unknown(cmd) => {
-                writeln!(writer, "Comando no encontrado: {}", cmd).unwrap();
+                writeln!(console, "Comando no encontrado: {}", cmd).unwrap();
             },
             Command::None => {}
         }
     }
+}
+
+/// Permisos de `mode` al estilo de `ls -l`, como `rwxr-xr-x`.
+fn permissions(mode: u32) -> String {
+    (0..9).rev().map(|bit| if mode & (1 << bit) == 0 { '-' } else { b"xwr"[bit % 3] as char }).collect()
+}
+
+/// Aplica `operation` a cada ruta de `paths` e informa de los errores.
+fn for_each_path(console: &mut Console, command: &str, paths: &str, operation: impl Fn(&str) -> Result<(), fs::Error>) {
+    for target in paths.split_whitespace() {
+        if let Err(error) = operation(target) {
+            writeln!(console, "{}: {}: {}", command, target, error).unwrap();
+        }
+    }
+}
+
+/// Destino de `mv` y `cp`: si `to` es un directorio, la entrada de `from`
+/// dentro de él.
+fn destination(cwd: &str, from: &str, to: &str) -> String {
+    match path::resolve(cwd, to) {
+        Ok(resolved) if resolved.inode.metadata().kind == Kind::Directory => {
+            let name = from.trim_end_matches('/').rsplit('/').next().unwrap_or(from);
+            path::join(&resolved.path, name)
+        }
+        _ => String::from(to),
+    }
 }
\ No newline at end of file
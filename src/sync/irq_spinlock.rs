@@ -0,0 +1,105 @@
+//! Spinlock que deshabilita las interrupciones mientras está tomado.
+//!
+//! Con un `spin::Mutex` normal, si una interrupción llega mientras el código
+//! normal tiene el lock y su manejador intenta tomarlo, la CPU gira para
+//! siempre. `IrqSpinlock` guarda el estado de las interrupciones, las
+//! deshabilita antes de tomar el lock y lo restaura al soltarlo; además, al
+//! estar deshabilitadas, el planificador no puede expulsar al hilo que lo
+//! tiene.
+
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+
+use spin::{Mutex, MutexGuard};
+
+use super::lock_order::{self, LockClass};
+use crate::arch::target::cpu;
+
+/// Spinlock seguro frente a interrupciones, con su clase para el
+/// comprobador de orden de locks.
+pub struct IrqSpinlock<T> {
+    class: &'static LockClass,
+    inner: Mutex<T>,
+}
+
+impl<T> IrqSpinlock<T> {
+    pub const fn new(class: &'static LockClass, value: T) -> Self {
+        Self { class, inner: Mutex::new(value) }
+    }
+
+    /// Deshabilita las interrupciones y toma el lock, girando hasta que esté libre.
+    pub fn lock(&self) -> IrqSpinlockGuard<'_, T> {
+        let interrupts = disable();
+        lock_order::acquire(self.class);
+        let guard = self.inner.lock();
+        lock_order::acquired(self.class);
+        IrqSpinlockGuard { class: self.class, guard: ManuallyDrop::new(guard), interrupts }
+    }
+
+    /// Toma el lock si está libre, sin esperar.
+    #[allow(dead_code)]
+    pub fn try_lock(&self) -> Option<IrqSpinlockGuard<'_, T>> {
+        let interrupts = disable();
+        match self.inner.try_lock() {
+            Some(guard) => {
+                lock_order::acquired(self.class);
+                Some(IrqSpinlockGuard { class: self.class, guard: ManuallyDrop::new(guard), interrupts })
+            }
+            None => {
+                restore(interrupts);
+                None
+            }
+        }
+    }
+}
+
+/// Deshabilita las interrupciones y devuelve si estaban habilitadas.
+fn disable() -> bool {
+    let enabled = cpu::interrupts_enabled();
+    if enabled {
+        cpu::disable_interrupts();
+    }
+    enabled
+}
+
+/// Vuelve a habilitar las interrupciones si lo estaban antes de [`disable`].
+fn restore(enabled: bool) {
+    if enabled {
+        // Se restaura el estado que había al tomar el lock.
+        unsafe { cpu::enable_interrupts() };
+    }
+}
+
+/// Acceso al dato protegido por un [`IrqSpinlock`].
+///
+/// Al soltarlo se libera el lock y se restaura el estado de las
+/// interrupciones. Si se toman varios locks, hay que soltarlos en orden
+/// inverso: el primero en soltarse volvería a habilitarlas.
+pub struct IrqSpinlockGuard<'a, T> {
+    class: &'static LockClass,
+    guard: ManuallyDrop<MutexGuard<'a, T>>,
+    interrupts: bool,
+}
+
+impl<T> Deref for IrqSpinlockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for IrqSpinlockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for IrqSpinlockGuard<'_, T> {
+    fn drop(&mut self) {
+        // El lock se suelta antes de que pueda llegar ninguna interrupción.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        lock_order::release(self.class);
+        restore(self.interrupts);
+    }
+}
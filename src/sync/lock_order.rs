@@ -0,0 +1,135 @@
+//! Comprobador del orden de los locks.
+//!
+//! Cada [`IrqSpinlock`](super::IrqSpinlock) pertenece a una clase con un
+//! rango, y un lock solo puede tomarse mientras se tienen locks de rango
+//! estrictamente menor. Si todo el kernel respeta este orden, dos caminos de
+//! código nunca pueden esperarse mutuamente. En las compilaciones de
+//! depuración, tomar un lock fuera de orden provoca un pánico que nombra
+//! ambos locks, aunque en esa ejecución no se haya llegado a bloquear nada;
+//! en las de publicación, la comprobación desaparece.
+//!
+//! Los locks se registran por CPU: mientras se tiene un `IrqSpinlock` las
+//! interrupciones están deshabilitadas, así que ningún otro hilo ni
+//! manejador puede ejecutarse entre tomarlo y soltarlo.
+
+/// Clase de un lock: todos los locks de una clase ocupan la misma posición
+/// en el orden.
+#[derive(Debug)]
+pub struct LockClass {
+    pub name: &'static str,
+    pub rank: u8,
+}
+
+// --- Orden global de los locks, de exterior a interior ---
+
+/// Tabla de procesos; al crear uno se toma el planificador.
+pub static PROCESSES: LockClass = LockClass { name: "procesos", rank: 5 };
+/// Estado del planificador; al eliminar hilos suelta sus pilas.
+pub static SCHEDULER: LockClass = LockClass { name: "planificador", rank: 10 };
+/// Plazos de las tareas dormidas; al vencer despierta tareas.
+pub static TIMERS: LockClass = LockClass { name: "temporizadores", rank: 20 };
+/// `Waker` que espera un evento.
+pub static WAKER: LockClass = LockClass { name: "waker", rank: 30 };
+/// Cola de tareas despertadas del ejecutor.
+pub static EXECUTOR: LockClass = LockClass { name: "ejecutor", rank: 40 };
+/// Decodificador del teclado.
+pub static KEYBOARD: LockClass = LockClass { name: "teclado", rank: 50 };
+/// Ranuras de las pilas del kernel.
+pub static STACKS: LockClass = LockClass { name: "pilas", rank: 60 };
+/// Tablas de páginas del kernel; reservan marcos para tablas nuevas.
+pub static PAGING: LockClass = LockClass { name: "paginación", rank: 70 };
+/// Heap del kernel; crece con marcos nuevos.
+pub static HEAP: LockClass = LockClass { name: "heap", rank: 80 };
+/// Asignador de marcos físicos.
+pub static FRAMES: LockClass = LockClass { name: "marcos", rank: 90 };
+/// Búfer del registro del kernel.
+pub static LOG: LockClass = LockClass { name: "registro", rank: 100 };
+/// Texto pendiente de la consola.
+pub static CONSOLE_PENDING: LockClass = LockClass { name: "consola pendiente", rank: 110 };
+/// Puertos serie.
+pub static UART: LockClass = LockClass { name: "uart", rank: 120 };
+/// Controlador de interrupciones.
+pub static PICS: LockClass = LockClass { name: "pic", rank: 130 };
+
+#[cfg(debug_assertions)]
+mod checker {
+    use core::cell::UnsafeCell;
+
+    use super::LockClass;
+
+    /// Máximo de locks tomados a la vez que se registran.
+    const MAX_HELD: usize = 16;
+
+    struct Held {
+        locks: [Option<&'static LockClass>; MAX_HELD],
+        depth: usize,
+    }
+
+    struct HeldCell(UnsafeCell<Held>);
+
+    // Solo se toca con las interrupciones deshabilitadas, en la única CPU.
+    unsafe impl Sync for HeldCell {}
+
+    static HELD: HeldCell = HeldCell(UnsafeCell::new(Held { locks: [None; MAX_HELD], depth: 0 }));
+
+    fn held() -> &'static mut Held {
+        unsafe { &mut *HELD.0.get() }
+    }
+
+    pub fn acquire(class: &'static LockClass) {
+        let held = held();
+        if let Some(outer) = held.locks[..held.depth].iter().flatten().find(|outer| outer.rank >= class.rank) {
+            // El manejador de pánicos tomará otros locks: se olvidan los
+            // registrados para no volver a fallar dentro de él.
+            held.depth = 0;
+            panic!(
+                "orden de locks invertido: se toma «{}» (rango {}) con «{}» (rango {}) tomado",
+                class.name, class.rank, outer.name, outer.rank
+            );
+        }
+    }
+
+    pub fn acquired(class: &'static LockClass) {
+        let held = held();
+        if held.depth < MAX_HELD {
+            held.locks[held.depth] = Some(class);
+        }
+        held.depth += 1;
+    }
+
+    pub fn release(class: &'static LockClass) {
+        let held = held();
+        let depth = held.depth.min(MAX_HELD);
+        // Normalmente es el último, pero se admite soltar en otro orden.
+        if let Some(index) = held.locks[..depth].iter().rposition(|lock| lock.is_some_and(|lock| core::ptr::eq(lock, class))) {
+            held.locks.copy_within(index + 1..depth, index);
+            held.locks[depth - 1] = None;
+        }
+        held.depth = held.depth.saturating_sub(1);
+    }
+}
+
+#[cfg(not(debug_assertions))]
+mod checker {
+    use super::LockClass;
+
+    pub fn acquire(_: &'static LockClass) {}
+    pub fn acquired(_: &'static LockClass) {}
+    pub fn release(_: &'static LockClass) {}
+}
+
+/// Comprueba, antes de esperar por un lock de la clase `class`, que tomarlo
+/// respeta el orden. Se llama con las interrupciones deshabilitadas.
+pub(super) fn acquire(class: &'static LockClass) {
+    checker::acquire(class);
+}
+
+/// Registra que se ha tomado un lock de la clase `class`.
+pub(super) fn acquired(class: &'static LockClass) {
+    checker::acquired(class);
+}
+
+/// Registra que se ha soltado un lock de la clase `class`.
+pub(super) fn release(class: &'static LockClass) {
+    checker::release(class);
+}
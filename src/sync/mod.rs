@@ -0,0 +1,16 @@
+//! Primitivas de sincronización del kernel.
+//!
+//! - [`IrqSpinlock`]: spinlock que deshabilita las interrupciones mientras
+//!   está tomado, para los datos que comparten el código normal y los
+//!   manejadores de interrupción.
+//! - [`SpscRing`]: cola circular sin locks para un productor y un consumidor,
+//!   como un manejador de interrupción y la tarea que consume sus datos.
+//! - [`lock_order`]: comprobación, en las compilaciones de depuración, de que
+//!   los locks se toman siempre en el mismo orden.
+
+pub mod lock_order;
+mod irq_spinlock;
+mod spsc;
+
+pub use self::irq_spinlock::IrqSpinlock;
+pub use self::spsc::SpscRing;
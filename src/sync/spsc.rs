@@ -0,0 +1,76 @@
+//! Cola circular sin locks de un productor y un consumidor.
+//!
+//! El productor solo escribe `tail` y el consumidor solo escribe `head`,
+//! así que ninguno tiene que esperar al otro: el manejador de interrupción
+//! que produce nunca puede bloquearse por el código que consume. Cuando la
+//! cola está llena, el elemento nuevo se descarta y se cuenta.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Cola de `N` elementos; `N` tiene que ser potencia de dos.
+pub struct SpscRing<T: Copy, const N: usize> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    /// Siguiente posición a leer; solo la avanza el consumidor.
+    head: AtomicUsize,
+    /// Siguiente posición a escribir; solo la avanza el productor.
+    tail: AtomicUsize,
+    /// Elementos descartados por tener la cola llena.
+    overflows: AtomicU64,
+}
+
+// Cada casilla la escribe solo el productor antes de publicarla y la lee
+// solo el consumidor después de verla publicada.
+unsafe impl<T: Copy + Send, const N: usize> Sync for SpscRing<T, N> {}
+
+impl<T: Copy, const N: usize> SpscRing<T, N> {
+    pub const fn new() -> Self {
+        assert!(N.is_power_of_two(), "el tamaño de la cola tiene que ser potencia de dos");
+        Self {
+            slots: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            overflows: AtomicU64::new(0),
+        }
+    }
+
+    /// Añade `value` al final de la cola. Devuelve `false`, y cuenta el
+    /// desbordamiento, si está llena.
+    ///
+    /// # Safety
+    ///
+    /// Solo puede haber un productor: dos llamadas a `push` no pueden
+    /// ejecutarse a la vez.
+    pub unsafe fn push(&self, value: T) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(self.head.load(Ordering::Acquire)) == N {
+            self.overflows.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        unsafe { (*self.slots[tail % N].get()).write(value) };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Saca el primer elemento de la cola, si lo hay.
+    ///
+    /// # Safety
+    ///
+    /// Solo puede haber un consumidor: dos llamadas a `pop` no pueden
+    /// ejecutarse a la vez.
+    pub unsafe fn pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let value = unsafe { (*self.slots[head % N].get()).assume_init() };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// Elementos descartados desde el principio por tener la cola llena.
+    pub fn overflows(&self) -> u64 {
+        self.overflows.load(Ordering::Relaxed)
+    }
+}
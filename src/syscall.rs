@@ -0,0 +1,362 @@
+//! Llamadas al sistema.
+//!
+//! El código de la arquitectura recoge el número de la llamada y sus seis
+//! argumentos y llama a [`dispatch`], que busca el manejador en una tabla
+//! indexada por número. El resultado es un entero con signo: los valores
+//! negativos son códigos de error ([`Error`]), como en Linux.
+//!
+//! Ningún puntero que venga de un programa se usa sin comprobar antes que
+//! todo el rango está en la mitad de usuario y mapeado con permisos de
+//! usuario en el espacio del proceso.
+
+use core::fmt;
+use core::str;
+
+use crate::arch::target::keyboard::{self, KeyStream};
+use crate::arch::target::paging::{Flags, MapError, PAGE_SIZE};
+use crate::fs::{self, file::File, file::SeekFrom};
+use crate::log;
+use crate::process::{self, USER_END};
+use crate::sched;
+use crate::time;
+use pc_keyboard::DecodedKey;
+
+/// `write(fd, buffer, len)`: escribe en la consola (1 y 2) o en un fichero
+/// abierto.
+pub const WRITE: u64 = 0;
+/// `read(fd, buffer, len)`: lee del teclado (0), donde espera al menos a
+/// una tecla, o de un fichero abierto.
+pub const READ: u64 = 1;
+/// `exit(code)`: termina el proceso.
+pub const EXIT: u64 = 2;
+/// `sleep(ms)`: duerme al menos los milisegundos indicados.
+pub const SLEEP: u64 = 3;
+/// `map(address, len, prot)`: mapea memoria nueva y a cero en el proceso.
+pub const MAP: u64 = 4;
+/// `open(path, len)`: abre un fichero y devuelve su descriptor. La ruta no
+/// lleva nulo final y es relativa al directorio del proceso.
+pub const OPEN: u64 = 5;
+/// `close(fd)`: cierra un fichero abierto.
+pub const CLOSE: u64 = 6;
+/// `seek(fd, offset, whence)`: cambia la posición de un fichero abierto y
+/// devuelve la nueva.
+pub const SEEK: u64 = 7;
+
+/// Bit de `prot` en [`MAP`]: la memoria se puede escribir.
+pub const PROT_WRITE: u64 = 1 << 0;
+/// Bit de `prot` en [`MAP`]: la memoria se puede ejecutar.
+pub const PROT_EXEC: u64 = 1 << 1;
+
+/// Valores de `whence` en [`SEEK`]: desde el principio, desde la posición
+/// actual o desde el final.
+pub const SEEK_SET: u64 = 0;
+pub const SEEK_CUR: u64 = 1;
+pub const SEEK_END: u64 = 2;
+
+/// Mayor espera que acepta [`SLEEP`], para que el cálculo de ticks no se desborde.
+const MAX_SLEEP_MS: u64 = u32::MAX as u64;
+/// Mayor escritura en la consola por llamada: se pinta sin interrupciones,
+/// y un búfer mayor se escribe en varias llamadas.
+const MAX_CONSOLE_WRITE: usize = 4096;
+
+/// Errores de las llamadas al sistema, con los códigos de Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// No existe el fichero (`ENOENT`).
+    NotFound,
+    /// Descriptor de fichero inválido (`EBADF`).
+    BadDescriptor,
+    /// No hay memoria (`ENOMEM`).
+    OutOfMemory,
+    /// Puntero fuera de la memoria del proceso (`EFAULT`).
+    BadAddress,
+    /// Un componente de la ruta no es un directorio (`ENOTDIR`).
+    NotDirectory,
+    /// Es un directorio (`EISDIR`).
+    IsDirectory,
+    /// Argumento inválido (`EINVAL`).
+    InvalidArgument,
+    /// Demasiados ficheros abiertos (`EMFILE`).
+    TooManyFiles,
+    /// Sistema de ficheros de solo lectura (`EROFS`).
+    ReadOnly,
+    /// No existe la llamada (`ENOSYS`).
+    NoSyscall,
+}
+
+impl Error {
+    /// Código de error, ya negativo, que recibe el programa.
+    pub fn code(self) -> i64 {
+        -match self {
+            Error::NotFound => 2,
+            Error::BadDescriptor => 9,
+            Error::OutOfMemory => 12,
+            Error::BadAddress => 14,
+            Error::NotDirectory => 20,
+            Error::IsDirectory => 21,
+            Error::InvalidArgument => 22,
+            Error::TooManyFiles => 24,
+            Error::ReadOnly => 30,
+            Error::NoSyscall => 38,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::NotFound => "no existe el fichero",
+            Error::BadDescriptor => "descriptor inválido",
+            Error::OutOfMemory => "no hay memoria",
+            Error::BadAddress => "dirección inválida",
+            Error::NotDirectory => "no es un directorio",
+            Error::IsDirectory => "es un directorio",
+            Error::InvalidArgument => "argumento inválido",
+            Error::TooManyFiles => "demasiados ficheros abiertos",
+            Error::ReadOnly => "sistema de ficheros de solo lectura",
+            Error::NoSyscall => "llamada inexistente",
+        })
+    }
+}
+
+impl From<MapError> for Error {
+    fn from(error: MapError) -> Error {
+        match error {
+            MapError::OutOfMemory => Error::OutOfMemory,
+            _ => Error::InvalidArgument,
+        }
+    }
+}
+
+impl From<fs::Error> for Error {
+    fn from(error: fs::Error) -> Error {
+        match error {
+            fs::Error::NotFound => Error::NotFound,
+            fs::Error::NotDirectory => Error::NotDirectory,
+            fs::Error::IsDirectory => Error::IsDirectory,
+            fs::Error::BadDescriptor => Error::BadDescriptor,
+            fs::Error::TooManyFiles => Error::TooManyFiles,
+            fs::Error::ReadOnly => Error::ReadOnly,
+            fs::Error::NoSpace => Error::OutOfMemory,
+            fs::Error::InvalidArgument
+            | fs::Error::Busy
+            | fs::Error::UnknownFileSystem
+            | fs::Error::AlreadyExists
+            | fs::Error::NotEmpty
+            | fs::Error::CrossDevice => Error::InvalidArgument,
+        }
+    }
+}
+
+/// Manejador de una llamada: recibe los seis argumentos en bruto.
+type Handler = fn([u64; 6]) -> Result<u64, Error>;
+
+/// Tabla de llamadas: número, nombre y manejador.
+const TABLE: [(u64, &str, Handler); 8] = [
+    (WRITE, "write", write),
+    (READ, "read", read),
+    (EXIT, "exit", exit),
+    (SLEEP, "sleep", sleep),
+    (MAP, "map", map),
+    (OPEN, "open", open),
+    (CLOSE, "close", close),
+    (SEEK, "seek", seek),
+];
+
+/// Ejecuta la llamada `number` y devuelve su resultado o un código de error negativo.
+pub fn dispatch(number: u64, arguments: [u64; 6]) -> i64 {
+    let Some(&(_, name, handler)) = TABLE.iter().find(|(entry, ..)| *entry == number) else {
+        log::debug!("llamada al sistema inexistente: {}", number);
+        return Error::NoSyscall.code();
+    };
+    log::trace!("{}({:#x}, {:#x}, {:#x})", name, arguments[0], arguments[1], arguments[2]);
+    match handler(arguments) {
+        Ok(value) => value as i64,
+        Err(error) => {
+            log::debug!("{}: {}", name, error);
+            error.code()
+        }
+    }
+}
+
+/// Comprueba que `[address, address + len)` esté en la mitad de usuario y
+/// mapeado en el proceso actual con permisos de usuario, y de escritura si
+/// `write`. La primera página nunca es válida, aunque el rango esté vacío:
+/// un búfer nulo no puede convertirse en slice.
+fn check_user(address: u64, len: usize, write: bool) -> Result<(), Error> {
+    if address < PAGE_SIZE {
+        return Err(Error::BadAddress);
+    }
+    let end = address.checked_add(len as u64).filter(|&end| end <= USER_END).ok_or(Error::BadAddress)?;
+    let mut required = Flags::USER;
+    if write {
+        required = required | Flags::WRITABLE;
+    }
+    let first = address / PAGE_SIZE * PAGE_SIZE;
+    let mapped = sched::with_address_space(|space| {
+        (first..end)
+            .step_by(PAGE_SIZE as usize)
+            .all(|page| space.translate(page).is_some_and(|(_, flags)| flags.contains(required)))
+    });
+    if mapped == Some(true) { Ok(()) } else { Err(Error::BadAddress) }
+}
+
+/// Búfer de solo lectura del proceso actual.
+fn user_slice<'a>(address: u64, len: u64) -> Result<&'a [u8], Error> {
+    let len = usize::try_from(len).map_err(|_| Error::BadAddress)?;
+    check_user(address, len, false)?;
+    // El rango está mapeado en el espacio activo, que es el del proceso, y
+    // sus páginas no se desmapean mientras el proceso está en el kernel.
+    Ok(unsafe { core::slice::from_raw_parts(address as *const u8, len) })
+}
+
+/// Búfer de lectura y escritura del proceso actual.
+fn user_slice_mut<'a>(address: u64, len: u64) -> Result<&'a mut [u8], Error> {
+    let len = usize::try_from(len).map_err(|_| Error::BadAddress)?;
+    check_user(address, len, true)?;
+    // Como en `user_slice`.
+    Ok(unsafe { core::slice::from_raw_parts_mut(address as *mut u8, len) })
+}
+
+fn write([fd, address, len, ..]: [u64; 6]) -> Result<u64, Error> {
+    let bytes = user_slice(address, len)?;
+    match fd {
+        0 => Err(Error::BadDescriptor),
+        1 => {
+            let bytes = console_chunk(bytes);
+            crate::print!("{}", Lossy(bytes));
+            Ok(bytes.len() as u64)
+        }
+        2 => {
+            let bytes = console_chunk(bytes);
+            crate::eprint!("{}", Lossy(bytes));
+            Ok(bytes.len() as u64)
+        }
+        _ => {
+            let count = with_files(|files| files.get(fd))?.lock().write(bytes)?;
+            Ok(count as u64)
+        }
+    }
+}
+
+/// Parte de `bytes` que se escribe en la consola en una llamada: como mucho
+/// [`MAX_CONSOLE_WRITE`] bytes, sin cortar un carácter por la mitad salvo
+/// que no quepa ninguno entero.
+fn console_chunk(bytes: &[u8]) -> &[u8] {
+    if bytes.len() <= MAX_CONSOLE_WRITE {
+        return bytes;
+    }
+    let chunk = &bytes[..MAX_CONSOLE_WRITE];
+    // Un carácter incompleto al final empieza en alguno de los 3 últimos bytes.
+    let start = (MAX_CONSOLE_WRITE - 3..MAX_CONSOLE_WRITE).rev().find(|&i| chunk[i] & 0xC0 != 0x80);
+    match start {
+        Some(start) if str::from_utf8(&chunk[start..]).is_err_and(|error| error.error_len().is_none()) => &chunk[..start],
+        _ => chunk,
+    }
+}
+
+/// Muestra bytes como UTF-8, con U+FFFD en cada secuencia inválida, sin
+/// copiarlos a una cadena.
+struct Lossy<'a>(&'a [u8]);
+
+impl fmt::Display for Lossy<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for chunk in self.0.utf8_chunks() {
+            f.write_str(chunk.valid())?;
+            if !chunk.invalid().is_empty() {
+                f.write_str("\u{FFFD}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read([fd, address, len, ..]: [u64; 6]) -> Result<u64, Error> {
+    let buffer = user_slice_mut(address, len)?;
+    match fd {
+        0 => Ok(read_keyboard(buffer)),
+        1 | 2 => Err(Error::BadDescriptor),
+        _ => {
+            let count = with_files(|files| files.get(fd))?.lock().read(buffer)?;
+            Ok(count as u64)
+        }
+    }
+}
+
+/// Lee del teclado en `buffer` al menos un byte. Mientras no haya teclas,
+/// el hilo queda bloqueado hasta que la interrupción del teclado lo despierte.
+fn read_keyboard(buffer: &mut [u8]) -> u64 {
+    let mut keys = KeyStream::new();
+    let mut count = 0;
+    while count < buffer.len() {
+        let key = if count == 0 { Some(sched::block_on(keys.next())) } else { keyboard::poll_key() };
+        match key {
+            Some(DecodedKey::Unicode(c)) => {
+                // La distribución US solo produce ASCII; un carácter que no
+                // quepa en lo que queda del búfer se descarta.
+                let mut encoded = [0; 4];
+                let encoded = c.encode_utf8(&mut encoded).as_bytes();
+                if let Some(space) = buffer.get_mut(count..count + encoded.len()) {
+                    space.copy_from_slice(encoded);
+                    count += encoded.len();
+                }
+            }
+            Some(DecodedKey::RawKey(_)) => {}
+            None => break,
+        }
+    }
+    count as u64
+}
+
+fn exit([code, ..]: [u64; 6]) -> Result<u64, Error> {
+    process::exit(code as i64);
+}
+
+fn sleep([ms, ..]: [u64; 6]) -> Result<u64, Error> {
+    time::sleep_ms(ms.min(MAX_SLEEP_MS));
+    Ok(0)
+}
+
+fn map([address, len, prot, ..]: [u64; 6]) -> Result<u64, Error> {
+    if prot & !(PROT_WRITE | PROT_EXEC) != 0 || len == 0 || !address.is_multiple_of(PAGE_SIZE) {
+        return Err(Error::InvalidArgument);
+    }
+    // La página cero queda sin mapear para que los punteros nulos fallen.
+    let end = address.checked_add(len).map(|end| end.next_multiple_of(PAGE_SIZE));
+    if address < PAGE_SIZE || end.is_none_or(|end| end > USER_END) {
+        return Err(Error::BadAddress);
+    }
+    let flags = process::user_flags(prot & PROT_WRITE != 0, prot & PROT_EXEC != 0);
+    sched::with_address_space(|space| process::map_zeroed(space, address, len, flags))
+        .ok_or(Error::InvalidArgument)??;
+    Ok(address)
+}
+
+/// Ejecuta `f` con la tabla de ficheros del proceso actual, que no debe
+/// leer ni escribir: para eso se saca el fichero de la tabla.
+fn with_files<R>(f: impl FnOnce(&mut fs::file::FileTable) -> Result<R, fs::Error>) -> Result<R, Error> {
+    process::with_current(|process| f(&mut process.files)).ok_or(Error::BadDescriptor)?.map_err(Error::from)
+}
+
+fn open([address, len, ..]: [u64; 6]) -> Result<u64, Error> {
+    let path = str::from_utf8(user_slice(address, len)?).map_err(|_| Error::InvalidArgument)?;
+    let cwd = process::with_current(|process| process.cwd.clone()).ok_or(Error::InvalidArgument)?;
+    // La ruta se resuelve sin la tabla de procesos tomada.
+    let file = File::open(&cwd, path)?;
+    with_files(|files| files.insert(file))
+}
+
+fn close([fd, ..]: [u64; 6]) -> Result<u64, Error> {
+    with_files(|files| files.remove(fd))?;
+    Ok(0)
+}
+
+fn seek([fd, offset, whence, ..]: [u64; 6]) -> Result<u64, Error> {
+    let from = match whence {
+        SEEK_SET => SeekFrom::Start(offset),
+        SEEK_CUR => SeekFrom::Current(offset as i64),
+        SEEK_END => SeekFrom::End(offset as i64),
+        _ => return Err(Error::InvalidArgument),
+    };
+    Ok(with_files(|files| files.get(fd))?.lock().seek(from)?)
+}
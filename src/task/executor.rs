@@ -0,0 +1,113 @@
+//! Ejecutor de tareas asíncronas.
+//!
+//! Sondea solo las tareas que han sido despertadas. La cola de tareas
+//! despertadas se comparte con sus `Waker`, que pueden usarse desde un
+//! manejador de interrupción; cuando está vacía, el ejecutor cede la CPU a
+//! otros hilos o la detiene hasta la siguiente interrupción.
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::task::{Context, Waker};
+
+use super::{Task, TaskId};
+use crate::arch;
+use crate::sched;
+use crate::sync::{lock_order, IrqSpinlock};
+
+/// Tareas despertadas pendientes de sondear.
+struct WokenQueue {
+    tasks: IrqSpinlock<VecDeque<TaskId>>,
+}
+
+impl WokenQueue {
+    fn push(&self, id: TaskId) {
+        let mut tasks = self.tasks.lock();
+        if !tasks.contains(&id) {
+            tasks.push_back(id);
+        }
+    }
+
+    fn pop(&self) -> Option<TaskId> {
+        self.tasks.lock().pop_front()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tasks.lock().is_empty()
+    }
+}
+
+/// `Waker` de una tarea: la vuelve a poner en la cola del ejecutor.
+struct TaskWaker {
+    id: TaskId,
+    queue: Arc<WokenQueue>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.queue.push(self.id);
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.queue.push(self.id);
+    }
+}
+
+/// Ejecutor de tareas de un solo hilo.
+pub struct Executor {
+    tasks: BTreeMap<TaskId, Task>,
+    wakers: BTreeMap<TaskId, Waker>,
+    queue: Arc<WokenQueue>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self {
+            tasks: BTreeMap::new(),
+            wakers: BTreeMap::new(),
+            queue: Arc::new(WokenQueue { tasks: IrqSpinlock::new(&lock_order::EXECUTOR, VecDeque::new()) }),
+        }
+    }
+
+    /// Añade una tarea; se sondea por primera vez en cuanto el ejecutor corre.
+    pub fn spawn(&mut self, task: Task) {
+        let id = task.id;
+        assert!(self.tasks.insert(id, task).is_none(), "tarea {:?} añadida dos veces", id);
+        // Se reserva sitio para que despertarla desde una interrupción no tenga que crecer la cola.
+        self.queue.tasks.lock().reserve(self.tasks.len());
+        self.queue.push(id);
+    }
+
+    /// Ejecuta las tareas para siempre.
+    pub fn run(&mut self) -> ! {
+        loop {
+            self.run_woken();
+            self.wait();
+        }
+    }
+
+    /// Sondea las tareas despertadas hasta vaciar la cola.
+    fn run_woken(&mut self) {
+        while let Some(id) = self.queue.pop() {
+            // Una tarea terminada puede seguir recibiendo despertares.
+            let Some(task) = self.tasks.get_mut(&id) else { continue };
+            let queue = &self.queue;
+            let waker = self.wakers.entry(id).or_insert_with(|| Waker::from(Arc::new(TaskWaker { id, queue: queue.clone() })));
+            if task.poll(&mut Context::from_waker(waker)).is_ready() {
+                self.tasks.remove(&id);
+                self.wakers.remove(&id);
+            }
+        }
+    }
+
+    /// Espera a que alguna tarea sea despertada.
+    fn wait(&self) {
+        // La comprobación y el `hlt` son atómicos respecto a las interrupciones:
+        // un despertar que llegue en medio saca a la CPU del `hlt`.
+        arch::without_interrupts(|| {
+            if self.queue.is_empty() && !sched::yield_now() {
+                arch::enable_interrupts_and_wait();
+            }
+        });
+    }
+}
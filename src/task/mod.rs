@@ -0,0 +1,80 @@
+//! Tareas asíncronas del kernel.
+//!
+//! Una tarea es un `Future` que el [`Executor`] sondea cuando alguien la
+//! despierta a través de su `Waker`. Los manejadores de interrupción guardan
+//! el `Waker` de la tarea que espera un evento en un [`WakerSlot`] y lo usan
+//! cuando el evento llega: así la tarea no gasta CPU mientras espera, y el
+//! ejecutor detiene la CPU cuando no queda ninguna tarea lista.
+
+pub mod executor;
+pub mod timer;
+
+use alloc::boxed::Box;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use crate::sync::{lock_order, IrqSpinlock};
+
+pub use self::executor::Executor;
+
+/// Identificador de una tarea.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        TaskId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Una tarea: un `Future` sin resultado, fijado en el heap.
+pub struct Task {
+    id: TaskId,
+    future: Pin<Box<dyn Future<Output = ()>>>,
+}
+
+impl Task {
+    pub fn new(future: impl Future<Output = ()> + 'static) -> Self {
+        Self { id: TaskId::new(), future: Box::pin(future) }
+    }
+
+    fn poll(&mut self, context: &mut Context) -> Poll<()> {
+        self.future.as_mut().poll(context)
+    }
+}
+
+/// Hueco para el `Waker` de la tarea que espera un evento.
+///
+/// La tarea lo registra desde su `poll` y el productor del evento, que puede
+/// ser un manejador de interrupción, lo despierta. Solo guarda un `Waker`:
+/// cada evento tiene un único consumidor.
+pub struct WakerSlot {
+    waker: IrqSpinlock<Option<Waker>>,
+}
+
+impl WakerSlot {
+    pub const fn new() -> Self {
+        Self { waker: IrqSpinlock::new(&lock_order::WAKER, None) }
+    }
+
+    /// Registra la tarea a la que pertenece `waker`, en lugar de la anterior.
+    pub fn register(&self, waker: &Waker) {
+        let mut slot = self.waker.lock();
+        if !slot.as_ref().is_some_and(|registered| registered.will_wake(waker)) {
+            *slot = Some(waker.clone());
+        }
+    }
+
+    /// Despierta a la tarea registrada, si la hay. Es seguro llamarla desde
+    /// una interrupción.
+    pub fn wake(&self) {
+        // El lock se suelta antes de despertar: la tarea puede volver a registrarse.
+        let waker = self.waker.lock().take();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! Esperas asíncronas sobre el temporizador del sistema.
+//!
+//! Las tareas que duermen registran su `Waker` con el tick en el que vence
+//! su plazo, y la interrupción del temporizador despierta a las que ya han
+//! vencido.
+
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use crate::arch;
+use crate::sync::{lock_order, IrqSpinlock};
+
+/// Plazos pendientes y el `Waker` de la tarea que espera cada uno.
+static TIMERS: IrqSpinlock<Vec<(u64, Waker)>> = IrqSpinlock::new(&lock_order::TIMERS, Vec::new());
+
+/// Despierta a las tareas cuyo plazo ha vencido. Se llama desde la
+/// interrupción del temporizador en cada tick.
+pub fn wake_expired(now: u64) {
+    // Se despierta sin el lock: el `Waker` de un hilo bloqueado toma el del
+    // planificador, que va antes en el orden de locks.
+    let expired: Vec<Waker> = TIMERS.lock().extract_if(.., |(deadline, _)| *deadline <= now).map(|(_, waker)| waker).collect();
+    for waker in expired {
+        waker.wake();
+    }
+}
+
+/// Future que se completa cuando el temporizador alcanza un tick dado.
+///
+/// Si se suelta antes de tiempo, su tarea aún recibirá un despertar de más
+/// cuando venza el plazo.
+pub struct Sleep {
+    deadline: u64,
+    registered: bool,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<()> {
+        if arch::timer_ticks() >= self.deadline {
+            return Poll::Ready(());
+        }
+        if !self.registered {
+            TIMERS.lock().push((self.deadline, context.waker().clone()));
+            self.registered = true;
+        }
+        Poll::Pending
+    }
+}
+
+/// Espera al menos `ms` milisegundos sin ocupar la CPU.
+pub fn sleep_ms(ms: u64) -> Sleep {
+    // Igual que `time::sleep_ms`: un tick de más para no quedarse corto.
+    let wait = (ms * arch::TIMER_HZ).div_ceil(1000) + 1;
+    Sleep { deadline: arch::timer_ticks() + wait, registered: false }
+}
@@ -0,0 +1,419 @@
+//! Modelo de texto de la consola: una rejilla de celdas de carácter.
+//!
+//! Cada celda guarda un carácter y sus atributos (color de primer plano y de
+//! fondo). La rejilla no dibuja nada por sí misma: registra qué partes han
+//! cambiado desde el último pintado para que la consola solo tenga que
+//! redibujar esas celdas. Las filas que salen por la parte superior pasan a un
+//! historial de desplazamiento (scrollback) que puede volver a mostrarse.
+
+use crate::ansi::Erase;
+use crate::colors;
+
+/// Número máximo de columnas de la rejilla.
+pub const MAX_COLUMNS: usize = 256;
+/// Número máximo de filas de la rejilla.
+pub const MAX_ROWS: usize = 128;
+/// Número de filas que conserva el historial de desplazamiento.
+pub const SCROLLBACK_ROWS: usize = 256;
+
+/// Una celda de la rejilla: un carácter con sus colores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub c: char,
+    pub fg: colors::Color,
+    pub bg: colors::Color,
+}
+
+impl Cell {
+    /// Celda en la que nunca se ha escrito. Se muestra vacía, con el color de
+    /// fondo de la rejilla. Es todo ceros para que la rejilla inicial vaya a `.bss`.
+    pub const UNWRITTEN: Cell = Cell { c: '\0', fg: 0, bg: 0 };
+
+    /// Crea una celda vacía con los colores dados.
+    pub const fn blank(fg: colors::Color, bg: colors::Color) -> Self {
+        Self { c: ' ', fg, bg }
+    }
+}
+
+/// Una fila de la rejilla.
+#[derive(Clone, Copy)]
+pub struct Row {
+    cells: [Cell; MAX_COLUMNS],
+}
+
+impl Row {
+    /// Fila en la que nunca se ha escrito.
+    const UNWRITTEN: Row = Row { cells: [Cell::UNWRITTEN; MAX_COLUMNS] };
+
+    /// Rellena la fila con celdas vacías de los colores dados.
+    fn fill(&mut self, blank: Cell) {
+        self.cells = [blank; MAX_COLUMNS];
+    }
+}
+
+/// Rango de columnas `[start, end)` de una fila que hay que redibujar.
+#[derive(Clone, Copy)]
+struct Span {
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    const CLEAN: Span = Span { start: 0, end: 0 };
+
+    fn is_clean(&self) -> bool {
+        self.start >= self.end
+    }
+
+    fn include(&mut self, start: usize, end: usize) {
+        if self.is_clean() {
+            *self = Span { start, end };
+        } else {
+            self.start = self.start.min(start);
+            self.end = self.end.max(end);
+        }
+    }
+}
+
+/// Cambios pendientes de pintar en la pantalla.
+pub enum Damage {
+    /// Toda la rejilla debe redibujarse.
+    Full,
+    /// El contenido se desplazó `scrolled` filas hacia arriba y, además,
+    /// cambiaron los tramos de celdas marcados.
+    Partial { scrolled: usize },
+}
+
+/// La rejilla de texto de la consola.
+pub struct TextGrid {
+    rows: [Row; MAX_ROWS],
+    /// Tramos modificados de cada fila desde el último pintado.
+    dirty: [Span; MAX_ROWS],
+    /// Filas que salieron por arriba, de la más antigua a la más reciente.
+    history: heapless::Deque<Row, SCROLLBACK_ROWS>,
+    columns: usize,
+    height: usize,
+    cursor_column: usize,
+    cursor_row: usize,
+    fg: colors::Color,
+    bg: colors::Color,
+    /// Color de fondo de la pantalla, fijado por el último `clear`.
+    default_bg: colors::Color,
+    /// Posición del cursor y colores guardados con `save_cursor`.
+    saved: (usize, usize, colors::Color, colors::Color),
+    /// Filas que la vista está desplazada hacia atrás en el historial.
+    view_offset: usize,
+    /// Filas desplazadas desde el último pintado.
+    scrolled: usize,
+    /// Indica si hay que redibujar la rejilla completa.
+    full_redraw: bool,
+}
+
+impl TextGrid {
+    /// Crea una rejilla vacía y sin dimensiones; antes de usarla hay que
+    /// llamar a `resize` y a `clear`.
+    ///
+    /// Todos los campos empiezan a cero para que la rejilla, que ocupa más de
+    /// un megabyte, vaya a `.bss` en lugar de ocupar espacio en la imagen del kernel.
+    pub const fn new() -> Self {
+        Self {
+            rows: [Row::UNWRITTEN; MAX_ROWS],
+            dirty: [Span::CLEAN; MAX_ROWS],
+            history: heapless::Deque::new(),
+            columns: 0,
+            height: 0,
+            cursor_column: 0,
+            cursor_row: 0,
+            fg: 0,
+            bg: 0,
+            default_bg: 0,
+            saved: (0, 0, 0, 0),
+            view_offset: 0,
+            scrolled: 0,
+            full_redraw: false,
+        }
+    }
+
+    /// Número de columnas visibles.
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Número de filas visibles.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Posición del cursor como `(columna, fila)`.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.cursor_column, self.cursor_row)
+    }
+
+    /// Indica si la vista muestra el final del texto (y, por tanto, el cursor).
+    pub fn at_bottom(&self) -> bool {
+        self.view_offset == 0
+    }
+
+    /// Color de primer plano con el que se escriben los caracteres.
+    pub fn foreground(&self) -> colors::Color {
+        self.fg
+    }
+
+    /// Cambia el color de primer plano de los caracteres que se escriban.
+    pub fn set_foreground(&mut self, color: colors::Color) {
+        self.fg = color;
+    }
+
+    /// Cambia el color de fondo de los caracteres que se escriban.
+    pub fn set_background(&mut self, color: colors::Color) {
+        self.bg = color;
+    }
+
+    /// Color de fondo de la pantalla, fijado por el último `clear`.
+    pub fn default_background(&self) -> colors::Color {
+        self.default_bg
+    }
+
+    /// Cambia las dimensiones visibles de la rejilla.
+    ///
+    /// El contenido se conserva: las filas guardan siempre `MAX_COLUMNS`
+    /// celdas, así que al estrechar la rejilla solo se ocultan las columnas
+    /// sobrantes. Si la fila del cursor deja de caber, el contenido se desplaza
+    /// hacia arriba hasta que vuelva a quedar visible.
+    pub fn resize(&mut self, columns: usize, height: usize) {
+        self.columns = columns.clamp(1, MAX_COLUMNS);
+        self.height = height.clamp(1, MAX_ROWS);
+        while self.cursor_row >= self.height {
+            self.scroll_up();
+        }
+        self.cursor_column = self.cursor_column.min(self.columns - 1);
+        self.view_offset = 0;
+        self.full_redraw = true;
+    }
+
+    /// Escribe un carácter en la posición del cursor y lo avanza.
+    ///
+    /// Interpreta `\n` (salto de línea), `\r` (retorno de carro), `\t`
+    /// (tabulador cada 4 columnas) y `\x08` (retroceso sin borrar); el resto
+    /// de caracteres de control se ignoran. Si la vista estaba desplazada en
+    /// el historial, vuelve primero al final.
+    pub fn write_char(&mut self, c: char) {
+        self.scroll_view_to_bottom();
+        match c {
+            '\n' => self.new_line(),
+            '\r' => self.cursor_column = 0,
+            '\x08' => self.move_cursor(-1, 0),
+            '\t' => {
+                let next_stop = (self.cursor_column / 4 + 1) * 4;
+                while self.cursor_column < next_stop.min(self.columns) {
+                    self.put(' ');
+                }
+            }
+            c if c.is_control() => {}
+            c => self.put(c),
+        }
+    }
+
+    /// Mueve el cursor `columns` columnas y `rows` filas, sin salir de la rejilla.
+    pub fn move_cursor(&mut self, columns: isize, rows: isize) {
+        let column = self.cursor_column.min(self.columns - 1).saturating_add_signed(columns);
+        let row = self.cursor_row.saturating_add_signed(rows);
+        self.set_cursor(column, row);
+    }
+
+    /// Coloca el cursor en `(column, row)`, ajustado a los límites de la rejilla.
+    pub fn set_cursor(&mut self, column: usize, row: usize) {
+        self.scroll_view_to_bottom();
+        self.cursor_column = column.min(self.columns - 1);
+        self.cursor_row = row.min(self.height - 1);
+    }
+
+    /// Guarda la posición del cursor y los colores actuales.
+    pub fn save_cursor(&mut self) {
+        self.saved = (self.cursor_column, self.cursor_row, self.fg, self.bg);
+    }
+
+    /// Restaura la posición y los colores guardados con `save_cursor`.
+    pub fn restore_cursor(&mut self) {
+        let (column, row, fg, bg) = self.saved;
+        self.set_cursor(column, row);
+        self.fg = fg;
+        self.bg = bg;
+    }
+
+    /// Borra parte de la línea del cursor con el color de fondo actual.
+    pub fn erase_in_line(&mut self, erase: Erase) {
+        self.scroll_view_to_bottom();
+        let column = self.cursor_column.min(self.columns - 1);
+        let (start, end) = match erase {
+            Erase::ToEnd => (column, self.columns),
+            Erase::ToStart => (0, column + 1),
+            Erase::All => (0, self.columns),
+        };
+        self.erase(self.cursor_row, start, end);
+    }
+
+    /// Borra parte de la pantalla con el color de fondo actual. El cursor no se mueve.
+    pub fn erase_in_display(&mut self, erase: Erase) {
+        self.erase_in_line(erase);
+        let rows = match erase {
+            Erase::ToEnd => self.cursor_row + 1..self.height,
+            Erase::ToStart => 0..self.cursor_row,
+            Erase::All => 0..self.height,
+        };
+        for row in rows {
+            self.erase(row, 0, self.columns);
+        }
+    }
+
+    /// Deja en blanco las celdas `[start, end)` de una fila.
+    fn erase(&mut self, row: usize, start: usize, end: usize) {
+        let blank = Cell::blank(self.fg, self.bg);
+        self.rows[row].cells[start..end].fill(blank);
+        self.dirty[row].include(start, end);
+    }
+
+    /// Escribe un carácter imprimible, pasando a la línea siguiente si la actual está llena.
+    fn put(&mut self, c: char) {
+        if self.cursor_column >= self.columns {
+            self.new_line();
+        }
+        let (column, row) = (self.cursor_column, self.cursor_row);
+        self.rows[row].cells[column] = Cell { c, fg: self.fg, bg: self.bg };
+        self.dirty[row].include(column, column + 1);
+        self.cursor_column += 1;
+    }
+
+    /// Borra el carácter anterior al cursor y retrocede una posición.
+    pub fn backspace(&mut self) {
+        self.scroll_view_to_bottom();
+        if self.cursor_column == 0 {
+            // Al principio de una fila, se vuelve al final de la anterior (la
+            // línea se había partido al llegar al borde derecho).
+            if self.cursor_row == 0 {
+                return;
+            }
+            self.cursor_row -= 1;
+            self.cursor_column = self.columns;
+        }
+        self.cursor_column -= 1;
+        let (column, row) = (self.cursor_column, self.cursor_row);
+        self.rows[row].cells[column] = Cell::blank(self.fg, self.bg);
+        self.dirty[row].include(column, column + 1);
+    }
+
+    /// Lleva el cursor al principio de la línea siguiente, desplazando el
+    /// contenido si ya estaba en la última fila.
+    pub fn new_line(&mut self) {
+        self.cursor_column = 0;
+        if self.cursor_row + 1 < self.height {
+            self.cursor_row += 1;
+        } else {
+            self.scroll_up();
+        }
+    }
+
+    /// Desplaza la rejilla una fila hacia arriba. La fila superior pasa al historial.
+    fn scroll_up(&mut self) {
+        if self.history.is_full() {
+            self.history.pop_front();
+        }
+        let _ = self.history.push_back(self.rows[0]);
+
+        let last = self.height - 1;
+        self.rows.copy_within(1..=last, 0);
+        self.rows[last].fill(Cell::blank(self.fg, self.bg));
+        self.dirty.copy_within(1..=last, 0);
+        self.dirty[last] = Span { start: 0, end: self.columns };
+        self.cursor_row = self.cursor_row.saturating_sub(1);
+        self.scrolled += 1;
+    }
+
+    /// Borra la pantalla con el color de fondo dado y lleva el cursor al origen.
+    ///
+    /// Las filas que tenían texto pasan al historial, de modo que siguen
+    /// pudiendo consultarse con Shift+RePág.
+    pub fn clear(&mut self, bg: colors::Color) {
+        let used_rows = if self.cursor() == (0, 0) { 0 } else { self.cursor_row + 1 };
+        for row in 0..used_rows {
+            if self.history.is_full() {
+                self.history.pop_front();
+            }
+            let _ = self.history.push_back(self.rows[row]);
+        }
+        self.bg = bg;
+        self.default_bg = bg;
+        let blank = Cell::blank(self.fg, bg);
+        for row in self.rows.iter_mut() {
+            row.fill(blank);
+        }
+        self.cursor_column = 0;
+        self.cursor_row = 0;
+        self.view_offset = 0;
+        self.full_redraw = true;
+    }
+
+    /// Desplaza la vista `rows` filas hacia atrás en el historial.
+    pub fn scroll_view_up(&mut self, rows: usize) {
+        let offset = (self.view_offset + rows).min(self.history.len());
+        if offset != self.view_offset {
+            self.view_offset = offset;
+            self.full_redraw = true;
+        }
+    }
+
+    /// Desplaza la vista `rows` filas hacia delante en el historial.
+    pub fn scroll_view_down(&mut self, rows: usize) {
+        let offset = self.view_offset.saturating_sub(rows);
+        if offset != self.view_offset {
+            self.view_offset = offset;
+            self.full_redraw = true;
+        }
+    }
+
+    fn scroll_view_to_bottom(&mut self) {
+        if self.view_offset != 0 {
+            self.view_offset = 0;
+            self.full_redraw = true;
+        }
+    }
+
+    /// Devuelve la celda que se muestra en la posición `(column, row)` de la
+    /// pantalla, teniendo en cuenta el desplazamiento de la vista.
+    pub fn visible_cell(&self, column: usize, row: usize) -> Cell {
+        let index = self.history.len() + row - self.view_offset;
+        let row = match index.checked_sub(self.history.len()) {
+            Some(index) => &self.rows[index],
+            None => self.history.iter().nth(index).unwrap_or(&Row::UNWRITTEN),
+        };
+        match row.cells[column] {
+            Cell { c: '\0', .. } => Cell::blank(self.fg, self.default_bg),
+            cell => cell,
+        }
+    }
+
+    /// Marca toda la rejilla para redibujarla.
+    pub fn invalidate(&mut self) {
+        self.full_redraw = true;
+    }
+
+    /// Devuelve los cambios acumulados y los da por pintados.
+    ///
+    /// Tras un `Damage::Partial`, los tramos a redibujar se obtienen con
+    /// `take_dirty_span`.
+    pub fn take_damage(&mut self) -> Damage {
+        let scrolled = core::mem::take(&mut self.scrolled);
+        if core::mem::take(&mut self.full_redraw) || scrolled >= self.height {
+            self.dirty = [Span::CLEAN; MAX_ROWS];
+            Damage::Full
+        } else {
+            Damage::Partial { scrolled }
+        }
+    }
+
+    /// Devuelve y limpia el tramo `[inicio, fin)` modificado de una fila, si lo hay.
+    pub fn take_dirty_span(&mut self, row: usize) -> Option<(usize, usize)> {
+        let span = core::mem::replace(&mut self.dirty[row], Span::CLEAN);
+        (!span.is_clean()).then(|| (span.start, span.end.min(self.columns)))
+    }
+}
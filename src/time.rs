@@ -0,0 +1,67 @@
+//! Servicios de tiempo del kernel: tiempo de actividad y esperas.
+//!
+//! Se apoyan en el contador de ticks del temporizador del sistema, que avanza
+//! a una frecuencia fija independiente de la velocidad del procesador.
+
+use core::fmt;
+use core::time::Duration;
+use crate::arch;
+use crate::sched;
+
+/// Devuelve el tiempo transcurrido desde que se inició el temporizador del sistema.
+pub fn uptime() -> Duration {
+    let ticks = arch::timer_ticks();
+    let hz = arch::TIMER_HZ;
+    Duration::from_secs(ticks / hz) + Duration::from_nanos((ticks % hz) * 1_000_000_000 / hz)
+}
+
+/// Detiene la ejecución durante al menos `ms` milisegundos.
+///
+/// El hilo actual duerme y la CPU queda para los demás hilos, o en `hlt` si
+/// no hay ninguno listo. Requiere que las interrupciones estén habilitadas:
+/// de lo contrario el contador de ticks no avanzaría nunca.
+pub fn sleep_ms(ms: u64) {
+    // Redondeamos hacia arriba y sumamos un tick más, porque el tick en curso
+    // puede estar a punto de terminar: así nunca dormimos menos de lo pedido.
+    let wait = (ms * arch::TIMER_HZ).div_ceil(1000) + 1;
+    sched::sleep_ticks(wait);
+}
+
+/// Detiene la ejecución durante al menos la duración indicada.
+#[allow(dead_code)]
+pub fn sleep(duration: Duration) {
+    sleep_ms(duration.as_millis() as u64);
+}
+
+/// Fecha y hora UTC de un instante en segundos desde 1970, que se muestra
+/// como `AAAA-MM-DD hh:mm:ss`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime(pub u64);
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (days, seconds) = (self.0 / 86_400, self.0 % 86_400);
+        // Algoritmo de Howard Hinnant (`civil_from_days`): cuenta en eras de
+        // 400 años que empiezan el 1 de marzo, para que el día bisiesto sea
+        // el último del año.
+        let days = days + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days % 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+        let year = year_of_era + era * 400 + u64::from(month <= 2);
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
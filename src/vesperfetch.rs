@@ -4,6 +4,8 @@
 //! del sistema operativo junto al logo de VesperOS de una forma estilizada.
 
 use core::fmt::Write;
+use core::time::Duration;
+use crate::ansi::Fg;
 use crate::vga::FramebufferWriter;
 use crate::branding;
 use crate::colors;
@@ -17,10 +19,12 @@ pub struct SystemInfo {
     pub os_version: &'static str,
     /// Versión del kernel.
     pub kernel_version: &'static str,
-    /// Información de la CPU (actualmente un placeholder).
+    /// Cadena de marca de la CPU.
     pub cpu_info: &'static str,
-    /// Tiempo de actividad del sistema (actualmente un placeholder).
-    pub uptime: &'static str,
+    /// Tiempo de actividad del sistema.
+    pub uptime: Duration,
+    /// Memoria en uso en Megabytes.
+    pub memory_used_mb: u64,
     /// Memoria total usable en Megabytes.
     pub memory_total_mb: u64,
     /// Ancho de la resolución de pantalla en píxeles.
@@ -103,12 +107,17 @@ impl VesperFetch {
         const LINE_HEIGHT: usize = 20;
         const VALUE_OFFSET: usize = 100; // Desplazamiento horizontal para los valores.
 
-        // OS + Version
-        writer.set_color(self.theme.accent_color);
+        // OS + Version, en dos colores dentro del mismo `write!`.
         writer.set_cursor_position(x, current_y);
-        write!(writer, "{}", self.system_info.os_name).unwrap();
-        writer.set_color(self.theme.value_color);
-        write!(writer, "@{}", self.system_info.os_version).unwrap();
+        write!(
+            writer,
+            "{}{}{}@{}",
+            Fg(self.theme.accent_color),
+            self.system_info.os_name,
+            Fg(self.theme.value_color),
+            self.system_info.os_version
+        )
+        .unwrap();
         current_y += LINE_HEIGHT;
 
         // Separador
@@ -120,7 +129,16 @@ impl VesperFetch {
         // Dibuja cada par etiqueta-valor.
         self.draw_info_line(writer, x, &mut current_y, LINE_HEIGHT, VALUE_OFFSET, "Kernel:", self.system_info.kernel_version);
         self.draw_info_line(writer, x, &mut current_y, LINE_HEIGHT, VALUE_OFFSET, "CPU:", self.system_info.cpu_info);
-        self.draw_info_line(writer, x, &mut current_y, LINE_HEIGHT, VALUE_OFFSET, "Uptime:", self.system_info.uptime);
+
+        // Uptime (con formato)
+        let uptime_secs = self.system_info.uptime.as_secs();
+        writer.set_color(self.theme.label_color);
+        writer.set_cursor_position(x, current_y);
+        write!(writer, "Uptime:").unwrap();
+        writer.set_color(self.theme.value_color);
+        writer.set_cursor_position(x + VALUE_OFFSET, current_y);
+        write!(writer, "{}h {}m {}s", uptime_secs / 3600, (uptime_secs / 60) % 60, uptime_secs % 60).unwrap();
+        current_y += LINE_HEIGHT;
 
         // Memory (con formato)
         writer.set_color(self.theme.label_color);
@@ -128,7 +146,7 @@ impl VesperFetch {
         write!(writer, "Memory:").unwrap();
         writer.set_color(self.theme.value_color);
         writer.set_cursor_position(x + VALUE_OFFSET, current_y);
-        write!(writer, "{} MB", self.system_info.memory_total_mb).unwrap();
+        write!(writer, "{} MB / {} MB", self.system_info.memory_used_mb, self.system_info.memory_total_mb).unwrap();
         current_y += LINE_HEIGHT;
 
         // Resolution (con formato)
@@ -180,9 +198,11 @@ pub fn show_animated_fetch(writer: &mut FramebufferWriter, system_info: SystemIn
         
         let vesper_fetch = VesperFetch::new(system_info).with_theme(theme);
         vesper_fetch.display(writer, x, y);
-        
+        // Cada fotograma se muestra entero de una vez.
+        writer.present();
+
         // Pequeña pausa para controlar la velocidad de la animación.
-        for _ in 0..10000 { unsafe { core::arch::asm!("nop"); } }
+        crate::time::sleep_ms(10);
     }
 }
 
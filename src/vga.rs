@@ -3,22 +3,49 @@
 //! Proporciona la estructura `FramebufferWriter` que abstrae el acceso directo
 //! a la memoria de video, permitiendo dibujar píxeles, texto e imágenes
 //! de una manera sencilla y segura.
+//!
+//! El escritor de la consola dibuja sobre un búfer trasero en memoria normal
+//! y anota las zonas que modifica; `present()` copia solo esas zonas a la
+//! memoria de vídeo. Así los redibujados completos y las animaciones no
+//! muestran estados intermedios ni parpadean.
 
 use core::fmt;
 use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
 use embedded_graphics::image::Image;
 use embedded_graphics::{
-    mono_font::MonoTextStyle,
+    mono_font::MonoFont,
     pixelcolor::Rgb888,
     prelude::*,
-    text::{Baseline, Text},
+    primitives::Rectangle,
 };
 use profont::PROFONT_14_POINT;
 use tinybmp::Bmp;
 use limine::framebuffer::Framebuffer;
 
+use crate::ansi::{Action, Parser};
 use crate::colors;
 use crate::branding;
+use crate::dirty::{DirtyRegion, Rect};
+use crate::glyph_cache::{self, Glyph, GlyphCache};
+use crate::log;
+use crate::pixel_format::PixelFormat;
+
+/// Margen izquierdo y superior del texto, en píxeles.
+const TEXT_MARGIN: usize = 20;
+
+/// Tamaño del búfer trasero: suficiente para 1920x1080 a 32 bits por píxel.
+const BACK_BUFFER_SIZE: usize = 1920 * 1080 * 4;
+
+/// Memoria del búfer trasero, alineada a página.
+#[repr(C, align(4096))]
+struct BackBuffer([u8; BACK_BUFFER_SIZE]);
+
+/// Búfer trasero del escritor de la consola. Empieza a cero, así que no ocupa
+/// espacio en la imagen del kernel.
+static mut BACK_BUFFER: BackBuffer = BackBuffer([0; BACK_BUFFER_SIZE]);
+/// Indica si algún escritor ya tomó `BACK_BUFFER`.
+static BACK_BUFFER_TAKEN: AtomicBool = AtomicBool::new(false);
 
 /// Estructura para escribir en el Framebuffer.
 ///
@@ -28,14 +55,26 @@ use crate::branding;
 /// y `embedded_graphics::draw_target::DrawTarget` para ser compatible
 /// con la librería `embedded-graphics`.
 pub struct FramebufferWriter {
+    /// Memoria de vídeo.
     framebuffer: &'static mut [u8],
+    /// Búfer trasero sobre el que se dibuja, con la misma disposición que la
+    /// memoria de vídeo. Sin él se dibuja directamente en pantalla.
+    back: Option<&'static mut [u8]>,
+    /// Zonas del búfer trasero que aún no se han copiado a la pantalla.
+    dirty: DirtyRegion,
+    /// Glifos ya rasterizados. Sin caché, cada carácter se rasteriza al dibujarlo.
+    glyphs: Option<&'static mut GlyphCache>,
     width: usize,
     height: usize,
     pitch: usize,
     bytes_per_pixel: usize,
+    /// Formato de los píxeles, según lo informa el gestor de arranque.
+    format: PixelFormat,
     x_pos: usize,
     y_pos: usize,
     color: colors::Color,
+    /// Intérprete de las secuencias de escape del texto escrito.
+    parser: Parser,
 }
 
 impl FramebufferWriter {
@@ -54,16 +93,80 @@ impl FramebufferWriter {
     pub fn new(fb: &Framebuffer) -> Self {
         let addr = fb.addr() as *mut u8;
         let len = (fb.pitch() * fb.height()) as usize;
-        
+        let format = PixelFormat::from_framebuffer(fb);
+
         Self {
             framebuffer: unsafe { core::slice::from_raw_parts_mut(addr, len) },
+            back: None,
+            dirty: DirtyRegion::new(),
+            glyphs: None,
             width: fb.width() as usize,
             height: fb.height() as usize,
             pitch: fb.pitch() as usize,
-            bytes_per_pixel: (fb.bpp() / 8) as usize,
+            bytes_per_pixel: format.bytes_per_pixel,
+            format,
             x_pos: 20, // Margen izquierdo inicial
             y_pos: 20, // Margen superior inicial
             color: colors::TEXT_PRIMARY, // Color de texto por defecto
+            parser: Parser::new(),
+        }
+    }
+
+    /// Crea un escritor que dibuja sobre el búfer trasero global y usa la
+    /// caché global de glifos.
+    ///
+    /// Lo dibujado no aparece en pantalla hasta llamar a [`present`](Self::present).
+    /// Solo un escritor puede tener el búfer trasero; si ya está tomado o el
+    /// framebuffer no cabe en él, el escritor dibuja directamente en pantalla.
+    pub fn double_buffered(fb: &Framebuffer) -> Self {
+        let mut writer = Self::new(fb);
+        writer.glyphs = glyph_cache::take();
+        let len = writer.framebuffer.len();
+        if len > BACK_BUFFER_SIZE {
+            log::warn!("framebuffer de {} bytes demasiado grande para el búfer trasero", len);
+        } else if !BACK_BUFFER_TAKEN.swap(true, Ordering::AcqRel) {
+            // `BACK_BUFFER_TAKEN` garantiza que este es el único acceso al búfer.
+            let back = &raw mut BACK_BUFFER;
+            let buffer = unsafe { &mut (&mut *back).0[..len] };
+            buffer.copy_from_slice(writer.framebuffer);
+            writer.back = Some(buffer);
+        }
+        writer
+    }
+
+    /// Copia a la pantalla las zonas del búfer trasero modificadas desde la
+    /// última llamada. Sin búfer trasero no hace nada.
+    pub fn present(&mut self) {
+        let Some(back) = &self.back else { return };
+        let bpp = self.bytes_per_pixel;
+        for rect in self.dirty.rects() {
+            if rect.x == 0 && rect.width == self.width {
+                // Filas completas: un único bloque contiguo.
+                let range = rect.y * self.pitch..rect.bottom() * self.pitch;
+                self.framebuffer[range.clone()].copy_from_slice(&back[range]);
+                continue;
+            }
+            for y in rect.y..rect.bottom() {
+                let start = y * self.pitch + rect.x * bpp;
+                let range = start..start + rect.width * bpp;
+                self.framebuffer[range.clone()].copy_from_slice(&back[range]);
+            }
+        }
+        self.dirty.clear();
+    }
+
+    /// Memoria sobre la que se dibuja: el búfer trasero si lo hay, o la pantalla.
+    fn surface(&mut self) -> &mut [u8] {
+        match &mut self.back {
+            Some(back) => back,
+            None => self.framebuffer,
+        }
+    }
+
+    /// Anota que `rect` ha cambiado y debe copiarse en el próximo `present`.
+    fn mark_dirty(&mut self, rect: Rect) {
+        if self.back.is_some() {
+            self.dirty.add(rect.clip(self.width, self.height));
         }
     }
 
@@ -88,6 +191,16 @@ impl FramebufferWriter {
         self.height
     }
 
+    /// Devuelve el formato de los píxeles del framebuffer.
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Devuelve los aciertos y fallos de la caché de glifos, si el escritor tiene una.
+    pub fn glyph_cache_stats(&self) -> Option<(u64, u64)> {
+        self.glyphs.as_ref().map(|cache| cache.stats())
+    }
+
     /// Cambia el color de texto actual.
     pub fn set_color(&mut self, color: colors::Color) {
         self.color = color;
@@ -98,31 +211,127 @@ impl FramebufferWriter {
     /// Rellena cada píxel del framebuffer con el `color` dado y reinicia
     /// la posición del cursor a su valor inicial.
     pub fn clear(&mut self, color: colors::Color) {
-        let color_bytes = color.to_le_bytes();
-        let pixel = &color_bytes[..self.bytes_per_pixel];
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let offset = y * self.pitch + x * self.bytes_per_pixel;
-                self.framebuffer[offset..offset + self.bytes_per_pixel].copy_from_slice(pixel);
-            }
-        }
+        self.fill(Rect::new(0, 0, self.width, self.height), color);
         // Reiniciamos la posición del cursor después de limpiar.
-        self.x_pos = 20;
-        self.y_pos = 20;
+        self.x_pos = TEXT_MARGIN;
+        self.y_pos = TEXT_MARGIN;
     }
 
     /// Dibuja un píxel en las coordenadas `(x, y)` con el `color` especificado.
     ///
     /// Esta es la función base para todo el renderizado. Ignora las coordenadas
     /// que están fuera de los límites de la pantalla.
+    #[allow(dead_code)]
     pub fn write_pixel(&mut self, x: usize, y: usize, color: colors::Color) {
+        self.put_pixel(x, y, color);
+        self.mark_dirty(Rect::new(x, y, 1, 1));
+    }
+
+    /// Como `write_pixel`, pero sin anotar el píxel como modificado: quien
+    /// la llama anota de una vez toda la zona que dibuja.
+    fn put_pixel(&mut self, x: usize, y: usize, color: colors::Color) {
         if x >= self.width || y >= self.height {
             return;
         }
-        let offset = y * self.pitch + x * self.bytes_per_pixel;
-        let pixel_bytes = color.to_le_bytes();
-        self.framebuffer[offset..offset + self.bytes_per_pixel]
-            .copy_from_slice(&pixel_bytes[..self.bytes_per_pixel]);
+        let bpp = self.bytes_per_pixel;
+        let offset = y * self.pitch + x * bpp;
+        let value = self.format.encode(color, x, y);
+        store(self.surface(), offset, value, bpp);
+    }
+
+    /// Rellena un rectángulo, recortado a la pantalla, con un color.
+    ///
+    /// Solo se codifican los píxeles de un patrón (uno, o 4x4 si el formato
+    /// usa tramado); el resto de cada fila se rellena copiando bloques cada
+    /// vez más grandes de lo ya escrito, y cada fila siguiente es una copia de
+    /// una fila anterior.
+    fn fill(&mut self, rect: Rect, color: colors::Color) {
+        let rect = rect.clip(self.width, self.height);
+        if rect.is_empty() {
+            return;
+        }
+        let (format, pitch, bpp) = (self.format, self.pitch, self.bytes_per_pixel);
+        let period = if format.dithers() { 4 } else { 1 };
+        let row_len = rect.width * bpp;
+        let surface = self.surface();
+        for y in rect.y..rect.bottom() {
+            let start = y * pitch + rect.x * bpp;
+            if y >= rect.y + period {
+                let source = start - period * pitch;
+                surface.copy_within(source..source + row_len, start);
+                continue;
+            }
+            let row = &mut surface[start..start + row_len];
+            let pattern = period.min(rect.width);
+            for i in 0..pattern {
+                store(row, i * bpp, format.encode(color, rect.x + i, y), bpp);
+            }
+            let mut filled = pattern * bpp;
+            while filled < row_len {
+                let count = filled.min(row_len - filled);
+                row.copy_within(..count, filled);
+                filled += count;
+            }
+        }
+        self.mark_dirty(rect);
+    }
+
+    /// Copia un bloque de `width` x `height` píxeles con la esquina superior
+    /// izquierda en `(x0, y0)`, recortado a la pantalla.
+    ///
+    /// `pixel(x, y)` da el color de cada píxel del bloque, o `None` si es
+    /// transparente. Los límites se comprueban una vez por bloque y no por píxel.
+    fn blit(&mut self, x0: usize, y0: usize, width: usize, height: usize, mut pixel: impl FnMut(usize, usize) -> Option<colors::Color>) {
+        let area = Rect::new(x0, y0, width, height).clip(self.width, self.height);
+        let (format, pitch, bpp) = (self.format, self.pitch, self.bytes_per_pixel);
+        let surface = self.surface();
+        for y in area.y..area.bottom() {
+            let mut offset = y * pitch + area.x * bpp;
+            for x in area.x..area.right() {
+                if let Some(color) = pixel(x - x0, y - y0) {
+                    store(surface, offset, format.encode(color, x, y), bpp);
+                }
+                offset += bpp;
+            }
+        }
+        self.mark_dirty(area);
+    }
+
+    /// Dibuja el carácter `c` en `(x, y)` con la fuente dada, en color `fg`
+    /// sobre `bg` (`colors::TRANSPARENT` para no pintar el fondo).
+    ///
+    /// El glifo sale de la caché; si está codificado en el formato del
+    /// framebuffer, cada fila se copia de una vez.
+    fn draw_glyph(&mut self, x: usize, y: usize, c: char, font: &MonoFont, fg: colors::Color, bg: colors::Color) {
+        let format = self.format;
+        let mut cache = self.glyphs.take();
+        let local;
+        let glyph = match cache.as_deref_mut() {
+            Some(cache) => cache.get(font, c, fg, bg, format),
+            None => {
+                local = Glyph::new(font, c, fg, bg, format);
+                &local
+            }
+        };
+
+        let area = Rect::new(x, y, glyph.width(), glyph.height()).clip(self.width, self.height);
+        let (pitch, bpp) = (self.pitch, self.bytes_per_pixel);
+        let surface = self.surface();
+        for row in area.y..area.bottom() {
+            let start = row * pitch + area.x * bpp;
+            if let Some(pixels) = glyph.encoded_row(row - y) {
+                let skip = (area.x - x) * bpp;
+                surface[start..start + area.width * bpp].copy_from_slice(&pixels[skip..skip + area.width * bpp]);
+                continue;
+            }
+            for column in area.x..area.right() {
+                if let Some(color) = glyph.color(column - x, row - y) {
+                    store(surface, start + (column - area.x) * bpp, format.encode(color, column, row), bpp);
+                }
+            }
+        }
+        self.glyphs = cache;
+        self.mark_dirty(area);
     }
 
     /// Dibuja un carácter en la posición actual del cursor.
@@ -130,42 +339,64 @@ impl FramebufferWriter {
     /// Maneja saltos de línea (`\n`) y ajusta el texto a la siguiente línea
     /// si se alcanza el borde derecho de la pantalla.
     fn write_char(&mut self, c: char) {
-        // Define el estilo del carácter usando la fuente ProFont y el color actual.
-        let r = ((self.color >> 16) & 0xFF) as u8;
-        let g = ((self.color >> 8) & 0xFF) as u8;
-        let b = (self.color & 0xFF) as u8;
-        let char_style = MonoTextStyle::new(&PROFONT_14_POINT, Rgb888::new(r, g, b));
-
         match c {
-            '\n' => {
-                self.y_pos += PROFONT_14_POINT.character_size.height as usize;
-                self.x_pos = 20;
-            }
+            '\n' => self.new_line(),
             c => {
                 let char_width = PROFONT_14_POINT.character_size.width as usize;
                 if self.x_pos + char_width > self.width {
-                    self.y_pos += PROFONT_14_POINT.character_size.height as usize;
-                    self.x_pos = 20;
+                    self.new_line();
                 }
 
-                // Crea un buffer de 1 carácter para dibujar.
-                let mut buf = [0u8; 4];
-                let s = c.encode_utf8(&mut buf);
-
-                // Dibuja el texto usando embedded-graphics.
-                Text::with_baseline(s, Point::new(self.x_pos as i32, self.y_pos as i32), char_style, Baseline::Top)
-                    .draw(self)
-                    .unwrap();
+                // Dibuja el carácter con la fuente ProFont y el color actual, sin fondo.
+                self.draw_glyph(self.x_pos, self.y_pos, c, &PROFONT_14_POINT, self.color, colors::TRANSPARENT);
 
                 self.x_pos += char_width;
             }
         }
     }
 
+    /// Mueve el cursor al principio de la línea siguiente.
+    ///
+    /// Si la nueva línea no cabe en la pantalla, desplaza todo el contenido
+    /// hacia arriba para hacerle sitio.
+    fn new_line(&mut self) {
+        let char_height = PROFONT_14_POINT.character_size.height as usize;
+        self.x_pos = TEXT_MARGIN;
+        self.y_pos += char_height;
+        while self.y_pos + char_height > self.height && self.y_pos >= char_height {
+            let height = self.height;
+            self.scroll_region(0, height, char_height, colors::BACKGROUND_COLOR);
+            self.y_pos -= char_height;
+        }
+    }
+
+    /// Desplaza hacia arriba `rows` filas de píxeles el contenido de la franja
+    /// horizontal que empieza en `y` y mide `height` píxeles, y rellena el
+    /// hueco inferior con `color`.
+    pub fn scroll_region(&mut self, y: usize, height: usize, rows: usize, color: colors::Color) {
+        let height = height.min(self.height.saturating_sub(y));
+        let rows = rows.min(height);
+        let start = (y + rows) * self.pitch;
+        let end = (y + height) * self.pitch;
+        let dest = y * self.pitch;
+        self.surface().copy_within(start..end, dest);
+        let width = self.width;
+        self.mark_dirty(Rect::new(0, y, width, height));
+        self.draw_rect(0, y + height - rows, width, rows, color);
+    }
+
+    /// Dibuja un carácter con su fondo en `(x, y)` usando la fuente dada, sin
+    /// modificar el cursor de texto. Es la primitiva que usa la consola para
+    /// pintar las celdas de su rejilla.
+    pub fn draw_cell(&mut self, x: usize, y: usize, c: char, font: &MonoFont, fg: colors::Color, bg: colors::Color) {
+        self.draw_glyph(x, y, c, font, fg, bg);
+    }
+
     /// Borra el carácter anterior a la posición actual del cursor.
+    #[allow(dead_code)]
     pub fn backspace(&mut self) {
         let char_width = PROFONT_14_POINT.character_size.width as usize;
-        let left_margin = 20;
+        let left_margin = TEXT_MARGIN;
 
         if self.x_pos > left_margin {
             self.x_pos -= char_width;
@@ -179,15 +410,11 @@ impl FramebufferWriter {
     /// Después de dibujar, actualiza la posición del cursor de texto para que
     /// se sitúe debajo de la imagen.
     pub fn draw_image(&mut self, image: &branding::RawImage, start_x: usize, start_y: usize) {
-        for y in 0..image.height {
-            for x in 0..image.width {
-                let color = image.data[y * image.width + x];
-                // Si el color no es transparente, lo dibujamos.
-                if color != colors::TRANSPARENT {
-                    self.write_pixel(start_x + x, start_y + y, color);
-                }
-            }
-        }
+        self.blit(start_x, start_y, image.width, image.height, |x, y| {
+            let color = image.data[y * image.width + x];
+            // Los píxeles transparentes no se dibujan.
+            (color != colors::TRANSPARENT).then_some(color)
+        });
         // Movemos el cursor de texto debajo de la imagen.
         self.x_pos = 20;
         self.y_pos = start_y + image.height + 20; // 20px de margen
@@ -209,24 +436,16 @@ impl FramebufferWriter {
     /// A diferencia de `draw_image`, esta función es un "blit" puro y no modifica
     /// el estado del cursor de texto.
     pub fn blit_raw_image(&mut self, image: &branding::RawImage, start_x: usize, start_y: usize) {
-        for y in 0..image.height {
-            for x in 0..image.width {
-                let color = image.data[y * image.width + x];
-                // Si el color no es transparente, lo dibujamos.
-                if color != colors::TRANSPARENT {
-                    self.write_pixel(start_x + x, start_y + y, color);
-                }
-            }
-        }
+        self.blit(start_x, start_y, image.width, image.height, |x, y| {
+            let color = image.data[y * image.width + x];
+            // Los píxeles transparentes no se dibujan.
+            (color != colors::TRANSPARENT).then_some(color)
+        });
     }
 
     /// Dibuja un rectángulo relleno.
     pub fn draw_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: colors::Color) {
-        for j in 0..height {
-            for i in 0..width {
-                self.write_pixel(x + i, y + j, color);
-            }
-        }
+        self.fill(Rect::new(x, y, width, height), color);
     }
 
     /// Dibuja un bloque de píxeles RGB888 crudos.
@@ -241,21 +460,11 @@ impl FramebufferWriter {
         h: usize,
         data: &[u8], // len = w*h*3
     ) {
-        let stride = w * 3;
-        for row in 0..h {
-            let off = row * stride;
-            let line = &data[off..off + stride];
-            for col in 0..w {
-                let i = col * 3;
-                let r = line[i];
-                let g = line[i + 1];
-                let b = line[i + 2];
-
-                // Construimos 0x00RRGGBB
-                let color_u32 = ((r as u32) << 16) | ((g as u32) << 8) | (b as u32);
-                self.write_pixel(x0 + col, y0 + row, color_u32);
-            }
-        }
+        self.blit(x0, y0, w, h, |col, row| {
+            let i = (row * w + col) * 3;
+            // Construimos 0x00RRGGBB
+            Some((data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32)
+        });
     }
 
     /// Dibuja una imagen desde un buffer de bytes en formato BMP.
@@ -268,15 +477,54 @@ impl FramebufferWriter {
     }
 }
 
+/// Convierte un color de `embedded-graphics` a `0x00RRGGBB`.
+fn from_rgb888(color: Rgb888) -> colors::Color {
+    (color.r() as u32) << 16 | (color.g() as u32) << 8 | color.b() as u32
+}
+
+/// Convierte un rectángulo de `embedded-graphics` ya recortado a la pantalla
+/// (sin coordenadas negativas). Devuelve `None` si está vacío.
+fn to_rect(rect: &Rectangle) -> Option<Rect> {
+    let rect = Rect::new(
+        rect.top_left.x.max(0) as usize,
+        rect.top_left.y.max(0) as usize,
+        rect.size.width as usize,
+        rect.size.height as usize,
+    );
+    (!rect.is_empty()).then_some(rect)
+}
+
+/// Escribe en `surface`, a partir de `offset`, los `bpp` bytes de un píxel ya codificado.
+fn store(surface: &mut [u8], offset: usize, value: u32, bpp: usize) {
+    surface[offset..offset + bpp].copy_from_slice(&value.to_le_bytes()[..bpp]);
+}
+
 /// Implementación del trait `core::fmt::Write` para `FramebufferWriter`.
 ///
 /// Esto permite usar macros de formato como `write!`, `writeln!`, etc.,
 /// para escribir texto directamente en el framebuffer.
+///
+/// El texto pasa por el intérprete de secuencias ANSI, pero como el escritor
+/// no guarda el contenido de la pantalla solo aplica las que tienen sentido
+/// sobre píxeles: el color de primer plano, el retorno de carro y el
+/// posicionamiento absoluto del cursor. El resto se descartan.
 impl fmt::Write for FramebufferWriter {
     fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut parser = core::mem::replace(&mut self.parser, Parser::new());
         for c in s.chars() {
-            self.write_char(c);
+            parser.advance(c, |action| match action {
+                Action::Print(c) | Action::Control(c @ '\n') => self.write_char(c),
+                Action::Control('\r') => self.x_pos = TEXT_MARGIN,
+                Action::Foreground(color) => self.color = color.unwrap_or(colors::TEXT_PRIMARY),
+                Action::CursorPosition(column, row) => {
+                    let size = PROFONT_14_POINT.character_size;
+                    self.x_pos = TEXT_MARGIN + column * size.width as usize;
+                    self.y_pos = TEXT_MARGIN + row * size.height as usize;
+                }
+                _ => {}
+            });
         }
+        self.parser = parser;
         Ok(())
     }
 }
@@ -290,16 +538,52 @@ impl DrawTarget for FramebufferWriter {
     where
         I: IntoIterator<Item = Pixel<Self::Color>>,
     {
+        // Se anota como modificado el rectángulo que contiene todos los píxeles dibujados.
+        let mut bounds: Option<Rect> = None;
         for Pixel(coord, color) in pixels.into_iter() {
             // `coord` es un `Point` con coordenadas i32. Las convertimos a usize.
             // Hacemos una comprobación para evitar un pánico si las coordenadas son negativas.
             if coord.x >= 0 && coord.y >= 0 {
                 let x = coord.x as usize;
                 let y = coord.y as usize;
-                let color_u32 = (color.r() as u32) << 16 | (color.g() as u32) << 8 | color.b() as u32;
-                self.write_pixel(x, y, color_u32);
+                self.put_pixel(x, y, from_rgb888(color));
+                let pixel = Rect::new(x, y, 1, 1);
+                bounds = Some(bounds.map_or(pixel, |bounds| bounds.union(&pixel)));
+            }
+        }
+        if let Some(bounds) = bounds {
+            self.mark_dirty(bounds);
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        // Los colores recorren `area` entera por filas; solo se dibujan los
+        // que caen dentro de la pantalla.
+        let Some(visible) = to_rect(&area.intersection(&self.bounding_box())) else { return Ok(()) };
+        let (format, pitch, bpp) = (self.format, self.pitch, self.bytes_per_pixel);
+        let mut colors = colors.into_iter();
+        let surface = self.surface();
+        'rows: for y in area.rows() {
+            for x in area.columns() {
+                let Some(color) = colors.next() else { break 'rows };
+                if x >= 0 && y >= 0 && visible.contains(x as usize, y as usize) {
+                    let (x, y) = (x as usize, y as usize);
+                    store(surface, y * pitch + x * bpp, format.encode(from_rgb888(color), x, y), bpp);
+                }
             }
         }
+        self.mark_dirty(visible);
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        if let Some(rect) = to_rect(&area.intersection(&self.bounding_box())) {
+            self.fill(rect, from_rgb888(color));
+        }
         Ok(())
     }
 }